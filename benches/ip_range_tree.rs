@@ -0,0 +1,125 @@
+//! Compares the interval tree and the prefix trie on the RIS and VRP data
+//! in test/20190304, for the queries used when validating announcements
+//! and when looking up the announcements for VRPs.
+//!
+//! Run with: cargo bench --bench ip_range_tree
+//!
+//! The number of results is printed next to the time, and should be the
+//! same for both.
+extern crate secure_routing_stats;
+
+use secure_routing_stats::announcements::Announcements;
+use secure_routing_stats::ip::IpPrefix;
+use secure_routing_stats::ip::IpRangeTreeBuilder;
+use secure_routing_stats::vrps::Vrps;
+use std::path::PathBuf;
+use std::time::Instant;
+
+fn time<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let res = f();
+    println!("{:<48} {:>8.3}s", name, start.elapsed().as_secs_f64());
+    res
+}
+
+fn count(name: &str, f: impl FnOnce() -> usize) {
+    let start = Instant::now();
+    let res = f();
+    println!(
+        "{:<48} {:>8.3}s {:>10} results",
+        name,
+        start.elapsed().as_secs_f64(),
+        res
+    );
+}
+
+fn builder(prefixes: &[IpPrefix]) -> IpRangeTreeBuilder<IpPrefix> {
+    let mut builder = IpRangeTreeBuilder::empty();
+    for prefix in prefixes {
+        builder.add(prefix.clone());
+    }
+    builder
+}
+
+fn main() {
+    let announcements = Announcements::from_ris(&[
+        PathBuf::from("test/20190304/riswhoisdump.IPv4"),
+        PathBuf::from("test/20190304/riswhoisdump.IPv6"),
+    ])
+    .unwrap();
+    let vrps = Vrps::from_file(&PathBuf::from("test/20190304/vrps.csv")).unwrap();
+
+    let announced: Vec<IpPrefix> = announcements.iter().map(|a| a.prefix().clone()).collect();
+    let authorised: Vec<IpPrefix> = vrps.iter().map(|v| v.prefix().clone()).collect();
+    println!(
+        "{} announcements, {} VRPs\n",
+        announced.len(),
+        authorised.len()
+    );
+
+    let vrp_tree = time("interval tree: build VRPs", || {
+        builder(&authorised).build_interval_tree()
+    });
+    let vrp_trie = time("prefix trie:   build VRPs", || {
+        builder(&authorised).build_prefix_trie()
+    });
+    let ann_tree = time("interval tree: build announcements", || {
+        builder(&announced).build_interval_tree()
+    });
+    let ann_trie = time("prefix trie:   build announcements", || {
+        builder(&announced).build_prefix_trie()
+    });
+    println!();
+
+    count("interval tree: VRPs covering announcements", || {
+        announced
+            .iter()
+            .map(|p| vrp_tree.matching_or_less_specific(p.as_ref()).len())
+            .sum()
+    });
+    count("prefix trie:   VRPs covering announcements", || {
+        announced
+            .iter()
+            .map(|p| vrp_trie.matching_or_less_specific(p.as_ref()).len())
+            .sum()
+    });
+
+    count("interval tree: most specific VRP", || {
+        announced
+            .iter()
+            .filter(|p| vrp_tree.most_specific_match(p.as_ref()).is_some())
+            .count()
+    });
+    count("prefix trie:   most specific VRP", || {
+        announced
+            .iter()
+            .filter(|p| vrp_trie.most_specific_match(p.as_ref()).is_some())
+            .count()
+    });
+
+    count("interval tree: announcements in VRPs", || {
+        authorised
+            .iter()
+            .map(|p| ann_tree.matching_or_more_specific(p.as_ref()).len())
+            .sum()
+    });
+    count("prefix trie:   announcements in VRPs", || {
+        authorised
+            .iter()
+            .map(|p| ann_trie.matching_or_more_specific(p.as_ref()).len())
+            .sum()
+    });
+
+    count("interval tree: announcements intersecting VRPs", || {
+        authorised
+            .iter()
+            .map(|p| ann_tree.intersecting(p.as_ref()).len())
+            .sum()
+    });
+    count("prefix trie:   announcements intersecting VRPs", || {
+        authorised
+            .iter()
+            .map(|p| ann_trie.intersecting(p.as_ref()).len())
+            .sum()
+    });
+}
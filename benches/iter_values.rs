@@ -0,0 +1,111 @@
+//! Shows the allocations saved by iterating over the values in the range
+//! trees, instead of collecting them with 'all' or 'in_scope', using the
+//! RIS and VRP data in test/20190304.
+//!
+//! Run with: cargo bench --bench iter_values
+//!
+//! Allocations are counted by a global allocator that wraps the system
+//! allocator, so the numbers include everything allocated while running
+//! the closure.
+extern crate secure_routing_stats;
+
+use secure_routing_stats::announcements::Announcements;
+use secure_routing_stats::delegations::IpDelegations;
+use secure_routing_stats::report::world::WorldStatsReporter;
+use secure_routing_stats::report::ScopeLimits;
+use secure_routing_stats::vrps::Vrps;
+use std::alloc::GlobalAlloc;
+use std::alloc::Layout;
+use std::alloc::System;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn measure<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let res = f();
+    println!(
+        "{:<40} {:>8.3}s {:>12} bytes allocated",
+        name,
+        start.elapsed().as_secs_f64(),
+        ALLOCATED.load(Ordering::Relaxed) - before
+    );
+    res
+}
+
+fn main() {
+    let announcements = Announcements::from_ris(&[
+        PathBuf::from("test/20190304/riswhoisdump.IPv4"),
+        PathBuf::from("test/20190304/riswhoisdump.IPv6"),
+    ])
+    .unwrap();
+    let vrps = Vrps::from_file(&PathBuf::from("test/20190304/vrps.csv")).unwrap();
+    let delegations =
+        IpDelegations::from_file(&PathBuf::from("test/20190304/delegated-extended.txt")).unwrap();
+    let scope = ScopeLimits::empty();
+
+    let lengths = |total: u64, length: u8| total + u64::from(length);
+
+    measure("announcements: all", || {
+        announcements
+            .all()
+            .iter()
+            .map(|a| a.prefix().length())
+            .fold(0, lengths)
+    });
+    measure("announcements: iter", || {
+        announcements
+            .iter()
+            .map(|a| a.prefix().length())
+            .fold(0, lengths)
+    });
+    measure("announcements: in_scope", || {
+        announcements
+            .in_scope(&scope)
+            .iter()
+            .map(|a| a.prefix().length())
+            .fold(0, lengths)
+    });
+    measure("announcements: iter_in_scope", || {
+        announcements
+            .iter_in_scope(&scope)
+            .map(|a| a.prefix().length())
+            .fold(0, lengths)
+    });
+    println!();
+
+    measure("vrps: all", || {
+        vrps.all()
+            .iter()
+            .map(|v| v.prefix().length())
+            .fold(0, lengths)
+    });
+    measure("vrps: iter", || {
+        vrps.iter().map(|v| v.prefix().length()).fold(0, lengths)
+    });
+    println!();
+
+    measure("world: analyse", || {
+        WorldStatsReporter::new(&announcements, &vrps, &delegations).analyse(&scope)
+    });
+}
@@ -1,16 +1,23 @@
-//! Support parsing announcements, for the moment only from RIS
+//! Support parsing announcements from RIS, or from Routeviews or PCH
+//! snapshots converted to JSON.
 //!
 //! http://www.ris.ripe.net/dumps/riswhoisdump.IPv4.gz
 
+use crate::input;
+use crate::input::Progress;
 use crate::ip::Asn;
 use crate::ip::AsnError;
 use crate::ip::IpPrefix;
 use crate::ip::IpPrefixError;
 use crate::ip::IpRange;
-use crate::ip::IpRangeTree;
+use crate::ip::IpRangeOverlay;
 use crate::ip::IpRangeTreeBuilder;
 use crate::report::ScopeLimits;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use std::fmt;
 use std::fmt::Display;
+use std::fmt::Write;
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
@@ -21,7 +28,7 @@ use std::str::FromStr;
 
 //------------ Announcement --------------------------------------------------
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
 pub struct Announcement {
     asn: Asn,
     prefix: IpPrefix,
@@ -55,6 +62,12 @@ impl FromStr for Announcement {
     }
 }
 
+impl Display for Announcement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.asn, self.prefix)
+    }
+}
+
 impl AsRef<IpRange> for Announcement {
     fn as_ref(&self) -> &IpRange {
         self.prefix.as_ref()
@@ -63,21 +76,64 @@ impl AsRef<IpRange> for Announcement {
 
 //------------ Announcements -------------------------------------------------
 
+/// The number of updates that are kept in an overlay on the tree, before
+/// it is rebuilt.
+pub const COMPACT_AFTER_CHANGES: usize = 10_000;
+
+/// Announcements must be seen by at least this many RIS peers by default.
+/// Those seen by fewer are often leaks or very short lived.
+pub const DEFAULT_MIN_PEERS: u32 = 6;
+
 #[derive(Debug)]
 pub struct Announcements {
-    tree: IpRangeTree<Announcement>,
+    tree: IpRangeOverlay<Announcement>,
+    data_date: Option<NaiveDate>,
 }
 
 impl Announcements {
+    /// Parses the date from a RIS dump comment line like:
+    /// "% This file was generated at Mon Mar  4 10:03:01 UTC 2019."
+    fn parse_ris_date(line: &str) -> Option<NaiveDate> {
+        let marker = "This file was generated at";
+        let start = line.find(marker)? + marker.len();
+        let date_str = line[start..].trim().trim_end_matches('.');
+        let date_str: Vec<&str> = date_str.split_whitespace().collect();
+
+        NaiveDateTime::parse_from_str(&date_str.join(" "), "%a %b %d %H:%M:%S UTC %Y")
+            .ok()
+            .map(|dt| dt.date())
+    }
+
+    /// Parses the RIS file into the builder, see 'parse_ris_reader'.
     fn parse_ris_file(
         builder: &mut IpRangeTreeBuilder<Announcement>,
         path: &Path,
-    ) -> Result<(), Error> {
-        let file = File::open(path).map_err(|_| Error::read_error(path))?;
-        let reader = BufReader::new(file);
+        min_peers: u32,
+        progress: Progress,
+    ) -> Result<(Option<NaiveDate>, usize), Error> {
+        let reader = input::open(path, progress).map_err(|_| Error::read_error(path))?;
+        Self::parse_ris_reader(builder, reader, min_peers)
+    }
+
+    /// Parses the RIS dump lines into the builder, and returns the date
+    /// found in its header, if any, and the number of announcements added.
+    /// Lines seen by fewer than 'min_peers' peers are skipped.
+    fn parse_ris_reader<R: BufRead>(
+        builder: &mut IpRangeTreeBuilder<Announcement>,
+        reader: R,
+        min_peers: u32,
+    ) -> Result<(Option<NaiveDate>, usize), Error> {
+        let mut date = None;
+        let mut added = 0;
         for lres in reader.lines() {
             let line = lres.map_err(Error::parse_error)?;
-            if line.is_empty() || line.starts_with('%') {
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('%') {
+                if date.is_none() {
+                    date = Self::parse_ris_date(&line);
+                }
                 continue;
             }
 
@@ -87,7 +143,7 @@ impl Announcements {
             let prefix_str = values.next().ok_or(Error::MissingColumn)?;
             let peers = values.next().ok_or(Error::MissingColumn)?;
 
-            if u32::from_str(peers)? <= 5 {
+            if u32::from_str(peers)? < min_peers {
                 continue;
             }
 
@@ -101,43 +157,224 @@ impl Announcements {
             let ann = Announcement { asn, prefix };
 
             builder.add(ann);
+            added += 1;
         }
-        Ok(())
+
+        Ok((date, added))
     }
 
+    /// Reads announcements from RIS dump files. A file without usable
+    /// announcements is accepted, e.g. an IPv6 dump for a setup that only
+    /// uses IPv4, as long as the files combined have some. A path of '-'
+    /// reads from stdin.
+    ///
+    /// Announcements seen by fewer than DEFAULT_MIN_PEERS RIS peers are
+    /// ignored.
     pub fn from_ris(paths: &[PathBuf]) -> Result<Self, Error> {
+        Self::from_ris_with_threshold(paths, DEFAULT_MIN_PEERS)
+    }
+
+    /// Like 'from_ris', but ignores announcements seen by fewer than
+    /// 'min_peers' RIS peers. Use 0 to keep all announcements.
+    pub fn from_ris_with_threshold(paths: &[PathBuf], min_peers: u32) -> Result<Self, Error> {
+        Self::from_ris_with_progress(paths, min_peers, Progress::Silent)
+    }
+
+    /// Like 'from_ris_with_threshold', but logs the lines read from each
+    /// file if progress is enabled.
+    pub fn from_ris_with_progress(
+        paths: &[PathBuf],
+        min_peers: u32,
+        progress: Progress,
+    ) -> Result<Self, Error> {
         let mut builder = IpRangeTreeBuilder::empty();
+        let mut data_date: Option<NaiveDate> = None;
+        let mut added = 0;
 
         for path in paths {
-            Self::parse_ris_file(&mut builder, path)?;
+            let (file_date, file_added) =
+                Self::parse_ris_file(&mut builder, path, min_peers, progress)?;
+            added += file_added;
+
+            // Keep the oldest date if files were generated at different times.
+            data_date = match (data_date, file_date) {
+                (Some(d), Some(f)) => Some(::std::cmp::min(d, f)),
+                (d, f) => d.or(f),
+            };
+        }
+
+        if added == 0 {
+            let paths: Vec<String> = paths
+                .iter()
+                .map(|p| p.to_string_lossy().to_string())
+                .collect();
+            return Err(Error::NoRecords(paths.join(", ")));
         }
 
         Ok(Announcements {
-            tree: builder.build(),
+            tree: IpRangeOverlay::new(builder.build()),
+            data_date,
         })
     }
 
+    /// Reads announcements from a RIS dump in any reader, ignoring those
+    /// seen by fewer than 'min_peers' RIS peers. Unlike 'from_ris', having no
+    /// announcements is not an error.
+    pub fn from_ris_reader<R: BufRead>(reader: R, min_peers: u32) -> Result<Self, Error> {
+        let mut builder = IpRangeTreeBuilder::empty();
+        let (data_date, _) = Self::parse_ris_reader(&mut builder, reader, min_peers)?;
+
+        Ok(Announcements {
+            tree: IpRangeOverlay::new(builder.build()),
+            data_date,
+        })
+    }
+
+    /// Reads announcements from a JSON list of objects with a 'prefix' and
+    /// an 'as_path', as found in Routeviews or PCH snapshots converted to
+    /// JSON. The last AS in the path is the origin:
+    ///
+    ///   [ { "prefix": "10.0.0.0/16", "as_path": "3356 1299 65000" } ]
+    ///
+    /// Paths that end in an AS_SET, like "3356 {65001,65002}", and empty
+    /// paths are skipped.
+    pub fn from_routeviews_json(path: &Path) -> Result<Self, Error> {
+        let file = File::open(path).map_err(|_| Error::read_error(path))?;
+        let entries: Vec<RouteviewsEntry> =
+            serde_json::from_reader(BufReader::new(file)).map_err(Error::parse_error)?;
+
+        let mut builder = IpRangeTreeBuilder::empty();
+        let mut added = 0;
+        for entry in entries {
+            let origin = match entry.as_path.split_whitespace().last() {
+                Some(origin) => origin,
+                None => continue,
+            };
+            if origin.ends_with('}') {
+                continue; // AS_SET, there is no single origin
+            }
+
+            let asn = Asn::from_str(origin)?;
+            let prefix = IpPrefix::from_str(&entry.prefix)?;
+
+            builder.add(Announcement { asn, prefix });
+            added += 1;
+        }
+
+        if added == 0 {
+            return Err(Error::no_records(path));
+        }
+
+        Ok(Announcements {
+            tree: IpRangeOverlay::new(builder.build()),
+            data_date: None,
+        })
+    }
+
+    /// Reads announcements in the CSV format produced by 'to_csv', i.e. with
+    /// the header: ASN,IP Prefix
+    pub fn from_csv(path: &Path) -> Result<Self, Error> {
+        let file = File::open(path).map_err(|_| Error::read_error(path))?;
+        let announcements = Self::from_csv_reader(BufReader::new(file))?;
+        if announcements.iter().next().is_none() {
+            return Err(Error::no_records(path));
+        }
+        Ok(announcements)
+    }
+
+    /// Reads announcements in the CSV format produced by 'to_csv' from any
+    /// reader. The header is optional.
+    pub fn from_csv_reader<R: BufRead>(reader: R) -> Result<Self, Error> {
+        let mut builder = IpRangeTreeBuilder::empty();
+        for lres in reader.lines() {
+            let line = lres.map_err(Error::parse_error)?;
+            if line.trim().is_empty() || line.starts_with("ASN,") {
+                continue;
+            }
+            builder.add(Announcement::from_str(&line)?);
+        }
+
+        Ok(Announcements {
+            tree: IpRangeOverlay::new(builder.build()),
+            data_date: None,
+        })
+    }
+
+    /// Applies a batch of updates, like from a BGP update stream. As in a
+    /// BGP UPDATE message, withdrawals are processed first, so a prefix that
+    /// is withdrawn and announced in the same batch stays announced. A
+    /// withdrawal removes the prefix for all origins. Adding an announcement
+    /// that is already present has no effect.
+    ///
+    /// The updates are kept in an overlay on the tree, so that the tree
+    /// does not need to be rebuilt for each batch. The tree is rebuilt with
+    /// the updates once there are more than COMPACT_AFTER_CHANGES of them.
+    pub fn apply_updates(&mut self, adds: &[Announcement], withdraws: &[IpPrefix]) {
+        for prefix in withdraws {
+            self.tree.remove(prefix.as_ref());
+        }
+
+        for ann in adds {
+            if !self.tree.matching_exact(ann.as_ref()).contains(&ann) {
+                self.tree.add(ann.clone());
+            }
+        }
+
+        if self.tree.changes() > COMPACT_AFTER_CHANGES {
+            self.tree.compact();
+        }
+    }
+
+    /// Returns the date on which the RIS dump was generated, according to
+    /// its header. If multiple dumps were read, the oldest date is returned.
+    pub fn data_date(&self) -> Option<NaiveDate> {
+        self.data_date
+    }
+
     pub fn all(&self) -> Vec<&Announcement> {
         self.tree.all()
     }
 
+    /// Iterates over all announcements, without collecting them first.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = &'a Announcement> + 'a {
+        self.tree.iter_values()
+    }
+
     pub fn in_scope(&self, scope: &ScopeLimits) -> Vec<&Announcement> {
-        let mut anns = if scope.limits_ips() {
-            let ranges = scope.ips().ranges();
-            ranges
-                .iter()
-                .flat_map(|range| self.contained_by(range))
-                .collect()
+        self.iter_in_scope(scope).collect()
+    }
+
+    /// Iterates over the announcements in scope. Without IP limits in the
+    /// scope, this does not collect the announcements first.
+    pub fn iter_in_scope<'a, 's>(
+        &'a self,
+        scope: &'s ScopeLimits,
+    ) -> impl Iterator<Item = &'a Announcement> + 's
+    where
+        'a: 's,
+    {
+        let asn_set = scope.asns();
+        let in_limits = move |ann: &&Announcement| {
+            (!scope.limits_asns() || asn_set.contains(ann.asn()))
+                && scope.includes_family(ann.prefix().ip_address_family())
+        };
+
+        let (ranges, all) = if scope.limits_ips() {
+            (scope.ips().ranges().as_slice(), None)
         } else {
-            self.all()
+            (&[][..], Some(self.iter()))
         };
 
-        if scope.limits_asns() {
-            let asn_set = &scope.asns();
-            anns.retain(|ann| asn_set.contains(ann.asn()));
-        }
+        ranges
+            .iter()
+            .flat_map(move |range| self.contained_by(range))
+            .chain(all.into_iter().flatten())
+            .filter(in_limits)
+    }
 
-        anns
+    /// Returns all announcements that overlap the given range.
+    pub fn intersecting(&self, range: &IpRange) -> Vec<&Announcement> {
+        self.tree.intersecting(range)
     }
 
     /// Matches announcements that match the given range exactly, or which
@@ -145,6 +382,32 @@ impl Announcements {
     pub fn contained_by(&self, range: &IpRange) -> Vec<&Announcement> {
         self.tree.matching_or_more_specific(range)
     }
+
+    /// Matches announcements that match the given range exactly, or which
+    /// are less specific (i.e. the covering aggregates).
+    pub fn covering(&self, range: &IpRange) -> Vec<&Announcement> {
+        self.tree.matching_or_less_specific(range)
+    }
+
+    /// Returns all announcements as CSV, with the header: ASN,IP Prefix
+    /// Every other line can be parsed as an Announcement again.
+    pub fn to_csv(&self) -> String {
+        let mut s = String::new();
+        writeln!(s, "ASN,IP Prefix").unwrap();
+        for ann in self.all() {
+            writeln!(s, "{},{}", ann.asn(), ann.prefix()).unwrap();
+        }
+        s
+    }
+}
+
+//------------ RouteviewsEntry ----------------------------------------------
+
+/// A route in a Routeviews or PCH snapshot converted to JSON.
+#[derive(Deserialize)]
+struct RouteviewsEntry {
+    prefix: String,
+    as_path: String,
 }
 
 //------------ Error --------------------------------------------------------
@@ -159,12 +422,18 @@ pub enum Error {
 
     #[display(fmt = "Error parsing announcements: {}", _0)]
     ParseError(String),
+
+    #[display(fmt = "No usable records in: {}", _0)]
+    NoRecords(String),
 }
 
 impl Error {
     fn read_error(path: &Path) -> Self {
         Error::CannotRead(path.to_string_lossy().to_string())
     }
+    fn no_records(path: &Path) -> Self {
+        Error::NoRecords(path.to_string_lossy().to_string())
+    }
     fn parse_error(e: impl Display) -> Self {
         Error::ParseError(format!("{}", e))
     }
@@ -193,6 +462,7 @@ impl From<AsnError> for Error {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Cursor;
 
     #[test]
     fn should_read_from_file() {
@@ -217,4 +487,279 @@ mod tests {
 
         assert_eq!(1, announcements.contained_by(test_v6_ann.as_ref()).len())
     }
+
+    #[test]
+    fn should_apply_min_peers_threshold() {
+        let paths = vec![PathBuf::from("test/small/riswhoisdump.IPv4")];
+
+        let default = Announcements::from_ris(&paths).unwrap();
+        let all = Announcements::from_ris_with_threshold(&paths, 0).unwrap();
+
+        assert_eq!(5, default.all().len());
+        assert_eq!(6, all.all().len());
+        assert!(sorted_strings(&all).contains(&"AS65000 10.1.0.0/16".to_string()));
+        assert!(!sorted_strings(&default).contains(&"AS65000 10.1.0.0/16".to_string()));
+
+        // The threshold itself is enough.
+        let seen_by_ten = Announcements::from_ris_with_threshold(&paths, 10).unwrap();
+        assert_eq!(5, seen_by_ten.all().len());
+
+        match Announcements::from_ris_with_threshold(&paths, 11) {
+            Err(Error::NoRecords(_)) => {}
+            _ => panic!("Expected NoRecords error"),
+        }
+    }
+
+    #[test]
+    fn should_read_routeviews_json() {
+        let path = PathBuf::from("test/routeviews/announcements.json");
+        let announcements = Announcements::from_routeviews_json(&path).unwrap();
+
+        assert_eq!(
+            vec![
+                "AS65000 10.0.0.0/16",
+                "AS65000 2001:db8::/32",
+                "AS65001 10.0.1.0/24",
+                "AS65002 10.1.0.0/16",
+            ],
+            sorted_strings(&announcements)
+        );
+        assert_eq!(None, announcements.data_date());
+    }
+
+    #[test]
+    fn should_reject_files_without_announcements() {
+        for path in &["test/empty/empty", "test/empty/riswhoisdump.IPv4"] {
+            match Announcements::from_ris(&[PathBuf::from(path)]) {
+                Err(Error::NoRecords(p)) => assert_eq!(*path, p),
+                _ => panic!("Expected NoRecords error for {}", path),
+            }
+        }
+
+        match Announcements::from_ris(&[
+            PathBuf::from("test/empty/empty"),
+            PathBuf::from("test/empty/riswhoisdump.IPv4"),
+        ]) {
+            Err(Error::NoRecords(p)) => {
+                assert_eq!("test/empty/empty, test/empty/riswhoisdump.IPv4", p)
+            }
+            _ => panic!("Expected NoRecords error"),
+        }
+    }
+
+    #[test]
+    fn should_accept_empty_file_if_others_have_announcements() {
+        let announcements = Announcements::from_ris(&[
+            PathBuf::from("test/small/riswhoisdump.IPv4"),
+            PathBuf::from("test/empty/riswhoisdump.IPv4"),
+        ])
+        .unwrap();
+        assert_eq!(5, announcements.all().len());
+    }
+
+    #[test]
+    fn should_find_covering_announcements() {
+        let announcements = Announcements::from_ris(&[
+            PathBuf::from("test/small/riswhoisdump.IPv4"),
+            PathBuf::from("test/small/riswhoisdump.IPv6"),
+        ])
+        .unwrap();
+
+        let covering = |s| {
+            let prefix = IpPrefix::from_str(s).unwrap();
+            let mut res: Vec<String> = announcements
+                .covering(prefix.as_ref())
+                .iter()
+                .map(|a| a.to_string())
+                .collect();
+            res.sort();
+            res
+        };
+
+        assert_eq!(
+            vec!["AS65000 10.0.0.0/16", "AS65000 10.0.1.0/24"],
+            covering("10.0.1.0/24")
+        );
+        assert_eq!(vec!["AS65000 10.0.0.0/16"], covering("10.0.3.0/24"));
+        assert_eq!(vec!["AS65000 2001:db8::/32"], covering("2001:db8:2::/48"));
+        assert!(covering("10.0.0.0/8").is_empty());
+    }
+
+    #[test]
+    fn should_read_ris_from_reader() {
+        let dump = "% This file was generated at Mon Mar  4 10:03:01 UTC 2019.\n\
+                    65000\t10.0.0.0/16\t10\n\
+                    65001\t10.0.1.0/24\t3\n\
+                    {65002,65003}\t10.0.2.0/24\t10\n";
+
+        let announcements = Announcements::from_ris_reader(Cursor::new(dump), 5).unwrap();
+        let all: Vec<String> = announcements.iter().map(ToString::to_string).collect();
+        assert_eq!(vec!["AS65000 10.0.0.0/16"], all);
+        assert_eq!(
+            Some(NaiveDate::from_ymd_opt(2019, 3, 4).unwrap()),
+            announcements.data_date()
+        );
+
+        let announcements = Announcements::from_ris_reader(Cursor::new(dump), 0).unwrap();
+        assert_eq!(2, announcements.iter().count());
+
+        let empty = Announcements::from_ris_reader(Cursor::new(""), 0).unwrap();
+        assert_eq!(0, empty.iter().count());
+
+        assert!(Announcements::from_ris_reader(Cursor::new("65000\t10.0.0.0/16\n"), 0).is_err());
+    }
+
+    #[test]
+    fn should_parse_data_date_from_header() {
+        let v4_path = || PathBuf::from("test/small/riswhoisdump.IPv4");
+        let v6_path = || PathBuf::from("test/small/riswhoisdump.IPv6");
+
+        let announcements = Announcements::from_ris(&[v4_path()]).unwrap();
+        assert_eq!(
+            Some(NaiveDate::from_ymd_opt(2019, 3, 4).unwrap()),
+            announcements.data_date()
+        );
+
+        // The v6 fixture has no date in its header
+        let announcements = Announcements::from_ris(&[v6_path()]).unwrap();
+        assert_eq!(None, announcements.data_date());
+
+        let announcements = Announcements::from_ris(&[v6_path(), v4_path()]).unwrap();
+        assert_eq!(
+            Some(NaiveDate::from_ymd_opt(2019, 3, 4).unwrap()),
+            announcements.data_date()
+        );
+
+        assert_eq!(
+            Some(NaiveDate::from_ymd_opt(2019, 12, 24).unwrap()),
+            Announcements::parse_ris_date(
+                "% This file was generated at Tue Dec 24 08:00:01 UTC 2019."
+            )
+        );
+        assert_eq!(None, Announcements::parse_ris_date("% Format: <origin>"));
+    }
+
+    fn sorted_strings(announcements: &Announcements) -> Vec<String> {
+        let mut res: Vec<String> = announcements
+            .all()
+            .iter()
+            .map(|a| format!("{} {}", a.asn(), a.prefix()))
+            .collect();
+        res.sort();
+        res
+    }
+
+    #[test]
+    fn should_reparse_exported_csv() {
+        let paths = vec![
+            PathBuf::from("test/small/riswhoisdump.IPv4"),
+            PathBuf::from("test/small/riswhoisdump.IPv6"),
+        ];
+        let announcements = Announcements::from_ris(&paths).unwrap();
+
+        let csv = announcements.to_csv();
+        assert!(csv.starts_with("ASN,IP Prefix\n"));
+
+        let reparsed = Announcements::from_csv_reader(csv.as_bytes()).unwrap();
+
+        assert_eq!(7, reparsed.all().len());
+        assert_eq!(sorted_strings(&announcements), sorted_strings(&reparsed));
+        assert_eq!(csv, reparsed.to_csv());
+    }
+
+    #[test]
+    fn should_reload_exported_csv_file() {
+        let announcements =
+            Announcements::from_ris(&[PathBuf::from("test/small/riswhoisdump.IPv4")]).unwrap();
+
+        let path = ::std::env::temp_dir().join("secure_routing_stats_announcements_export.csv");
+        ::std::fs::write(&path, announcements.to_csv()).unwrap();
+        let reloaded = Announcements::from_csv(&path).unwrap();
+        ::std::fs::write(&path, "ASN,IP Prefix\n").unwrap();
+        let empty = Announcements::from_csv(&path);
+        ::std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(announcements.to_csv(), reloaded.to_csv());
+        match empty {
+            Err(Error::NoRecords(_)) => {}
+            _ => panic!("Expected NoRecords error"),
+        }
+    }
+
+    #[test]
+    fn should_apply_updates() {
+        let mut announcements = Announcements::from_ris(&[
+            PathBuf::from("test/small/riswhoisdump.IPv4"),
+            PathBuf::from("test/small/riswhoisdump.IPv6"),
+        ])
+        .unwrap();
+
+        let ann = |s| Announcement::from_str(s).unwrap();
+        let prefix = |s| IpPrefix::from_str(s).unwrap();
+        let origins = |announcements: &Announcements, s| -> Vec<String> {
+            let mut res: Vec<String> = announcements
+                .contained_by(prefix(s).as_ref())
+                .iter()
+                .map(|a| a.to_string())
+                .collect();
+            res.sort();
+            res
+        };
+
+        announcements.apply_updates(
+            &[ann("AS65009, 10.9.0.0/24"), ann("AS65000, 10.0.0.0/16")],
+            &[],
+        );
+        assert_eq!(8, announcements.all().len());
+        assert_eq!(
+            vec!["AS65009 10.9.0.0/24"],
+            origins(&announcements, "10.9.0.0/16")
+        );
+        assert_eq!(
+            Some(NaiveDate::from_ymd_opt(2019, 3, 4).unwrap()),
+            announcements.data_date()
+        );
+
+        announcements.apply_updates(&[], &[prefix("10.9.0.0/24"), prefix("10.0.1.0/24")]);
+        assert_eq!(6, announcements.all().len());
+        assert!(origins(&announcements, "10.9.0.0/16").is_empty());
+        assert_eq!(
+            vec!["AS65000 10.0.0.0/16", "AS65001 10.0.2.0/24"],
+            origins(&announcements, "10.0.0.0/16")
+        );
+
+        // Withdrawals go first, so the prefix is announced again
+        announcements.apply_updates(&[ann("AS65002, 10.0.1.0/24")], &[prefix("10.0.1.0/24")]);
+        assert_eq!(
+            vec!["AS65002 10.0.1.0/24"],
+            origins(&announcements, "10.0.1.0/24")
+        );
+
+        // Folding the updates into the tree keeps the same announcements
+        let before = origins(&announcements, "0.0.0.0/0");
+        announcements.tree.compact();
+        assert_eq!(before, origins(&announcements, "0.0.0.0/0"));
+        assert_eq!(7, announcements.all().len());
+    }
+
+    #[test]
+    fn v6_scope_should_exclude_v4_announcements() {
+        let path = PathBuf::from("test/routeviews/announcements.json");
+        let announcements = Announcements::from_routeviews_json(&path).unwrap();
+
+        let in_scope = |scope: &str| {
+            let scope = ScopeLimits::from_str(scope).unwrap();
+            let mut res: Vec<String> = announcements
+                .in_scope(&scope)
+                .iter()
+                .map(|a| format!("{} {}", a.asn(), a.prefix()))
+                .collect();
+            res.sort();
+            res
+        };
+
+        assert_eq!(vec!["AS65000 2001:db8::/32"], in_scope("ipv6"));
+        assert_eq!(vec!["AS65000 10.0.0.0/16"], in_scope("ipv4, AS65000"));
+        assert!(in_scope("ipv6, 10.0.0.0/8").is_empty());
+    }
 }
@@ -0,0 +1,677 @@
+//! The command line interface: the sub-commands and their arguments. The
+//! binary and the tests parse their arguments with the same definitions.
+use clap::App;
+use clap::Arg;
+use clap::SubCommand;
+
+/// Returns the application with all sub-commands.
+pub fn app<'a, 'b>() -> App<'a, 'b> {
+    App::new("NLnet Labs RRDP Server")
+        .version("0.1b")
+        .about("Analyse ROA quality vs BGP")
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .value_name("FILE")
+                .help("TOML config file. Command line options override its values.")
+                .global(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("progress")
+                .long("progress")
+                .help("Log the number of lines read from the input files")
+                .global(true)
+                .required(false),
+        )
+        .subcommand(
+            SubCommand::with_name("world")
+                .about("Report ROA quality on a per country basis")
+                .arg(
+                    Arg::with_name("announcements")
+                        .short("a")
+                        .long("announcements")
+                        .value_name("FILE")
+                        .help("RIS dump file(s)")
+                        .required(false)
+                        .min_values(1),
+                )
+                .arg(
+                    Arg::with_name("min_peers")
+                        .long("min-peers")
+                        .value_name("NUMBER")
+                        .help("Ignore announcements seen by fewer than this many RIS peers. Default: 6")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("vrps")
+                        .short("v")
+                        .long("vrps")
+                        .value_name("FILE")
+                        .help("Validated ROAs Payloads CSV file.")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("slurm")
+                        .short("s")
+                        .long("slurm")
+                        .value_name("FILE")
+                        .help("SLURM (RFC 8416) file with local exceptions to the VRPs.")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("delegations")
+                        .short("d")
+                        .long("delegations")
+                        .value_name("FILE")
+                        .help("Delegation stats (NRO extended delegated stats format).")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("ips")
+                        .short("i")
+                        .long("ips")
+                        .value_name("comma separated prefixes/ranges")
+                        .help("Optional scope for world report. Default: all")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("asns")
+                        .short("x")
+                        .long("asns")
+                        .value_name("comma separated ASNs / ASN ranges")
+                        .help("Optional scope for world report. Default: all")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("html_dir")
+                        .long("html-dir")
+                        .value_name("DIR")
+                        .help("Write the world map with its assets to this directory, instead of printing the report")
+                        .conflicts_with("format")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("threads")
+                        .short("t")
+                        .long("threads")
+                        .value_name("NUMBER")
+                        .help("Number of threads to use. Default: number of CPUs")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("stats")
+                        .long("stats")
+                        .help("Print a short summary of the run to stderr")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("denominator")
+                        .long("denominator")
+                        .value_name("all | assigned")
+                        .help("Count all announcements for adoption, or only those for assigned space. Default: all")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("ignore_max_length")
+                        .long("ignore-maxlength")
+                        .help("Treat max length as advisory: only the ASN has to match")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("exclude_dark_vrps")
+                        .long("exclude-dark-vrps")
+                        .help("Leave VRPs for space without any announcement out of the seen and unseen VRP counts")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("by")
+                        .long("by")
+                        .value_name("country | rir")
+                        .help("Group the stats per country or per RIR, defaults to country. RIR only supports json and text")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("deterministic")
+                        .long("deterministic")
+                        .help("Sort JSON output, so that runs on the same input can be diffed")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .value_name("json | text | not-found | geojson | cross-rir | tsv")
+                        .help("Specify output format, defaults to json")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("resources")
+                .about("Report ROA quality on a resource basis")
+                .arg(
+                    Arg::with_name("announcements")
+                        .short("a")
+                        .long("announcements")
+                        .value_name("FILE")
+                        .help("RIS dump file(s)")
+                        .required(false)
+                        .min_values(1),
+                )
+                .arg(
+                    Arg::with_name("min_peers")
+                        .long("min-peers")
+                        .value_name("NUMBER")
+                        .help("Ignore announcements seen by fewer than this many RIS peers. Default: 6")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("vrps")
+                        .short("v")
+                        .long("vrps")
+                        .value_name("FILE")
+                        .help("Validated ROAs Payloads CSV file.")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("slurm")
+                        .short("s")
+                        .long("slurm")
+                        .value_name("FILE")
+                        .help("SLURM (RFC 8416) file with local exceptions to the VRPs.")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("ips")
+                        .short("i")
+                        .long("ips")
+                        .value_name("comma separated prefixes/ranges")
+                        .help("Optional scope for invalid report. Default: all")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("asns")
+                        .short("x")
+                        .long("asns")
+                        .value_name("comma separated ASNs / ASN ranges")
+                        .help("Optional scope for invalid report. Default: all")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("family")
+                        .long("family")
+                        .value_name("ipv4|ipv6")
+                        .help("Only report on one address family. Default: both")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("stats")
+                        .long("stats")
+                        .help("Print a short summary of the run to stderr")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("ignore_max_length")
+                        .long("ignore-maxlength")
+                        .help("Treat max length as advisory: only the ASN has to match")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("now")
+                        .long("now")
+                        .value_name("TIMESTAMP")
+                        .help("Flag VRPs that are expired at this time, e.g. 2019-03-04T10:00:00Z")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("expiring_within")
+                        .long("expiring-within")
+                        .value_name("HOURS")
+                        .help("With --now, flag VRPs that expire within this time, defaults to 24")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("irr")
+                        .long("irr")
+                        .value_name("FILE")
+                        .help(
+                            "IRR route and route6 objects (RPSL) to cross-check with the VRPs",
+                        )
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("rank_by")
+                        .long("rank-by")
+                        .value_name("count | space")
+                        .help("Rank invalid origins by number of prefixes, or by address space")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("top")
+                        .long("top")
+                        .value_name("NUMBER")
+                        .help("The number of invalid origins to list, defaults to 10")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("ta")
+                        .long("ta")
+                        .value_name("TRUST ANCHOR")
+                        .help("Only report on the visibility of VRPs from this trust anchor")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("max_invalids")
+                        .long("max-invalids")
+                        .value_name("NUMBER")
+                        .help("Maximum number of invalids to list. Default: all")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .value_name("json | text | rpsl | csv | invalids-csv | summary")
+                        .help("Specify output format, defaults to json")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("daemon")
+                .about("Run as an HTTP server")
+                .arg(
+                    Arg::with_name("bind")
+                        .short("b")
+                        .long("bind")
+                        .value_name("ADDRESS:PORT")
+                        .help("Address to listen on. Default: 127.0.0.1:8080")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("denominator")
+                        .long("denominator")
+                        .value_name("all | assigned")
+                        .help("Count all announcements for adoption, or only those for assigned space. Default: all")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("log_format")
+                        .long("log-format")
+                        .value_name("text | json")
+                        .help("Log as plain text, or as JSON lines. Default: text")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("unix_socket")
+                        .long("unix-socket")
+                        .value_name("PATH")
+                        .help("Listen on a Unix domain socket instead of a TCP address")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("reload_token")
+                        .long("reload-token")
+                        .value_name("TOKEN")
+                        .help("Bearer token to allow reloads with POST /rpki-stats-api/reload. Default: disabled")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("announcements")
+                        .short("a")
+                        .long("announcements")
+                        .value_name("FILE")
+                        .help("RIS dump file(s)")
+                        .required(false)
+                        .min_values(1),
+                )
+                .arg(
+                    Arg::with_name("min_peers")
+                        .long("min-peers")
+                        .value_name("NUMBER")
+                        .help("Ignore announcements seen by fewer than this many RIS peers. Default: 6")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("vrps")
+                        .short("v")
+                        .long("vrps")
+                        .value_name("FILE")
+                        .help("Validated ROAs Payloads CSV file.")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("slurm")
+                        .short("s")
+                        .long("slurm")
+                        .value_name("FILE")
+                        .help("SLURM (RFC 8416) file with local exceptions to the VRPs.")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("delegations")
+                        .short("d")
+                        .long("delegations")
+                        .value_name("FILE")
+                        .help("Delegation stats (NRO extended delegated stats format).")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("debug")
+                .about("Show the loaded announcements, VRPs and delegations for a scope")
+                .arg(
+                    Arg::with_name("announcements")
+                        .short("a")
+                        .long("announcements")
+                        .value_name("FILE")
+                        .help("RIS dump file(s)")
+                        .required(false)
+                        .min_values(1),
+                )
+                .arg(
+                    Arg::with_name("vrps")
+                        .short("v")
+                        .long("vrps")
+                        .value_name("FILE")
+                        .help("Validated ROAs Payloads CSV file.")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("slurm")
+                        .short("s")
+                        .long("slurm")
+                        .value_name("FILE")
+                        .help("SLURM (RFC 8416) file with local exceptions to the VRPs.")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("delegations")
+                        .short("d")
+                        .long("delegations")
+                        .value_name("FILE")
+                        .help("Delegation stats (NRO extended delegated stats format).")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("ips")
+                        .short("i")
+                        .long("ips")
+                        .value_name("comma separated prefixes/ranges")
+                        .help("Show entries that overlap these resources")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("aggregate")
+                .about("Merge a list of prefixes/ranges into the minimal list of prefixes")
+                .arg(
+                    Arg::with_name("prefixes")
+                        .short("p")
+                        .long("prefixes")
+                        .value_name("FILE")
+                        .help("File with one prefix or range per line")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("lookup")
+                .about("Look up the country, registry and state of a list of prefixes")
+                .arg(
+                    Arg::with_name("delegations")
+                        .short("d")
+                        .long("delegations")
+                        .value_name("FILE")
+                        .help("Delegation stats (NRO extended delegated stats format).")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("prefixes")
+                        .short("p")
+                        .long("prefixes")
+                        .value_name("FILE")
+                        .help("File with one prefix per line")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("irr-check")
+                .about("Validate all IRR route objects against the VRPs, and list the invalids")
+                .arg(
+                    Arg::with_name("vrps")
+                        .short("v")
+                        .long("vrps")
+                        .value_name("FILE")
+                        .help("Validated ROAs Payloads CSV file.")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("slurm")
+                        .short("s")
+                        .long("slurm")
+                        .value_name("FILE")
+                        .help("SLURM (RFC 8416) file with local exceptions to the VRPs.")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("irr")
+                        .long("irr")
+                        .value_name("FILE")
+                        .help("IRR route and route6 objects (RPSL)")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("ips")
+                        .short("i")
+                        .long("ips")
+                        .value_name("comma separated prefixes/ranges")
+                        .help("Optional scope for the IRR routes. Default: all")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("asns")
+                        .short("x")
+                        .long("asns")
+                        .value_name("comma separated ASNs / ASN ranges")
+                        .help("Optional scope for the IRR routes. Default: all")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("family")
+                        .long("family")
+                        .value_name("ipv4|ipv6")
+                        .help("Only check one address family. Default: both")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("ignore_max_length")
+                        .long("ignore-maxlength")
+                        .help("Treat max length as advisory: only the ASN has to match")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .value_name("json | text")
+                        .help("Specify output format, defaults to json")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("validate")
+                .about("Validate a single prefix and origin against the VRPs")
+                .arg(
+                    Arg::with_name("vrps")
+                        .short("v")
+                        .long("vrps")
+                        .value_name("FILE")
+                        .help("Validated ROAs Payloads CSV file.")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("slurm")
+                        .short("s")
+                        .long("slurm")
+                        .value_name("FILE")
+                        .help("SLURM (RFC 8416) file with local exceptions to the VRPs.")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("ignore_max_length")
+                        .long("ignore-maxlength")
+                        .help("Treat max length as advisory: only the ASN has to match")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("prefix")
+                        .value_name("PREFIX")
+                        .help("The announced prefix, e.g. 10.0.0.0/24")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("asn")
+                        .value_name("ASN")
+                        .help("The origin ASN, e.g. AS64500")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("unreachables")
+                .about("Report the space that becomes unreachable if RPKI invalids are rejected")
+                .arg(
+                    Arg::with_name("announcements")
+                        .short("a")
+                        .long("announcements")
+                        .value_name("FILE")
+                        .help("RIS dump file(s)")
+                        .required(false)
+                        .min_values(1),
+                )
+                .arg(
+                    Arg::with_name("min_peers")
+                        .long("min-peers")
+                        .value_name("NUMBER")
+                        .help("Ignore announcements seen by fewer than this many RIS peers. Default: 6")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("vrps")
+                        .short("v")
+                        .long("vrps")
+                        .value_name("FILE")
+                        .help("Validated ROAs Payloads CSV file.")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("slurm")
+                        .short("s")
+                        .long("slurm")
+                        .value_name("FILE")
+                        .help("SLURM (RFC 8416) file with local exceptions to the VRPs.")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("ips")
+                        .short("i")
+                        .long("ips")
+                        .value_name("comma separated prefixes/ranges")
+                        .help("Optional scope for the invalid announcements. Default: all")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("asns")
+                        .short("x")
+                        .long("asns")
+                        .value_name("comma separated ASNs / ASN ranges")
+                        .help("Optional scope for the invalid announcements. Default: all")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("family")
+                        .long("family")
+                        .value_name("ipv4|ipv6")
+                        .help("Only report on one address family. Default: both")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("ignore_max_length")
+                        .long("ignore-maxlength")
+                        .help("Treat max length as advisory: only the ASN has to match")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("withdrawn")
+                        .long("withdrawn")
+                        .help("Also list the invalid announcements that leave space unreachable")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .value_name("json | text")
+                        .help("Specify output format, defaults to json")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Compare two VRP snapshots, and the announcements that change state")
+                .arg(
+                    Arg::with_name("vrps_old")
+                        .long("vrps-old")
+                        .value_name("FILE")
+                        .help("The old Validated ROAs Payloads file.")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("vrps_new")
+                        .long("vrps-new")
+                        .value_name("FILE")
+                        .help("The new Validated ROAs Payloads file.")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("announcements")
+                        .short("a")
+                        .long("announcements")
+                        .value_name("FILE")
+                        .help("Optional RIS dump file(s) to re-validate under both snapshots")
+                        .required(false)
+                        .min_values(1),
+                )
+                .arg(
+                    Arg::with_name("min_peers")
+                        .long("min-peers")
+                        .value_name("NUMBER")
+                        .help("Ignore announcements seen by fewer than this many RIS peers. Default: 6")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("ignore_max_length")
+                        .long("ignore-maxlength")
+                        .help("Treat max length as advisory: only the ASN has to match")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .value_name("json | text")
+                        .help("Specify output format, defaults to json")
+                        .required(false),
+                ),
+        )
+}
+
+/// Parses the arguments for a sub-command, given without the binary name,
+/// e.g. ["validate", "--vrps", "vrps.csv", "10.0.0.0/16", "AS65000"], and
+/// returns the matches for the sub-command. Panics if the arguments do not
+/// parse.
+#[cfg(test)]
+pub fn subcommand_matches(args: &[&str]) -> clap::ArgMatches<'static> {
+    let args = ::std::iter::once(&"secure_routing_stats").chain(args);
+    let matches = app().get_matches_from_safe(args).unwrap();
+    matches.subcommand().1.unwrap().clone()
+}
@@ -0,0 +1,397 @@
+//! Support reading input file locations and other settings from a TOML
+//! config file, so that they don't all need to be given on the command line.
+//!
+//! Example:
+//!
+//! ```toml
+//! announcements = ["riswhoisdump.IPv4", "riswhoisdump.IPv6"]
+//! vrps = "vrps.csv"
+//! delegations = "delegated-extended.txt"
+//! slurm = "slurm.json"
+//! irr = "routes.db"
+//! bind = "127.0.0.1:8080"
+//! denominator = "assigned"
+//! min_peers = 6
+//! unix_socket = "/run/secure_routing_stats.sock"
+//! log_format = "json"
+//! reload_token = "a long random string"
+//! ```
+use announcements::DEFAULT_MIN_PEERS;
+use clap::ArgMatches;
+use input;
+use logging::LogFormat;
+use report::world::AdoptionDenominator;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const DEFAULT_BIND: &str = "127.0.0.1:8080";
+
+//------------ Config --------------------------------------------------------
+
+/// The settings from a config file. Keys mirror the command line arguments.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    announcements: Option<Vec<PathBuf>>,
+    vrps: Option<PathBuf>,
+    delegations: Option<PathBuf>,
+    slurm: Option<PathBuf>,
+    irr: Option<PathBuf>,
+    bind: Option<SocketAddr>,
+    denominator: Option<AdoptionDenominator>,
+    min_peers: Option<u32>,
+    unix_socket: Option<PathBuf>,
+    log_format: Option<LogFormat>,
+    reload_token: Option<String>,
+}
+
+impl Config {
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let content = fs::read_to_string(path)
+            .map_err(|_| Error::CannotRead(path.to_string_lossy().to_string()))?;
+        Self::from_str(&content)
+    }
+
+    /// Loads the config file given with --config, if any. Values given on
+    /// the command line override the values from the file.
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, Error> {
+        let mut config = match matches.value_of("config") {
+            Some(path) => Self::from_file(&PathBuf::from(path))?,
+            None => Config::default(),
+        };
+
+        if let Some(names) = matches.values_of("announcements") {
+            config.announcements = Some(names.map(PathBuf::from).collect());
+        }
+
+        if let Some(vrps) = matches.value_of("vrps") {
+            config.vrps = Some(PathBuf::from(vrps));
+        }
+
+        if let Some(dels) = matches.value_of("delegations") {
+            config.delegations = Some(PathBuf::from(dels));
+        }
+
+        if let Some(slurm) = matches.value_of("slurm") {
+            config.slurm = Some(PathBuf::from(slurm));
+        }
+
+        if let Some(irr) = matches.value_of("irr") {
+            config.irr = Some(PathBuf::from(irr));
+        }
+
+        if let Some(bind) = matches.value_of("bind") {
+            let bind = SocketAddr::from_str(bind)
+                .map_err(|_| Error::InvalidBindAddress(bind.to_string()))?;
+            config.bind = Some(bind);
+        }
+
+        if let Some(denominator) = matches.value_of("denominator") {
+            let denominator = AdoptionDenominator::from_str(denominator)
+                .map_err(|_| Error::InvalidDenominator(denominator.to_string()))?;
+            config.denominator = Some(denominator);
+        }
+
+        if let Some(min_peers) = matches.value_of("min_peers") {
+            let min_peers = u32::from_str(min_peers)
+                .map_err(|_| Error::InvalidMinPeers(min_peers.to_string()))?;
+            config.min_peers = Some(min_peers);
+        }
+
+        if let Some(path) = matches.value_of("unix_socket") {
+            config.unix_socket = Some(PathBuf::from(path));
+        }
+
+        if let Some(format) = matches.value_of("log_format") {
+            let format = LogFormat::from_str(format)
+                .map_err(|_| Error::InvalidLogFormat(format.to_string()))?;
+            config.log_format = Some(format);
+        }
+
+        if let Some(token) = matches.value_of("reload_token") {
+            config.reload_token = Some(token.to_string());
+        }
+
+        if config.inputs().filter(|path| input::is_stdin(path)).count() > 1 {
+            return Err(Error::MultipleStdin);
+        }
+
+        Ok(config)
+    }
+
+    /// Returns all input files that are set.
+    fn inputs(&self) -> impl Iterator<Item = &PathBuf> {
+        self.announcements
+            .iter()
+            .flatten()
+            .chain(self.vrps.iter())
+            .chain(self.delegations.iter())
+            .chain(self.slurm.iter())
+            .chain(self.irr.iter())
+    }
+
+    pub fn announcements(&self) -> Result<Vec<PathBuf>, Error> {
+        match &self.announcements {
+            Some(announcements) if !announcements.is_empty() => Ok(announcements.clone()),
+            _ => Err(Error::Missing("announcements")),
+        }
+    }
+
+    pub fn vrps(&self) -> Result<PathBuf, Error> {
+        self.vrps.clone().ok_or(Error::Missing("vrps"))
+    }
+
+    pub fn delegations(&self) -> Result<PathBuf, Error> {
+        self.delegations
+            .clone()
+            .ok_or(Error::Missing("delegations"))
+    }
+
+    /// Returns the announcements, or an empty list if there are none.
+    pub fn optional_announcements(&self) -> Vec<PathBuf> {
+        self.announcements.clone().unwrap_or_default()
+    }
+
+    pub fn optional_vrps(&self) -> Option<PathBuf> {
+        self.vrps.clone()
+    }
+
+    pub fn optional_delegations(&self) -> Option<PathBuf> {
+        self.delegations.clone()
+    }
+
+    /// Returns the SLURM file with local exceptions to the VRPs, if any.
+    pub fn optional_slurm(&self) -> Option<PathBuf> {
+        self.slurm.clone()
+    }
+
+    /// Returns the IRR export with route and route6 objects.
+    pub fn irr(&self) -> Result<PathBuf, Error> {
+        self.irr.clone().ok_or(Error::Missing("irr"))
+    }
+
+    pub fn optional_irr(&self) -> Option<PathBuf> {
+        self.irr.clone()
+    }
+
+    /// Returns the address to bind the daemon to, 127.0.0.1:8080 by default.
+    pub fn bind(&self) -> SocketAddr {
+        self.bind
+            .unwrap_or_else(|| SocketAddr::from_str(DEFAULT_BIND).unwrap())
+    }
+
+    /// Returns which announcements count towards adoption, all by default.
+    pub fn denominator(&self) -> AdoptionDenominator {
+        self.denominator.unwrap_or_default()
+    }
+
+    /// Returns the number of RIS peers that an announcement must at least be
+    /// seen by, to be included. Defaults to DEFAULT_MIN_PEERS.
+    pub fn min_peers(&self) -> u32 {
+        self.min_peers.unwrap_or(DEFAULT_MIN_PEERS)
+    }
+
+    /// Returns the Unix socket for the daemon to listen on instead of the
+    /// bind address, if any.
+    pub fn optional_unix_socket(&self) -> Option<PathBuf> {
+        self.unix_socket.clone()
+    }
+
+    /// Returns the format for the daemon log, plain text by default.
+    pub fn log_format(&self) -> LogFormat {
+        self.log_format.unwrap_or_default()
+    }
+
+    /// Returns the bearer token that the daemon expects for reloads over
+    /// HTTP, if any.
+    pub fn optional_reload_token(&self) -> Option<String> {
+        self.reload_token.clone()
+    }
+}
+
+impl FromStr for Config {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s).map_err(|e| Error::ParseError(e.to_string()))
+    }
+}
+
+//------------ Error --------------------------------------------------------
+
+#[derive(Debug, Display)]
+pub enum Error {
+    #[display(fmt = "Cannot read config file: {}", _0)]
+    CannotRead(String),
+
+    #[display(fmt = "Error parsing config file: {}", _0)]
+    ParseError(String),
+
+    #[display(
+        fmt = "Missing '{}', use the command line option or set it in the config file",
+        _0
+    )]
+    Missing(&'static str),
+
+    #[display(fmt = "Invalid bind address: {}", _0)]
+    InvalidBindAddress(String),
+
+    #[display(fmt = "Unsupported denominator: {}. Supported are: all|assigned", _0)]
+    InvalidDenominator(String),
+
+    #[display(fmt = "Invalid number of RIS peers: {}", _0)]
+    InvalidMinPeers(String),
+
+    #[display(fmt = "Unsupported log format: {}. Supported are: text|json", _0)]
+    InvalidLogFormat(String),
+
+    #[display(fmt = "Only one input can be read from stdin ('-')")]
+    MultipleStdin,
+}
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cli;
+
+    fn matches(args: &[&str]) -> Config {
+        Config::from_matches(&cli::subcommand_matches(args)).unwrap()
+    }
+
+    #[test]
+    fn should_allow_one_input_from_stdin() {
+        let config = matches(&["daemon", "--vrps", "-", "--announcements", "a", "b"]);
+        assert_eq!(PathBuf::from("-"), config.vrps().unwrap());
+
+        let matches = cli::subcommand_matches(&["world", "--vrps", "-", "--announcements", "-"]);
+        match Config::from_matches(&matches) {
+            Err(Error::MultipleStdin) => {}
+            other => panic!("Expected MultipleStdin, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_load_config_file() {
+        let config = Config::from_file(&PathBuf::from("test/small/config.toml")).unwrap();
+
+        assert_eq!(
+            vec![
+                PathBuf::from("test/small/riswhoisdump.IPv4"),
+                PathBuf::from("test/small/riswhoisdump.IPv6"),
+            ],
+            config.announcements().unwrap()
+        );
+        assert_eq!(PathBuf::from("test/small/vrps.csv"), config.vrps().unwrap());
+        assert_eq!(
+            PathBuf::from("test/small/delegated-extended.txt"),
+            config.delegations().unwrap()
+        );
+        assert_eq!(
+            SocketAddr::from_str("127.0.0.1:8081").unwrap(),
+            config.bind()
+        );
+    }
+
+    #[test]
+    fn command_line_should_override_config_file() {
+        let config = matches(&[
+            "daemon",
+            "--config",
+            "test/small/config.toml",
+            "--vrps",
+            "other.csv",
+            "--bind",
+            "0.0.0.0:80",
+        ]);
+
+        assert_eq!(PathBuf::from("other.csv"), config.vrps().unwrap());
+        assert_eq!(SocketAddr::from_str("0.0.0.0:80").unwrap(), config.bind());
+        assert_eq!(2, config.announcements().unwrap().len());
+
+        let config = matches(&["daemon", "--announcements", "a", "b", "c"]);
+        assert_eq!(3, config.announcements().unwrap().len());
+        assert!(config.vrps().is_err());
+        assert_eq!(SocketAddr::from_str(DEFAULT_BIND).unwrap(), config.bind());
+    }
+
+    #[test]
+    fn should_read_denominator() {
+        assert_eq!(AdoptionDenominator::All, Config::default().denominator());
+
+        let config = Config::from_str("denominator = \"assigned\"").unwrap();
+        assert_eq!(AdoptionDenominator::AssignedOnly, config.denominator());
+        assert!(Config::from_str("denominator = \"some\"").is_err());
+
+        let config = matches(&["daemon", "--denominator", "assigned"]);
+        assert_eq!(AdoptionDenominator::AssignedOnly, config.denominator());
+    }
+
+    #[test]
+    fn should_read_min_peers() {
+        assert_eq!(DEFAULT_MIN_PEERS, Config::default().min_peers());
+
+        let config = Config::from_str("min_peers = 0").unwrap();
+        assert_eq!(0, config.min_peers());
+
+        let config = matches(&["daemon", "--min-peers", "2"]);
+        assert_eq!(2, config.min_peers());
+    }
+
+    #[test]
+    fn should_read_daemon_settings() {
+        let config = Config::default();
+        assert_eq!(None, config.optional_unix_socket());
+        assert_eq!(LogFormat::Text, config.log_format());
+        assert_eq!(None, config.optional_reload_token());
+
+        let config = Config::from_str(
+            "unix_socket = \"/tmp/stats.sock\"\nlog_format = \"json\"\nreload_token = \"secret\"",
+        )
+        .unwrap();
+        assert_eq!(
+            Some(PathBuf::from("/tmp/stats.sock")),
+            config.optional_unix_socket()
+        );
+        assert_eq!(LogFormat::Json, config.log_format());
+        assert_eq!(Some("secret".to_string()), config.optional_reload_token());
+        assert!(Config::from_str("log_format = \"xml\"").is_err());
+
+        let config = matches(&[
+            "daemon",
+            "--unix-socket",
+            "other.sock",
+            "--log-format",
+            "json",
+            "--reload-token",
+            "other",
+        ]);
+        assert_eq!(
+            Some(PathBuf::from("other.sock")),
+            config.optional_unix_socket()
+        );
+        assert_eq!(LogFormat::Json, config.log_format());
+        assert_eq!(Some("other".to_string()), config.optional_reload_token());
+    }
+
+    #[test]
+    fn should_read_irr() {
+        assert!(Config::default().irr().is_err());
+        assert_eq!(None, Config::default().optional_irr());
+
+        let config = Config::from_str("irr = \"routes.db\"").unwrap();
+        assert_eq!(PathBuf::from("routes.db"), config.irr().unwrap());
+
+        let config = matches(&["irr-check", "--irr", "other.db"]);
+        assert_eq!(Some(PathBuf::from("other.db")), config.optional_irr());
+    }
+
+    #[test]
+    fn should_reject_unknown_keys() {
+        assert!(Config::from_str("ris4 = \"riswhoisdump.IPv4\"").is_err());
+    }
+}
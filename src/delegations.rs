@@ -1,19 +1,23 @@
 //! Parse delegated extended stats
+use crate::input;
+use crate::input::Progress;
 use crate::ip::{
-    IpAddress, IpAddressError, IpRange, IpRangeError, IpRangeTree, IpRangeTreeBuilder,
+    Asn, AsnError, AsnRange, IpAddress, IpAddressError, IpRange, IpRangeError, IpRangeTree,
+    IpRangeTreeBuilder,
 };
 use ip::{IpPrefix, IpPrefixError};
+use serde::Serialize;
+use serde::Serializer;
+use std::fmt;
 use std::fmt::Display;
-use std::fs::File;
 use std::io::BufRead;
-use std::io::BufReader;
 use std::num::ParseIntError;
 use std::path::Path;
 use std::str::FromStr;
 
 //------------ Registry -----------------------------------------------------
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum Registry {
     Iana,
     Afrinic,
@@ -39,9 +43,38 @@ impl FromStr for Registry {
     }
 }
 
+impl Registry {
+    /// Returns the name as used in the delegated stats, e.g. 'ripencc'.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Registry::Iana => "iana",
+            Registry::Afrinic => "afrinic",
+            Registry::Apnic => "apnic",
+            Registry::Arin => "arin",
+            Registry::Lacnic => "lacnic",
+            Registry::RipeNcc => "ripencc",
+        }
+    }
+}
+
+impl Display for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Serialize for Registry {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
 //------------ DelegationState -----------------------------------------------
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum DelegationState {
     IANAPOOL,
     IETF,
@@ -66,6 +99,28 @@ impl FromStr for DelegationState {
     }
 }
 
+impl Display for DelegationState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            DelegationState::IANAPOOL => "ianapool",
+            DelegationState::IETF => "ietf",
+            DelegationState::AVAILABLE => "available",
+            DelegationState::ASSIGNED => "assigned",
+            DelegationState::RESERVED => "reserved",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Serialize for DelegationState {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
 //------------ IpDelegation -------------------------------------------------
 
 #[derive(Clone, Debug)]
@@ -118,8 +173,22 @@ impl IpDelegation {
         }))
     }
 
+    /// Returns true for lines in delegated stats that are not records: the
+    /// version header, summaries and comments. Both the NRO extended format
+    /// and the older per RIR format without opaque id and extensions have
+    /// these, but only the NRO header starts with 'nro'.
+    fn is_nro_header_line(s: &str) -> bool {
+        let mut values = s.split('|');
+        let first = values.next().unwrap_or("");
+        s.is_empty()
+            || s.starts_with('#')
+            || s.contains("nro|")
+            || f32::from_str(first).is_ok()
+            || values.nth(4) == Some("summary")
+    }
+
     fn from_nro_line(s: &str) -> Result<Option<Self>, Error> {
-        if s.contains("nro|") || s.contains("|asn|") {
+        if Self::is_nro_header_line(s) || s.contains("|asn|") {
             Ok(None)
         } else {
             let mut values = s.split('|');
@@ -138,8 +207,14 @@ impl IpDelegation {
                 let reg = Registry::from_str(reg_str)?;
                 let cc = cc_str.to_string();
                 let min = IpAddress::from_str(min_str)?;
-                let number = u128::from_str(amount_str)?;
-                let range = IpRange::from_min_and_number(min, number)?;
+
+                // For IPv4 the value is the number of addresses, but for IPv6
+                // it is the prefix length.
+                let range = if inr_type_str == "ipv6" {
+                    IpPrefix::new(min, u8::from_str(amount_str)?)?.into()
+                } else {
+                    IpRange::from_min_and_number(min, u128::from_str(amount_str)?)?
+                };
                 let state = DelegationState::from_str(state_str)?;
 
                 Ok(Some(IpDelegation {
@@ -153,12 +228,82 @@ impl IpDelegation {
     }
 }
 
+impl Display for IpDelegation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.range, self.reg, self.cc, self.state)
+    }
+}
+
 impl AsRef<IpRange> for IpDelegation {
     fn as_ref(&self) -> &IpRange {
         &self.range
     }
 }
 
+//------------ AsnDelegation ------------------------------------------------
+
+#[derive(Clone, Debug)]
+pub struct AsnDelegation {
+    reg: Registry,
+    cc: String,
+    range: AsnRange,
+    state: DelegationState,
+}
+
+impl AsnDelegation {
+    pub fn reg(&self) -> &Registry {
+        &self.reg
+    }
+    pub fn cc(&self) -> &str {
+        &self.cc
+    }
+    pub fn range(&self) -> &AsnRange {
+        &self.range
+    }
+    pub fn state(&self) -> &DelegationState {
+        &self.state
+    }
+}
+
+impl AsnDelegation {
+    /// Parses an 'asn' line in the delegated stats. Other lines, for IP
+    /// space and headers, are skipped.
+    fn from_nro_line(s: &str) -> Result<Option<Self>, Error> {
+        if IpDelegation::is_nro_header_line(s) || !s.contains("|asn|") {
+            return Ok(None);
+        }
+
+        let mut values = s.split('|');
+
+        let reg_str = values.next().ok_or_else(|| Error::missing("rir", s))?;
+        let cc_str = values.next().ok_or_else(|| Error::missing("cc", s))?;
+        let _inr_type_str = values.next().ok_or_else(|| Error::missing("type", s))?;
+        let min_str = values.next().ok_or_else(|| Error::missing("min", s))?;
+        let amount_str = values.next().ok_or_else(|| Error::missing("amount", s))?;
+        let _date_str = values.next().ok_or_else(|| Error::missing("date", s))?;
+        let state_str = values.next().ok_or_else(|| Error::missing("state", s))?;
+
+        let reg = Registry::from_str(reg_str)?;
+        let cc = cc_str.to_string();
+        let min = Asn::from_str(min_str)?;
+        let range = AsnRange::from_min_and_number(min, u32::from_str(amount_str)?)?;
+        let state = DelegationState::from_str(state_str)?;
+
+        Ok(Some(AsnDelegation {
+            reg,
+            cc,
+            range,
+            state,
+        }))
+    }
+}
+
+impl Display for AsnDelegation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.range, self.reg, self.cc, self.state)
+    }
+}
+
 //------------ IpDelegations ------------------------------------------------
 
 #[derive(Debug)]
@@ -167,21 +312,46 @@ pub struct IpDelegations {
 }
 
 impl IpDelegations {
+    /// Reads the IP delegations from the NRO (or per RIR) delegated stats,
+    /// or from CSV if the extension is 'csv'. A path of '-' reads the
+    /// delegated stats from stdin.
     pub fn from_file(path: &Path) -> Result<Self, Error> {
-        let file = File::open(path).map_err(|_| Error::read_error(path))?;
-        let reader = BufReader::new(file);
+        Self::from_file_with_progress(path, Progress::Silent)
+    }
+
+    /// Like 'from_file', but logs the lines read if progress is enabled.
+    pub fn from_file_with_progress(path: &Path, progress: Progress) -> Result<Self, Error> {
+        let reader = input::open(path, progress).map_err(|_| Error::read_error(path))?;
 
+        if path.to_string_lossy().ends_with(".csv") {
+            Self::from_csv_reader(reader)
+        } else {
+            Self::from_reader(reader)
+        }
+    }
+
+    /// Reads the IP delegations from the NRO (or per RIR) delegated stats
+    /// in any reader.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, Error> {
+        Self::parse_lines(reader, IpDelegation::from_nro_line)
+    }
+
+    /// Reads the IP delegations from CSV in any reader.
+    pub fn from_csv_reader<R: BufRead>(reader: R) -> Result<Self, Error> {
+        Self::parse_lines(reader, IpDelegation::from_csv_line)
+    }
+
+    /// Adds the delegation for each line that the parser gives one for.
+    fn parse_lines<R, F>(reader: R, parse: F) -> Result<Self, Error>
+    where
+        R: BufRead,
+        F: Fn(&str) -> Result<Option<IpDelegation>, Error>,
+    {
         let mut builder = IpRangeTreeBuilder::empty();
 
         for lres in reader.lines() {
             let line = lres.map_err(Error::parse_error)?;
-            let path_str = path.to_string_lossy().to_string();
-
-            if path_str.ends_with(".csv") {
-                if let Some(del) = IpDelegation::from_csv_line(&line)? {
-                    builder.add(del);
-                }
-            } else if let Some(del) = IpDelegation::from_nro_line(&line)? {
+            if let Some(del) = parse(&line)? {
                 builder.add(del);
             }
         }
@@ -191,13 +361,137 @@ impl IpDelegations {
         })
     }
 
+    /// Returns the most specific delegation that contains the given range,
+    /// if any. Delegations may overlap, e.g. when a registry delegates part
+    /// of a block to a different country.
+    fn find_delegation(&self, range: &IpRange) -> Option<&IpDelegation> {
+        self.tree.most_specific_match(range)
+    }
+
+    /// Returns all delegations that overlap the given range.
+    pub fn intersecting(&self, range: &IpRange) -> Vec<&IpDelegation> {
+        self.tree.intersecting(range)
+    }
+
+    /// Returns the registries of all delegations that overlap the given
+    /// range, sorted and without duplicates. More than one registry means
+    /// that the range spans space delegated by different registries.
+    pub fn registries(&self, range: &IpRange) -> Vec<Registry> {
+        let mut registries: Vec<Registry> = self
+            .intersecting(range)
+            .into_iter()
+            .map(|del| *del.reg())
+            .collect();
+        registries.sort();
+        registries.dedup();
+        registries
+    }
+
+    /// Returns all country codes found in the delegations, sorted and
+    /// without duplicates.
+    pub fn country_codes(&self) -> Vec<&str> {
+        let mut ccs: Vec<&str> = self.tree.iter_values().map(|del| del.cc()).collect();
+        ccs.sort();
+        ccs.dedup();
+        ccs
+    }
+
     pub fn find_cc(&self, range: &IpRange) -> &str {
-        let matching = self.tree.matching_or_less_specific(range);
-        match matching.first() {
+        match self.find_delegation(range) {
             Some(delegation) => delegation.cc(),
             None => "XX",
         }
     }
+
+    /// Returns the registry of the most specific delegation containing the
+    /// range, if any.
+    pub fn find_registry(&self, range: &IpRange) -> Option<Registry> {
+        self.find_delegation(range)
+            .map(|delegation| *delegation.reg())
+    }
+
+    /// Returns the state of the most specific delegation containing the
+    /// range, if any.
+    pub fn find_state(&self, range: &IpRange) -> Option<&DelegationState> {
+        self.find_delegation(range)
+            .map(|delegation| delegation.state())
+    }
+
+    /// Returns whether the given range falls in space that is assigned (or
+    /// allocated) by a registry. Space that is reserved, available, or not
+    /// found in the delegations at all is not considered assigned.
+    pub fn is_assigned(&self, range: &IpRange) -> bool {
+        match self.find_delegation(range) {
+            Some(delegation) => delegation.state() == &DelegationState::ASSIGNED,
+            None => false,
+        }
+    }
+}
+
+//------------ AsnDelegations -----------------------------------------------
+
+/// The ASN delegations from the same delegated stats as the IpDelegations,
+/// so that announcements can be mapped to the country of their origin.
+#[derive(Debug)]
+pub struct AsnDelegations {
+    delegations: Vec<AsnDelegation>,
+}
+
+impl AsnDelegations {
+    /// Reads the 'asn' lines in the NRO (or per RIR) delegated stats. The
+    /// CSV format only has IP space, so this gives no delegations for it.
+    /// A path of '-' reads from stdin.
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let reader = input::open(path, Progress::Silent).map_err(|_| Error::read_error(path))?;
+        if path.to_string_lossy().ends_with(".csv") {
+            return Ok(AsnDelegations {
+                delegations: vec![],
+            });
+        }
+        Self::from_reader(reader)
+    }
+
+    /// Reads the 'asn' lines in the NRO (or per RIR) delegated stats in any
+    /// reader.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, Error> {
+        let mut delegations = vec![];
+
+        for lres in reader.lines() {
+            let line = lres.map_err(Error::parse_error)?;
+            if let Some(del) = AsnDelegation::from_nro_line(&line)? {
+                delegations.push(del);
+            }
+        }
+
+        delegations.sort_by_key(|del| del.range.min());
+
+        Ok(AsnDelegations { delegations })
+    }
+
+    /// Returns the delegation containing the ASN, if any. ASN delegations
+    /// do not overlap, so this is the last one starting at or before it.
+    fn find_delegation(&self, asn: &Asn) -> Option<&AsnDelegation> {
+        let after = self
+            .delegations
+            .partition_point(|del| del.range.min() <= *asn);
+        after
+            .checked_sub(1)
+            .map(|i| &self.delegations[i])
+            .filter(|del| del.range.contains(*asn))
+    }
+
+    pub fn find_cc(&self, asn: &Asn) -> &str {
+        match self.find_delegation(asn) {
+            Some(delegation) => delegation.cc(),
+            None => "XX",
+        }
+    }
+
+    /// Returns the registry that delegated the ASN, if any.
+    pub fn find_registry(&self, asn: &Asn) -> Option<Registry> {
+        self.find_delegation(asn)
+            .map(|delegation| *delegation.reg())
+    }
 }
 
 //------------ Error --------------------------------------------------------
@@ -250,11 +544,18 @@ impl From<IpPrefixError> for Error {
     }
 }
 
+impl From<AsnError> for Error {
+    fn from(e: AsnError) -> Self {
+        Self::parse_error(e)
+    }
+}
+
 //------------ Tests --------------------------------------------------------
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Cursor;
     use std::path::PathBuf;
 
     #[test]
@@ -263,6 +564,157 @@ mod tests {
         IpDelegations::from_file(&path).unwrap();
     }
 
+    #[test]
+    fn should_find_most_specific_delegation() {
+        let lines = [
+            "ripencc|NL|ipv4|10.0.0.0|65536|20190304|allocated|A1|e-stats",
+            "ripencc|BE|ipv4|10.0.1.0|256|20190304|assigned|A2|e-stats",
+            "ripencc|DE|ipv4|10.0.0.0|16777216|20190304|allocated|A3|e-stats",
+        ];
+
+        // Add in different orders to make sure the order does not matter.
+        for order in &[[0, 1, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]] {
+            let mut builder = IpRangeTreeBuilder::empty();
+            for i in order.iter() {
+                builder.add(IpDelegation::from_nro_line(lines[*i]).unwrap().unwrap());
+            }
+            let dels = IpDelegations {
+                tree: builder.build(),
+            };
+
+            let range = |s| IpRange::from(IpPrefix::from_str(s).unwrap());
+
+            assert_eq!("BE", dels.find_cc(&range("10.0.1.0/24")));
+            assert_eq!("NL", dels.find_cc(&range("10.0.2.0/24")));
+            assert_eq!("NL", dels.find_cc(&range("10.0.0.0/16")));
+            assert_eq!("DE", dels.find_cc(&range("10.1.0.0/16")));
+            assert_eq!("XX", dels.find_cc(&range("11.0.0.0/24")));
+        }
+    }
+
+    #[test]
+    fn should_read_non_extended_format() {
+        let dels =
+            IpDelegations::from_file(&PathBuf::from("test/small/delegated-arin.txt")).unwrap();
+        let range = |s| IpRange::from(IpPrefix::from_str(s).unwrap());
+
+        assert_eq!("US", dels.find_cc(&range("192.168.1.0/24")));
+        assert_eq!("CA", dels.find_cc(&range("198.51.100.0/24")));
+        assert_eq!("US", dels.find_cc(&range("2001:db8:8000::/48")));
+        assert_eq!("XX", dels.find_cc(&range("2001:db8::/48")));
+        assert_eq!(vec!["CA", "US"], dels.country_codes());
+        assert_eq!(
+            Some(Registry::Arin),
+            dels.find_registry(&range("192.168.0.0/16"))
+        );
+
+        match IpDelegation::from_nro_line("arin|US|ipv4|192.168.0.0|65536") {
+            Err(Error::MissingColumn(column, _)) => assert_eq!("date", column),
+            _ => panic!("Expected missing column error"),
+        }
+    }
+
+    #[test]
+    fn should_use_prefix_length_for_ipv6() {
+        let line = "ripencc|NL|ipv6|2001:db8::|32|20190304|allocated|A1|e-stats";
+        let del = IpDelegation::from_nro_line(line).unwrap().unwrap();
+        assert_eq!(
+            &IpRange::from(IpPrefix::from_str("2001:db8::/32").unwrap()),
+            del.range()
+        );
+    }
+
+    #[test]
+    fn should_find_registries_for_range_spanning_delegations() {
+        let lines = [
+            "ripencc|NL|ipv4|10.0.0.0|65536|20190304|allocated|A1|e-stats",
+            "arin|US|ipv4|10.1.0.0|65536|20190304|allocated|A2|e-stats",
+            "ripencc|BE|ipv4|10.2.0.0|65536|20190304|allocated|A3|e-stats",
+        ];
+
+        let mut builder = IpRangeTreeBuilder::empty();
+        for line in lines.iter() {
+            builder.add(IpDelegation::from_nro_line(line).unwrap().unwrap());
+        }
+        let dels = IpDelegations {
+            tree: builder.build(),
+        };
+
+        let range = |s| IpRange::from(IpPrefix::from_str(s).unwrap());
+
+        // A VRP for 10.0.0.0/15 straddles the RIPE NCC and ARIN delegations
+        assert_eq!(
+            vec![Registry::Arin, Registry::RipeNcc],
+            dels.registries(&range("10.0.0.0/15"))
+        );
+        assert_eq!(
+            vec![Registry::RipeNcc],
+            dels.registries(&range("10.0.0.0/16"))
+        );
+        assert_eq!(vec![Registry::Arin], dels.registries(&range("10.1.2.0/24")));
+        assert!(dels.registries(&range("11.0.0.0/8")).is_empty());
+    }
+
+    #[test]
+    fn should_find_cc_for_asn() {
+        let path = PathBuf::from("test/20190304/delegated-extended.txt");
+        let dels = AsnDelegations::from_file(&path).unwrap();
+        let asn = |s| Asn::from_str(s).unwrap();
+
+        assert_eq!("NL", dels.find_cc(&asn("AS3333")));
+        assert_eq!("NL", dels.find_cc(&asn("AS199664")));
+        assert_eq!(Some(Registry::RipeNcc), dels.find_registry(&asn("AS3333")));
+
+        let small =
+            AsnDelegations::from_file(&PathBuf::from("test/small/delegated-extended.txt")).unwrap();
+        assert_eq!("NL", small.find_cc(&asn("AS65000")));
+        assert_eq!("US", small.find_cc(&asn("AS65001")));
+        assert_eq!("XX", small.find_cc(&asn("AS65002")));
+        assert_eq!(None, small.find_registry(&asn("AS64999")));
+    }
+
+    #[test]
+    fn should_parse_asn_blocks() {
+        let line = "apnic|AU|asn|131072|1024|20190304|allocated|A1|e-stats";
+        let del = AsnDelegation::from_nro_line(line).unwrap().unwrap();
+        assert_eq!("AS131072-AS132095", del.range().to_string());
+        assert_eq!(&DelegationState::ASSIGNED, del.state());
+
+        let ip_line = "ripencc|NL|ipv4|10.0.0.0|65536|20190304|allocated|A1|e-stats";
+        assert!(AsnDelegation::from_nro_line(ip_line).unwrap().is_none());
+        assert!(AsnDelegation::from_nro_line("nro|*|asn|*|2|summary")
+            .unwrap()
+            .is_none());
+        assert!(AsnDelegation::from_nro_line("apnic|AU|asn|131072|0|20190304|allocated").is_err());
+    }
+
+    #[test]
+    fn should_read_from_reader() {
+        let stats = "2|nro|20190304|3|19821213|20190304|+0000\n\
+                     nro|*|ipv4|*|2|summary\n\
+                     ripencc|NL|asn|65000|1|20190304|assigned|A1|e-stats\n\
+                     ripencc|NL|ipv4|10.0.0.0|65536|20190304|allocated|A1|e-stats\n\
+                     arin|US|ipv4|192.168.0.0|65536|20190304|assigned|A2|e-stats\n";
+        let range = |s| IpRange::from(IpPrefix::from_str(s).unwrap());
+
+        let dels = IpDelegations::from_reader(Cursor::new(stats)).unwrap();
+        assert_eq!("NL", dels.find_cc(&range("10.0.1.0/24")));
+        assert_eq!("US", dels.find_cc(&range("192.168.0.0/24")));
+        assert_eq!("XX", dels.find_cc(&range("172.16.0.0/24")));
+
+        let asns = AsnDelegations::from_reader(Cursor::new(stats)).unwrap();
+        assert_eq!("NL", asns.find_cc(&Asn::from_str("AS65000").unwrap()));
+
+        let csv = "prefix,rir,date,country_code,status,block_start,block_ip_count\n\
+                   1.0.0.0/24,apnic,20110811,AU,assigned,1.0.0.0,256\n";
+        let dels = IpDelegations::from_csv_reader(Cursor::new(csv)).unwrap();
+        assert_eq!("AU", dels.find_cc(&range("1.0.0.0/24")));
+        assert_eq!(
+            Some(Registry::Apnic),
+            dels.find_registry(&range("1.0.0.0/24"))
+        );
+    }
+
     #[test]
     fn read_csv() {
         let path = PathBuf::from("test/nrostats-20190101-v4.csv");
@@ -0,0 +1,164 @@
+//! Open the input files, where a path of '-' means stdin, so that the VRPs,
+//! announcements or delegations can be piped in, e.g. from Routinator.
+//!
+//! Loading full dumps can take a while. With progress enabled, the number
+//! of lines read so far is logged, so that it ends up on stderr in the log
+//! format in use, keeping stdout clean for the reports.
+use clap::ArgMatches;
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::path::Path;
+
+/// The path that stands for stdin.
+pub const STDIN: &str = "-";
+
+/// Progress is reported each time this many more lines have been read.
+pub const PROGRESS_EVERY_LINES: usize = 100_000;
+
+/// Returns whether the path is '-', i.e. stdin.
+pub fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == STDIN
+}
+
+/// Opens the file, or stdin if the path is '-', for reading lines.
+pub fn open(path: &Path, progress: Progress) -> io::Result<Box<dyn BufRead>> {
+    let input: Box<dyn Read> = if is_stdin(path) {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(path)?)
+    };
+
+    match progress {
+        Progress::Log => {
+            let label = path.to_string_lossy().to_string();
+            let reader = ProgressReader::new(input, label, |msg: &str| info!("{}", msg));
+            Ok(Box::new(BufReader::new(reader)))
+        }
+        Progress::Silent => Ok(Box::new(BufReader::new(input))),
+    }
+}
+
+//------------ Progress ------------------------------------------------------
+
+/// Whether to log the number of lines read from an input.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Progress {
+    #[default]
+    Silent,
+    Log,
+}
+
+impl Progress {
+    /// Returns 'Log' if --progress was given.
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        if matches.is_present("progress") {
+            Progress::Log
+        } else {
+            Progress::Silent
+        }
+    }
+}
+
+//------------ ProgressReader ------------------------------------------------
+
+/// Counts the lines read from the inner reader, and reports the count every
+/// PROGRESS_EVERY_LINES lines, and once more when done.
+struct ProgressReader<R, F: FnMut(&str)> {
+    inner: R,
+    label: String,
+    lines: usize,
+    next_report: usize,
+    report: F,
+}
+
+impl<R: Read, F: FnMut(&str)> ProgressReader<R, F> {
+    fn new(inner: R, label: String, report: F) -> Self {
+        ProgressReader {
+            inner,
+            label,
+            lines: 0,
+            next_report: PROGRESS_EVERY_LINES,
+            report,
+        }
+    }
+}
+
+impl<R: Read, F: FnMut(&str)> Read for ProgressReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.lines += buf[..read].iter().filter(|b| **b == b'\n').count();
+        if self.lines >= self.next_report {
+            (self.report)(&format!("Reading {}: {} lines", self.label, self.lines));
+            self.next_report = (self.lines / PROGRESS_EVERY_LINES + 1) * PROGRESS_EVERY_LINES;
+        }
+        Ok(read)
+    }
+}
+
+impl<R, F: FnMut(&str)> Drop for ProgressReader<R, F> {
+    fn drop(&mut self) {
+        (self.report)(&format!("Read {}: {} lines", self.label, self.lines));
+    }
+}
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+    use std::path::PathBuf;
+
+    #[test]
+    fn should_only_treat_dash_as_stdin() {
+        assert!(is_stdin(&PathBuf::from("-")));
+        assert!(!is_stdin(&PathBuf::from("./-")));
+        assert!(!is_stdin(&PathBuf::from("vrps.csv")));
+
+        let mut line = String::new();
+        open(&PathBuf::from("test/small/vrps.csv"), Progress::Silent)
+            .unwrap()
+            .read_line(&mut line)
+            .unwrap();
+        assert_eq!("ASN,IP Prefix,Max Length,Trust Anchor\n", line);
+        assert!(open(&PathBuf::from("test/small/missing.csv"), Progress::Silent).is_err());
+    }
+
+    #[test]
+    fn should_only_log_progress_when_asked() {
+        let lookup = ["lookup", "-d", "dels.txt", "-p", "prefixes.txt"];
+        let silent = cli::subcommand_matches(&lookup);
+        assert_eq!(Progress::Silent, Progress::from_matches(&silent));
+
+        let after: Vec<&str> = lookup.iter().cloned().chain(Some("--progress")).collect();
+        let after = cli::subcommand_matches(&after);
+        assert_eq!(Progress::Log, Progress::from_matches(&after));
+
+        let before: Vec<&str> = Some("--progress")
+            .into_iter()
+            .chain(lookup.iter().cloned())
+            .collect();
+        let before = cli::subcommand_matches(&before);
+        assert_eq!(Progress::Log, Progress::from_matches(&before));
+    }
+
+    #[test]
+    fn should_report_progress() {
+        let input = "line\n".repeat(PROGRESS_EVERY_LINES * 2 + 5);
+        let mut reports: Vec<String> = vec![];
+        {
+            let report = |msg: &str| reports.push(msg.to_string());
+            let progress = ProgressReader::new(input.as_bytes(), "test".to_string(), report);
+            let lines = BufReader::with_capacity(1024, progress).lines().count();
+            assert_eq!(PROGRESS_EVERY_LINES * 2 + 5, lines);
+        }
+
+        assert_eq!(3, reports.len());
+        assert!(reports[0].starts_with("Reading test: 10"));
+        assert!(reports[1].starts_with("Reading test: 20"));
+        assert_eq!("Read test: 200005 lines", reports[2]);
+    }
+}
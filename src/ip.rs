@@ -1,10 +1,15 @@
 use intervaltree::IntervalTree;
+use serde::de;
+use serde::Deserialize;
+use serde::Deserializer;
 use serde::Serialize;
 use serde::Serializer;
 use std::cmp;
 use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt;
+use std::iter;
 use std::net;
 use std::num::ParseIntError;
 use std::ops::Range;
@@ -14,9 +19,31 @@ use std::str::FromStr;
 const IPV4_IN_IPV6: u128 = 0xffff_0000_0000;
 const IPV4_UNUSED: u128 = 0xffff_ffff_ffff_ffff_ffff_ffff_0000_0000;
 
+/// The maximum number of ASNs that AsnSet::asns will enumerate.
+pub const MAX_ASNS_TO_ENUMERATE: u64 = 65_536;
+
+/// Returns a mask with the host bits set for a prefix of the given length,
+/// in the 128 bit space. Lengths 0 and 128 are handled without overflowing.
+fn host_mask(length: u32) -> u128 {
+    u128::MAX.checked_shr(length).unwrap_or(0)
+}
+
+/// Deserializes a value from a string, using its FromStr implementation. The
+/// types in this module are serialized as strings, so that they can be read
+/// back this way.
+fn deserialize_from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let s = String::deserialize(deserializer)?;
+    T::from_str(&s).map_err(de::Error::custom)
+}
+
 //------------ Asn ----------------------------------------------------------
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct Asn {
     val: u32,
 }
@@ -29,7 +56,7 @@ impl Ord for Asn {
 
 impl PartialOrd for Asn {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.val.partial_cmp(&other.val)
+        Some(self.cmp(other))
     }
 }
 
@@ -39,12 +66,43 @@ impl AsRef<u32> for Asn {
     }
 }
 
+impl Asn {
+    /// Returns true for ASNs reserved for private use (RFC 6996). These
+    /// should not be seen as origins on the public internet.
+    pub fn is_private(&self) -> bool {
+        (64512..=65534).contains(&self.val) || (4_200_000_000..=4_294_967_294).contains(&self.val)
+    }
+
+    /// Returns the ASN in asdot notation (RFC 5396), e.g. "1.10" for
+    /// AS65546. ASNs below 65536 are returned as plain numbers.
+    pub fn to_asdot(&self) -> String {
+        if self.val < 65536 {
+            self.val.to_string()
+        } else {
+            format!("{}.{}", self.val >> 16, self.val & 0xffff)
+        }
+    }
+}
+
+impl From<u32> for Asn {
+    fn from(val: u32) -> Self {
+        Asn { val }
+    }
+}
+
 impl FromStr for Asn {
     type Err = AsnError;
 
     fn from_str(s: &str) -> Result<Self, AsnError> {
         let val = s.to_lowercase().replace("as", "");
-        let val = u32::from_str(&val).map_err(|_| AsnError::InvalidAsn)?;
+        let val = match val.split_once('.') {
+            Some((high, low)) => {
+                let high = u16::from_str(high).map_err(|_| AsnError::InvalidAsn)?;
+                let low = u16::from_str(low).map_err(|_| AsnError::InvalidAsn)?;
+                u32::from(high) << 16 | u32::from(low)
+            }
+            None => u32::from_str(&val).map_err(|_| AsnError::InvalidAsn)?,
+        };
         Ok(Asn { val })
     }
 }
@@ -64,6 +122,15 @@ impl Serialize for Asn {
     }
 }
 
+impl<'de> Deserialize<'de> for Asn {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_from_str(deserializer)
+    }
+}
+
 //------------ AsnRange ------------------------------------------------------
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -76,6 +143,43 @@ impl AsnRange {
     pub fn contains(&self, asn: Asn) -> bool {
         self.min <= asn && self.max >= asn
     }
+
+    /// Returns the number of ASNs in this range.
+    pub fn len(&self) -> u64 {
+        if self.min > self.max {
+            0
+        } else {
+            u64::from(self.max.val - self.min.val) + 1
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over all ASNs in this range, in order.
+    pub fn iter(&self) -> impl Iterator<Item = Asn> {
+        (self.min.val..=self.max.val).map(|val| Asn { val })
+    }
+
+    /// Creates a range of the given number of ASNs, starting at min, as
+    /// used in the delegated stats.
+    pub fn from_min_and_number(min: Asn, number: u32) -> Result<Self, AsnError> {
+        let last = number.checked_sub(1).ok_or(AsnError::InvalidRange)?;
+        let val = min.val.checked_add(last).ok_or(AsnError::InvalidRange)?;
+        Ok(AsnRange {
+            min,
+            max: Asn { val },
+        })
+    }
+
+    pub fn min(&self) -> Asn {
+        self.min
+    }
+
+    pub fn max(&self) -> Asn {
+        self.max
+    }
 }
 
 impl FromStr for AsnRange {
@@ -84,6 +188,12 @@ impl FromStr for AsnRange {
     fn from_str(s: &str) -> Result<Self, AsnError> {
         let values: Vec<&str> = s.split('-').collect();
 
+        // A single ASN is displayed without a dash
+        if values.len() == 1 {
+            let asn = Asn::from_str(values[0])?;
+            return Ok(AsnRange { min: asn, max: asn });
+        }
+
         if values.len() != 2 {
             return Err(AsnError::InvalidRange);
         }
@@ -114,6 +224,15 @@ impl Serialize for AsnRange {
     }
 }
 
+impl<'de> Deserialize<'de> for AsnRange {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_from_str(deserializer)
+    }
+}
+
 //------------ AsnSet --------------------------------------------------------
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -147,6 +266,32 @@ impl AsnSet {
         let range = AsnRange { min: asn, max: asn };
         self.ranges.push(range);
     }
+
+    /// Returns all individual ASNs in this set, sorted and without
+    /// duplicates. Fails if the ranges contain more than
+    /// MAX_ASNS_TO_ENUMERATE distinct ASNs, to avoid running out of memory
+    /// on something like AS0-AS4294967295.
+    pub fn asns(&self) -> Result<Vec<Asn>, AsnError> {
+        let mut ranges = self.ranges.clone();
+        ranges.sort_by_key(|range| range.min);
+
+        // Merge overlapping ranges first, so that the cap applies to the
+        // distinct ASNs.
+        let mut merged: Vec<AsnRange> = vec![];
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.min <= last.max => last.max = cmp::max(last.max, range.max),
+                _ => merged.push(range),
+            }
+        }
+
+        let total: u64 = merged.iter().map(AsnRange::len).sum();
+        if total > MAX_ASNS_TO_ENUMERATE {
+            return Err(AsnError::TooManyAsns(total));
+        }
+
+        Ok(merged.iter().flat_map(AsnRange::iter).collect())
+    }
 }
 
 impl FromStr for AsnSet {
@@ -171,12 +316,12 @@ impl FromStr for AsnSet {
 
 impl fmt::Display for AsnSet {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let last_i = self.ranges.len() - 1;
-        for i in 0..self.ranges.len() {
-            self.ranges[i].fmt(f)?;
-            if i != last_i {
-                write!(f, ", ")?;
-            }
+        let mut ranges = self.ranges.iter();
+        if let Some(first) = ranges.next() {
+            first.fmt(f)?;
+        }
+        for range in ranges {
+            write!(f, ", {}", range)?;
         }
 
         Ok(())
@@ -194,15 +339,46 @@ impl Serialize for AsnSet {
 
 //------------ IpAddressFamily -----------------------------------------------
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum IpAddressFamily {
     Ipv4,
     Ipv6,
 }
 
+impl IpAddressFamily {
+    /// Checks that a prefix length, or a max length, fits this family.
+    /// IPv4 addresses are stored as IPv4 mapped IPv6 addresses, so without
+    /// this check a length like 40 would be taken as a valid IPv6 length.
+    pub fn check_prefix_length(self, length: u8) -> Result<(), IpPrefixError> {
+        match self {
+            IpAddressFamily::Ipv4 if length > 32 => Err(IpPrefixError::Ipv4PrefixLength(length)),
+            IpAddressFamily::Ipv6 if length > 128 => Err(IpPrefixError::Ipv6PrefixLength(length)),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for IpAddressFamily {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IpAddressFamily::Ipv4 => write!(f, "ipv4"),
+            IpAddressFamily::Ipv6 => write!(f, "ipv6"),
+        }
+    }
+}
+
+impl Serialize for IpAddressFamily {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
 //------------ IpAddress -----------------------------------------------------
 
-#[derive(Clone, Copy, Eq, PartialEq)]
+#[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct IpAddress {
     value: u128,
 }
@@ -214,6 +390,20 @@ impl IpAddress {
         IpAddress { value }
     }
 
+    /// Creates an address from its 128 bit value. IPv4 addresses are kept as
+    /// IPv4-mapped IPv6 addresses, i.e. ::ffff:0:0/96, so values in that
+    /// range are IPv4 addresses. Use 'from_u32' for IPv4 numbers.
+    pub fn from_u128(value: u128) -> Self {
+        IpAddress { value }
+    }
+
+    /// Creates an IPv4 address from its 32 bit value.
+    pub fn from_u32(value: u32) -> Self {
+        IpAddress {
+            value: IPV4_IN_IPV6 | u128::from(value),
+        }
+    }
+
     pub fn to_net_ipaddr(&self) -> net::IpAddr {
         match self.ip_address_family() {
             IpAddressFamily::Ipv4 => net::IpAddr::V4(net::Ipv4Addr::from(self.value as u32)),
@@ -250,37 +440,55 @@ impl Serialize for IpAddress {
     }
 }
 
+impl<'de> Deserialize<'de> for IpAddress {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_from_str(deserializer)
+    }
+}
+
 impl FromStr for IpAddress {
     type Err = IpAddressError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if s.contains('.') {
-            let ipv4 = net::Ipv4Addr::from_str(s)?;
-            let mut value: u128 = 0;
-            for octet in &ipv4.octets() {
-                value <<= 8;
-                value += u128::from(*octet);
-            }
-            Ok(IpAddress {
-                value: IPV4_IN_IPV6 | value,
-            })
+            Ok(IpAddress::from(net::Ipv4Addr::from_str(s)?))
         } else if s.contains(':') {
-            let ipv6 = net::Ipv6Addr::from_str(s)?;
-            let mut value = 0;
-            for octet in &ipv6.octets() {
-                value <<= 8;
-                value += u128::from(*octet);
-            }
-            Ok(IpAddress { value })
+            Ok(IpAddress::from(net::Ipv6Addr::from_str(s)?))
         } else {
             Err(IpAddressError::NotAnIpAddress)
         }
     }
 }
 
+impl From<net::Ipv4Addr> for IpAddress {
+    fn from(addr: net::Ipv4Addr) -> Self {
+        IpAddress::from_u32(u32::from(addr))
+    }
+}
+
+impl From<net::Ipv6Addr> for IpAddress {
+    fn from(addr: net::Ipv6Addr) -> Self {
+        IpAddress::from_u128(u128::from(addr))
+    }
+}
+
+impl From<net::IpAddr> for IpAddress {
+    fn from(addr: net::IpAddr) -> Self {
+        match addr {
+            net::IpAddr::V4(addr) => IpAddress::from(addr),
+            net::IpAddr::V6(addr) => IpAddress::from(addr),
+        }
+    }
+}
+
 //------------ IpRange -------------------------------------------------------
 
-#[derive(Clone, Copy, Eq, PartialEq)]
+/// Ranges are ordered by their minimum address first, and then by their
+/// maximum address.
+#[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct IpRange {
     min: IpAddress,
     max: IpAddress,
@@ -295,12 +503,25 @@ impl IpRange {
         }
     }
 
+    /// Returns the range of 'number' addresses starting at 'min'. The range
+    /// must fit in the address family of 'min'.
     pub fn from_min_and_number(min: IpAddress, number: u128) -> Result<Self, IpRangeError> {
-        let value = min.value + number - 1;
+        let last = number.checked_sub(1).ok_or(IpRangeError::NoAddresses)?;
+        let value = min
+            .value
+            .checked_add(last)
+            .ok_or(IpRangeError::ExceedsAddressSpace)?;
         let max = IpAddress { value };
+        if max.ip_address_family() != min.ip_address_family() {
+            return Err(IpRangeError::ExceedsAddressSpace);
+        }
         Self::create(min, max)
     }
 
+    pub fn ip_address_family(&self) -> IpAddressFamily {
+        self.min.ip_address_family()
+    }
+
     pub fn is_prefix(&self) -> bool {
         // The following code is inspired by the RIPE NCC ip-resource java library
         // https://github.com/RIPE-NCC/ipresource/blob/master/src/main/java/net/ripe/ipresource/IpRange.java
@@ -311,16 +532,91 @@ impl IpRange {
         // Lower bound is then derived by keeping all bits in common from the
         // min value, and setting the remainder to 0s. This has to match the
         // value for self.min.value itself for this to be a valid prefix
-        let lower_bound = self.min.value & ::std::u128::MAX << (128 - lead_in_common);
+        let lower_bound = self.min.value & !host_mask(lead_in_common);
 
         // Upper bound is then derived by keeping all the bits in common from
         // min value, and setting the remainder to 1s. This has to match the
         // value for self.max.value
-        let upper_bound = lower_bound | ((1u128 << (128 - lead_in_common)) - 1);
+        let upper_bound = lower_bound | host_mask(lead_in_common);
 
         self.min.value == lower_bound && self.max.value == upper_bound
     }
 
+    /// Returns the minimal list of prefixes that together make up this
+    /// range, ordered by address.
+    pub fn to_prefixes(&self) -> Vec<IpPrefix> {
+        let family_bits = match self.ip_address_family() {
+            IpAddressFamily::Ipv4 => 32,
+            IpAddressFamily::Ipv6 => 128,
+        };
+
+        let mut res = vec![];
+        let mut min = self.min.value;
+        loop {
+            // The largest block that starts at 'min' and does not go beyond
+            // max. The number of addresses in the remaining range does not
+            // fit a u128 if it is the full IPv6 space, hence the check.
+            let remaining = self.max.value - min;
+            let size_bits = if remaining == u128::MAX {
+                128
+            } else {
+                127 - (remaining + 1).leading_zeros()
+            };
+            let host_bits = cmp::min(min.trailing_zeros(), size_bits);
+            let host_bits = cmp::min(host_bits, family_bits);
+
+            let max = min | host_mask(128 - host_bits);
+            let range = IpRange {
+                min: IpAddress::new(min),
+                max: IpAddress::new(max),
+            };
+            let length = (family_bits - host_bits) as u8;
+            res.push(IpPrefix { range, length });
+
+            if max >= self.max.value {
+                break;
+            }
+            min = max + 1;
+        }
+        res
+    }
+
+    /// Returns the smallest prefix that covers both this and the other range,
+    /// using the bits that their lowest and highest addresses have in common.
+    /// Ranges in different address families are covered by an IPv6 prefix
+    /// that includes the IPv4 mapped space.
+    pub fn common_prefix(&self, other: &IpRange) -> IpPrefix {
+        let min = cmp::min(self.min, other.min).value;
+        let max = cmp::max(self.max, other.max).value;
+
+        let lead_in_common = (min ^ max).leading_zeros();
+        let min = IpAddress::new(min & !host_mask(lead_in_common));
+        let max = IpAddress::new(min.value | host_mask(lead_in_common));
+
+        let length = match min.ip_address_family() {
+            IpAddressFamily::Ipv4 => lead_in_common - 96,
+            IpAddressFamily::Ipv6 => lead_in_common,
+        } as u8;
+
+        IpPrefix {
+            range: IpRange { min, max },
+            length,
+        }
+    }
+
+    /// Returns the prefix length if this range is a prefix.
+    pub fn prefix_length(&self) -> Option<u8> {
+        if !self.is_prefix() {
+            return None;
+        }
+
+        let length = (self.min.value ^ self.max.value).leading_zeros() as u8;
+        match self.ip_address_family() {
+            IpAddressFamily::Ipv4 => Some(length - 96),
+            IpAddressFamily::Ipv6 => Some(length),
+        }
+    }
+
     #[allow(clippy::nonminimal_bool)]
     pub fn intersects(&self, other: IpRange) -> bool {
         (self.min.value <= other.min.value && self.max.value >= other.min.value)
@@ -335,6 +631,13 @@ impl IpRange {
         IpRange::from(other).contains(&self.to_range())
     }
 
+    /// Returns whether the other range is in the same address family. This
+    /// keeps IPv6 ranges that cover the IPv4 mapped space, like ::/0, from
+    /// matching IPv4 ranges.
+    fn is_same_family(&self, other: &Range<u128>) -> bool {
+        self.ip_address_family() == IpRange::from(other).ip_address_family()
+    }
+
     pub fn to_range(&self) -> std::ops::Range<u128> {
         std::ops::Range {
             start: self.min.value,
@@ -345,7 +648,7 @@ impl IpRange {
 
 impl fmt::Debug for IpRange {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+        write!(f, "{}", self)
     }
 }
 
@@ -364,6 +667,15 @@ impl Serialize for IpRange {
     }
 }
 
+impl<'de> Deserialize<'de> for IpRange {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_from_str(deserializer)
+    }
+}
+
 impl FromStr for IpRange {
     type Err = IpRangeError;
 
@@ -389,18 +701,81 @@ impl From<&Range<u128>> for IpRange {
     }
 }
 
+//------------ CompactIpRange ------------------------------------------------
+
+/// Wraps an IpRange to display and serialize it in CIDR notation, e.g.
+/// 10.0.0.0/24, if it is a prefix. Other ranges use the normal dash
+/// notation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CompactIpRange(IpRange);
+
+impl From<IpRange> for CompactIpRange {
+    fn from(range: IpRange) -> Self {
+        CompactIpRange(range)
+    }
+}
+
+impl AsRef<IpRange> for CompactIpRange {
+    fn as_ref(&self) -> &IpRange {
+        &self.0
+    }
+}
+
+impl fmt::Display for CompactIpRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0.prefix_length() {
+            Some(length) => write!(f, "{}/{}", self.0.min, length),
+            None => self.0.fmt(f),
+        }
+    }
+}
+
+impl Serialize for CompactIpRange {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
 //------------ IpPrefix ------------------------------------------------------
 
-#[derive(Clone)]
+#[derive(Clone, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct IpPrefix {
     range: IpRange,
     length: u8,
 }
 
 impl IpPrefix {
+    /// Creates a prefix from its first address and length. Fails if the
+    /// length is too long for the address family, or if the address has
+    /// bits set beyond the prefix length.
+    pub fn new(min: IpAddress, length: u8) -> Result<Self, IpPrefixError> {
+        min.ip_address_family().check_prefix_length(length)?;
+
+        let full_length = match min.ip_address_family() {
+            IpAddressFamily::Ipv4 => u32::from(length) + 96,
+            IpAddressFamily::Ipv6 => u32::from(length),
+        };
+
+        if full_length < (128 - min.value.trailing_zeros()) {
+            return Err(IpPrefixError::InvalidPrefixLength);
+        }
+
+        let max = IpAddress::new(min.value | host_mask(full_length));
+        let range = IpRange { min, max };
+
+        Ok(IpPrefix { range, length })
+    }
+
     pub fn length(&self) -> u8 {
         self.length
     }
+
+    pub fn ip_address_family(&self) -> IpAddressFamily {
+        self.range.ip_address_family()
+    }
 }
 
 impl FromStr for IpPrefix {
@@ -416,21 +791,7 @@ impl FromStr for IpPrefix {
         let min = IpAddress::from_str(ip_values[0])?;
         let length: u8 = u8::from_str(ip_values[1])?;
 
-        let full_length = match min.ip_address_family() {
-            IpAddressFamily::Ipv4 => length + 96,
-            IpAddressFamily::Ipv6 => length,
-        };
-
-        if full_length > 128 || full_length < (128 - min.value.trailing_zeros() as u8) {
-            return Err(IpPrefixError::InvalidPrefixLength);
-        }
-
-        let max_val = min.value | ((1u128 << (128 - full_length)) - 1);
-        let max = IpAddress::new(max_val);
-
-        let range = IpRange { min, max };
-
-        Ok(IpPrefix { range, length })
+        IpPrefix::new(min, length)
     }
 }
 
@@ -442,7 +803,7 @@ impl AsRef<IpRange> for IpPrefix {
 
 impl fmt::Debug for IpPrefix {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+        write!(f, "{}", self)
     }
 }
 
@@ -461,6 +822,15 @@ impl Serialize for IpPrefix {
     }
 }
 
+impl<'de> Deserialize<'de> for IpPrefix {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_from_str(deserializer)
+    }
+}
+
 impl From<IpPrefix> for IpRange {
     fn from(pfx: IpPrefix) -> Self {
         pfx.range
@@ -540,6 +910,150 @@ impl IpResourceSet {
     pub fn is_empty(&self) -> bool {
         self.ranges.is_empty()
     }
+
+    /// Returns the minimal list of prefixes that covers exactly the space in
+    /// this set, ordered by address. Adjacent ranges in the same address
+    /// family are joined first.
+    pub fn to_prefixes(&self) -> Vec<IpPrefix> {
+        Self::join(self.ranges.clone())
+            .iter()
+            .flat_map(IpRange::to_prefixes)
+            .collect()
+    }
+
+    /// Creates a set from any number of ranges. Unlike adding ranges one by
+    /// one, this sorts the ranges once, so it can be used for large numbers
+    /// of ranges, e.g. all announced prefixes.
+    pub fn from_ranges(ranges: Vec<IpRange>) -> Self {
+        IpResourceSet {
+            ranges: Self::join(ranges),
+        }
+    }
+
+    /// Sorts the ranges, and joins overlapping and adjacent ranges in the same
+    /// address family.
+    fn join(mut ranges: Vec<IpRange>) -> Vec<IpRange> {
+        ranges.sort();
+
+        let mut joined: Vec<IpRange> = vec![];
+        for range in ranges {
+            if let Some(last) = joined.last_mut() {
+                if last.ip_address_family() == range.ip_address_family()
+                    && last.max.value.saturating_add(1) >= range.min.value
+                {
+                    last.max = cmp::max(last.max, range.max);
+                    continue;
+                }
+            }
+            joined.push(range);
+        }
+        joined
+    }
+
+    /// IPv4 addresses are kept as IPv4 mapped IPv6 addresses, so an IPv6
+    /// range like ::/0 spans the IPv4 space as well. Splits such ranges
+    /// around the IPv4 mapped block ::ffff:0:0/96, and leaves that block
+    /// out, so that IPv6 ranges never include IPv4 addresses. IPv4 ranges
+    /// are limited to the block.
+    fn split_families(range: IpRange) -> Vec<IpRange> {
+        let v4_min = IpAddress::new(IPV4_IN_IPV6);
+        let v4_max = IpAddress::new(IPV4_IN_IPV6 | 0xffff_ffff);
+
+        match range.ip_address_family() {
+            IpAddressFamily::Ipv4 => vec![IpRange {
+                min: range.min,
+                max: cmp::min(range.max, v4_max),
+            }],
+            IpAddressFamily::Ipv6 => {
+                let mut parts = vec![];
+                if range.min < v4_min {
+                    let max = cmp::min(range.max, IpAddress::new(v4_min.value - 1));
+                    parts.push(IpRange {
+                        min: range.min,
+                        max,
+                    });
+                }
+                if range.max > v4_max {
+                    let min = cmp::max(range.min, IpAddress::new(v4_max.value + 1));
+                    parts.push(IpRange {
+                        min,
+                        max: range.max,
+                    });
+                }
+                parts
+            }
+        }
+    }
+
+    /// Joins the ranges like 'join', after splitting them so that each range
+    /// is in a single address family, see 'split_families'.
+    fn join_per_family(ranges: Vec<IpRange>) -> Vec<IpRange> {
+        Self::join(ranges.into_iter().flat_map(Self::split_families).collect())
+    }
+
+    /// Returns whether the range is fully within this set. Adjacent ranges
+    /// in the set are joined first, so the range may span more than one of
+    /// the ranges that were added. IPv6 ranges in the set do not contain
+    /// IPv4 ranges.
+    pub fn contains_range(&self, range: &IpRange) -> bool {
+        Self::join_per_family(self.ranges.clone())
+            .iter()
+            .any(|stored| {
+                stored.contains(&range.to_range()) && range.is_same_family(&stored.to_range())
+            })
+    }
+
+    /// Returns whether the address is in this set. IPv6 ranges in the set do
+    /// not contain IPv4 addresses.
+    pub fn contains_address(&self, address: &IpAddress) -> bool {
+        self.contains_range(&IpRange {
+            min: *address,
+            max: *address,
+        })
+    }
+
+    /// Returns the space that is in both sets. IPv6 ranges in one set do not
+    /// intersect IPv4 ranges in the other, see 'split_families'.
+    pub fn intersection(&self, other: &IpResourceSet) -> IpResourceSet {
+        let left = Self::join_per_family(self.ranges.clone());
+        let right = Self::join_per_family(other.ranges.clone());
+
+        let mut ranges = vec![];
+        let (mut i, mut j) = (0, 0);
+        while i < left.len() && j < right.len() {
+            let min = cmp::max(left[i].min, right[j].min);
+            let max = cmp::min(left[i].max, right[j].max);
+            if min <= max {
+                ranges.push(IpRange { min, max });
+            }
+            if left[i].max < right[j].max {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+
+        IpResourceSet { ranges }
+    }
+
+    /// Returns the space that is in either set. Overlapping and adjacent
+    /// ranges in the same address family are joined, see 'from_ranges'.
+    pub fn union(&self, other: &IpResourceSet) -> IpResourceSet {
+        let mut ranges = self.ranges.clone();
+        ranges.extend(other.ranges.iter().cloned());
+        Self::from_ranges(ranges)
+    }
+
+    /// Returns the number of addresses of the given family in this set. The
+    /// IPv4 mapped block is not counted as IPv6 space, so ::/0 counts as
+    /// 2^128 - 2^32 addresses.
+    pub fn address_count(&self, family: IpAddressFamily) -> u128 {
+        Self::join_per_family(self.ranges.clone())
+            .iter()
+            .filter(|range| range.ip_address_family() == family)
+            .map(|range| (range.max.value - range.min.value).saturating_add(1))
+            .fold(0, u128::saturating_add)
+    }
 }
 
 impl FromStr for IpResourceSet {
@@ -564,19 +1078,18 @@ impl FromStr for IpResourceSet {
 
 impl fmt::Debug for IpResourceSet {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+        write!(f, "{}", self)
     }
 }
 
 impl fmt::Display for IpResourceSet {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let len = self.ranges.len();
-        let last = len - 1;
-        for i in 0..len {
-            self.ranges[i].fmt(f)?;
-            if i != last {
-                write!(f, ",")?;
-            }
+        let mut ranges = self.ranges.iter();
+        if let Some(first) = ranges.next() {
+            first.fmt(f)?;
+        }
+        for range in ranges {
+            write!(f, ",{}", range)?;
         }
         Ok(())
     }
@@ -591,62 +1104,367 @@ impl Serialize for IpResourceSet {
     }
 }
 
+impl<'de> Deserialize<'de> for IpResourceSet {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_from_str(deserializer)
+    }
+}
+
 //------------ IpRangeTree --------------------------------------------------
 
+/// The structure used to look up values by range. By default this is an
+/// interval tree. With the 'trie' feature it is a prefix trie, which finds
+/// less specific matches faster in large tables. Both have the same queries.
+#[cfg(not(feature = "trie"))]
+pub type IpRangeTree<V> = IpIntervalTree<V>;
+
+#[cfg(feature = "trie")]
+pub type IpRangeTree<V> = IpPrefixTrie<V>;
+
+//------------ IpIntervalTree -----------------------------------------------
+
 #[derive(Debug)]
-pub struct IpRangeTree<V: AsRef<IpRange>> {
+pub struct IpIntervalTree<V: AsRef<IpRange>> {
     tree: IntervalTree<u128, Vec<V>>,
 }
 
-impl<V: AsRef<IpRange>> IpRangeTree<V> {
+impl<V: AsRef<IpRange>> IpIntervalTree<V> {
+    /// Creates a tree from elements with the values for each range. The
+    /// interval tree crate takes the end of a range as exclusive, so the
+    /// element ranges must end one past the last address, see 'half_open'.
     pub fn new(tree: IntervalTree<u128, Vec<V>>) -> Self {
-        IpRangeTree { tree }
+        IpIntervalTree { tree }
+    }
+
+    /// Creates a tree from the values for each range, with the ranges in
+    /// the inclusive form of 'IpRange::to_range'.
+    fn from_ranges(values: impl IntoIterator<Item = (Range<u128>, Vec<V>)>) -> Self {
+        let tree = values
+            .into_iter()
+            .map(|(range, values)| (Self::half_open(&range), values))
+            .collect();
+        IpIntervalTree { tree }
+    }
+
+    /// Returns the range with the end one past the last address, as the
+    /// interval tree expects. The end cannot go past u128::MAX, so only the
+    /// very last IPv6 address is not found on its own.
+    fn half_open(range: &Range<u128>) -> Range<u128> {
+        let end = range.end.checked_add(1).unwrap_or(range.end);
+        range.start..end
+    }
+
+    /// Returns the elements for ranges that overlap the given range, with
+    /// the range of their values.
+    fn query<'a>(
+        &'a self,
+        range: &IpRange,
+    ) -> impl Iterator<Item = (Range<u128>, &'a Vec<V>)> + 'a {
+        self.tree
+            .query(Self::half_open(&range.to_range()))
+            .map(|el| (el.value[0].as_ref().to_range(), &el.value))
     }
 
     pub fn matching_or_less_specific(&self, range: &IpRange) -> Vec<&V> {
         let mut res = vec![];
-        for el in self.tree.query(range.to_range()) {
-            if range.is_contained_by(&el.range) {
-                for matching_range in &el.value {
-                    res.push(matching_range)
-                }
+        for (el_range, values) in self.query(range) {
+            if range.is_contained_by(&el_range) && range.is_same_family(&el_range) {
+                res.extend(values)
             }
         }
         res
     }
 
-    pub fn matching_or_more_specific(&self, range: &IpRange) -> Vec<&V> {
+    /// Returns the value with the smallest range that contains the given
+    /// range, if any. If there are multiple values for that range, the first
+    /// one that was added is returned.
+    pub fn most_specific_match(&self, range: &IpRange) -> Option<&V> {
+        self.query(range)
+            .filter(|(el_range, _)| {
+                range.is_contained_by(el_range) && range.is_same_family(el_range)
+            })
+            .min_by_key(|(el_range, _)| el_range.end - el_range.start)
+            .and_then(|(_, values)| values.first())
+    }
+
+    /// Returns all values with a range that overlaps the given range, in
+    /// the same address family.
+    pub fn intersecting(&self, range: &IpRange) -> Vec<&V> {
         let mut res = vec![];
-        for el in self.tree.query(range.to_range()) {
-            if range.contains(&el.range) {
-                for matching_range in &el.value {
-                    res.push(matching_range)
-                }
+        for (el_range, values) in self.query(range) {
+            if range.intersects(IpRange::from(&el_range)) && range.is_same_family(&el_range) {
+                res.extend(values)
             }
         }
         res
     }
 
-    pub fn all(&self) -> Vec<&V> {
+    /// Returns all values with a range equal to the given range.
+    pub fn matching_exact(&self, range: &IpRange) -> Vec<&V> {
+        let query = range.to_range();
+        let mut res = vec![];
+        for (el_range, values) in self.query(range) {
+            if el_range == query {
+                res.extend(values)
+            }
+        }
+        res
+    }
+
+    pub fn matching_or_more_specific(&self, range: &IpRange) -> Vec<&V> {
         let mut res = vec![];
-        for el in self.tree.iter() {
-            for range in &el.value {
-                res.push(range)
+        for (el_range, values) in self.query(range) {
+            if range.contains(&el_range) && range.is_same_family(&el_range) {
+                res.extend(values)
             }
         }
         res
     }
 
+    pub fn all(&self) -> Vec<&V> {
+        self.iter_values().collect()
+    }
+
+    /// Iterates over all values in the tree, without collecting them first.
+    pub fn iter_values<'a>(&'a self) -> impl Iterator<Item = &'a V> + 'a {
+        self.tree.iter().flat_map(|el| el.value.iter())
+    }
+
     pub fn inner(&self) -> &IntervalTree<u128, Vec<V>> {
         &self.tree
     }
 }
 
-pub struct IpRangeTreeBuilder<V: AsRef<IpRange>> {
-    values: HashMap<Range<u128>, Vec<V>>,
+//------------ IpPrefixTrie -------------------------------------------------
+
+/// A path compressed binary trie (a Patricia trie) on the bits of prefixes,
+/// with the same queries as the interval tree. Nodes only exist for
+/// prefixes with values, and where prefixes branch, so IPv4 prefixes do
+/// not need a node for each bit of the IPv4 mapped space.
+///
+/// Values for ranges that are not a prefix, like some delegations, are kept
+/// in an interval tree next to the trie.
+#[derive(Debug)]
+pub struct IpPrefixTrie<V: AsRef<IpRange>> {
+    root: TrieNode<V>,
+    others: IpIntervalTree<V>,
 }
 
-impl<V: AsRef<IpRange>> IpRangeTreeBuilder<V> {
+#[derive(Debug)]
+struct TrieNode<V> {
+    start: u128,
+    length: u32,
+    values: Vec<V>,
+    children: [Option<Box<TrieNode<V>>>; 2],
+}
+
+/// Returns the bit of the value at the given depth, counting from the most
+/// significant bit.
+fn bit_at(value: u128, depth: u32) -> usize {
+    ((value >> (127 - depth)) & 1) as usize
+}
+
+impl<V> TrieNode<V> {
+    fn new(start: u128, length: u32, values: Vec<V>) -> Self {
+        TrieNode {
+            start,
+            length,
+            values,
+            children: [None, None],
+        }
+    }
+
+    fn range(&self) -> Range<u128> {
+        self.start..self.start | host_mask(self.length)
+    }
+
+    /// Returns whether this node is for the prefix, or a less specific.
+    fn covers(&self, start: u128, length: u32) -> bool {
+        self.length <= length && start & !host_mask(self.length) == self.start
+    }
+
+    /// Adds values for a prefix that this node covers. Adds a node for the
+    /// prefix if needed, and a node where it branches from an existing
+    /// child.
+    fn add(&mut self, start: u128, length: u32, values: Vec<V>) {
+        if self.length == length {
+            self.values.extend(values);
+            return;
+        }
+
+        let bit = bit_at(start, self.length);
+        let node = match self.children[bit].take() {
+            None => Box::new(TrieNode::new(start, length, values)),
+            Some(mut child) => {
+                if child.covers(start, length) {
+                    child.add(start, length, values);
+                    child
+                } else {
+                    let common = cmp::min(
+                        (child.start ^ start).leading_zeros(),
+                        cmp::min(child.length, length),
+                    );
+                    let mut parent = TrieNode::new(start & !host_mask(common), common, vec![]);
+                    let child_bit = bit_at(child.start, common);
+                    parent.children[child_bit] = Some(child);
+                    if common == length {
+                        parent.values = values;
+                    } else {
+                        parent.children[bit_at(start, common)] =
+                            Some(Box::new(TrieNode::new(start, length, values)));
+                    }
+                    Box::new(parent)
+                }
+            }
+        };
+        self.children[bit] = Some(node);
+    }
+}
+
+impl<V: AsRef<IpRange>> IpPrefixTrie<V> {
+    /// Calls 'visit' for the nodes with prefixes that contain the given
+    /// range, least specific first.
+    fn covering<'a>(&'a self, range: &IpRange, mut visit: impl FnMut(&'a TrieNode<V>)) {
+        let start = range.min.value;
+        let length = (start ^ range.max.value).leading_zeros();
+
+        let mut node = &self.root;
+        while node.covers(start, length) {
+            visit(node);
+            if node.length == length {
+                break;
+            }
+            match &node.children[bit_at(start, node.length)] {
+                Some(child) => node = child,
+                None => break,
+            }
+        }
+    }
+
+    /// Calls 'visit' for all nodes with a prefix that overlaps the given
+    /// range.
+    fn intersecting_nodes<'a>(&'a self, range: &IpRange, mut visit: impl FnMut(&'a TrieNode<V>)) {
+        let mut stack = vec![&self.root];
+        while let Some(node) = stack.pop() {
+            if !range.intersects(IpRange::from(&node.range())) {
+                continue;
+            }
+            visit(node);
+            for child in node.children.iter().rev().flatten() {
+                stack.push(child);
+            }
+        }
+    }
+
+    pub fn matching_or_less_specific(&self, range: &IpRange) -> Vec<&V> {
+        let mut res = vec![];
+        self.covering(range, |node| {
+            if range.is_same_family(&node.range()) {
+                res.extend(node.values.iter())
+            }
+        });
+        res.extend(self.others.matching_or_less_specific(range));
+        res
+    }
+
+    /// Returns the value with the smallest range that contains the given
+    /// range, if any. If there are multiple values for that range, the first
+    /// one that was added is returned.
+    pub fn most_specific_match(&self, range: &IpRange) -> Option<&V> {
+        let mut prefix = None;
+        self.covering(range, |node| {
+            if !node.values.is_empty() && range.is_same_family(&node.range()) {
+                prefix = node.values.first()
+            }
+        });
+
+        let size = |v: &V| {
+            let range = v.as_ref().to_range();
+            range.end - range.start
+        };
+
+        match (prefix, self.others.most_specific_match(range)) {
+            (Some(prefix), Some(other)) if size(other) < size(prefix) => Some(other),
+            (Some(prefix), _) => Some(prefix),
+            (None, other) => other,
+        }
+    }
+
+    /// Returns all values with a range that overlaps the given range, in
+    /// the same address family.
+    pub fn intersecting(&self, range: &IpRange) -> Vec<&V> {
+        let mut res = vec![];
+        self.intersecting_nodes(range, |node| {
+            if range.is_same_family(&node.range()) {
+                res.extend(node.values.iter())
+            }
+        });
+        res.extend(self.others.intersecting(range));
+        res
+    }
+
+    /// Returns all values with a range equal to the given range.
+    pub fn matching_exact(&self, range: &IpRange) -> Vec<&V> {
+        if !range.is_prefix() {
+            return self.others.matching_exact(range);
+        }
+        let mut res = vec![];
+        self.covering(range, |node| {
+            if node.range() == range.to_range() {
+                res.extend(node.values.iter())
+            }
+        });
+        res
+    }
+
+    pub fn matching_or_more_specific(&self, range: &IpRange) -> Vec<&V> {
+        let mut res = vec![];
+        self.intersecting_nodes(range, |node| {
+            let node_range = node.range();
+            if range.contains(&node_range) && range.is_same_family(&node_range) {
+                res.extend(node.values.iter())
+            }
+        });
+        res.extend(self.others.matching_or_more_specific(range));
+        res
+    }
+
+    pub fn all(&self) -> Vec<&V> {
+        self.iter_values().collect()
+    }
+
+    /// Iterates over all values in the trie, ordered by their lowest
+    /// address like in the interval tree, without collecting them first.
+    pub fn iter_values<'a>(&'a self) -> impl Iterator<Item = &'a V> + 'a {
+        let mut stack = vec![&self.root];
+        let mut prefixes = iter::from_fn(move || {
+            let node = stack.pop()?;
+            for child in node.children.iter().rev().flatten() {
+                stack.push(child);
+            }
+            Some(node.values.iter())
+        })
+        .flatten()
+        .peekable();
+        let mut others = self.others.iter_values().peekable();
+
+        let start = |v: &V| v.as_ref().min.value;
+        iter::from_fn(move || match (prefixes.peek(), others.peek()) {
+            (Some(prefix), Some(other)) if start(other) < start(prefix) => others.next(),
+            (Some(_), _) => prefixes.next(),
+            (None, _) => others.next(),
+        })
+    }
+}
+
+pub struct IpRangeTreeBuilder<V: AsRef<IpRange>> {
+    values: HashMap<Range<u128>, Vec<V>>,
+}
+
+impl<V: AsRef<IpRange>> IpRangeTreeBuilder<V> {
     pub fn empty() -> Self {
         IpRangeTreeBuilder {
             values: HashMap::new(),
@@ -660,9 +1478,145 @@ impl<V: AsRef<IpRange>> IpRangeTreeBuilder<V> {
         entry.push(value);
     }
 
+    #[cfg(not(feature = "trie"))]
     pub fn build(self) -> IpRangeTree<V> {
-        let tree = self.values.into_iter().collect();
-        IpRangeTree { tree }
+        self.build_interval_tree()
+    }
+
+    #[cfg(feature = "trie")]
+    pub fn build(self) -> IpRangeTree<V> {
+        self.build_prefix_trie()
+    }
+
+    pub fn build_interval_tree(self) -> IpIntervalTree<V> {
+        IpIntervalTree::from_ranges(self.values)
+    }
+
+    pub fn build_prefix_trie(self) -> IpPrefixTrie<V> {
+        let mut root = TrieNode::new(0, 0, vec![]);
+        let mut others = vec![];
+        for (range, values) in self.values {
+            if IpRange::from(&range).is_prefix() {
+                let length = (range.start ^ range.end).leading_zeros();
+                root.add(range.start, length, values);
+            } else {
+                others.push((range, values));
+            }
+        }
+        IpPrefixTrie {
+            root,
+            others: IpIntervalTree::from_ranges(others),
+        }
+    }
+}
+
+//------------ IpRangeOverlay -----------------------------------------------
+
+/// An IpRangeTree with changes layered on top, so that values can be added
+/// and removed without rebuilding the tree. Queries consult both the base
+/// tree and the changes.
+///
+/// Added values are kept in a list, so queries get slower as changes
+/// accumulate. Use 'compact' to fold the changes into a new base tree.
+#[derive(Debug)]
+pub struct IpRangeOverlay<V: AsRef<IpRange>> {
+    base: IpRangeTree<V>,
+    added: Vec<V>,
+    removed: HashSet<Range<u128>>,
+}
+
+impl<V: AsRef<IpRange>> IpRangeOverlay<V> {
+    pub fn new(base: IpRangeTree<V>) -> Self {
+        IpRangeOverlay {
+            base,
+            added: vec![],
+            removed: HashSet::new(),
+        }
+    }
+
+    pub fn add(&mut self, value: V) {
+        self.added.push(value);
+    }
+
+    /// Removes all values with exactly the given range, including values
+    /// added to the overlay before. Values added later are kept.
+    pub fn remove(&mut self, range: &IpRange) {
+        let range = range.to_range();
+        self.added.retain(|v| v.as_ref().to_range() != range);
+        self.removed.insert(range);
+    }
+
+    /// Returns the number of changes on top of the base tree.
+    pub fn changes(&self) -> usize {
+        self.added.len() + self.removed.len()
+    }
+
+    pub fn matching_or_less_specific(&self, range: &IpRange) -> Vec<&V> {
+        let base = self.base.matching_or_less_specific(range);
+        self.with_changes(base, |el| {
+            range.is_contained_by(&el) && range.is_same_family(&el)
+        })
+    }
+
+    pub fn matching_or_more_specific(&self, range: &IpRange) -> Vec<&V> {
+        let base = self.base.matching_or_more_specific(range);
+        self.with_changes(base, |el| range.contains(&el) && range.is_same_family(&el))
+    }
+
+    /// Returns all values with a range that overlaps the given range.
+    pub fn intersecting(&self, range: &IpRange) -> Vec<&V> {
+        let base = self.base.intersecting(range);
+        self.with_changes(base, |el| {
+            range.intersects(IpRange::from(&el)) && range.is_same_family(&el)
+        })
+    }
+
+    /// Returns all values with a range equal to the given range.
+    pub fn matching_exact(&self, range: &IpRange) -> Vec<&V> {
+        let query = range.to_range();
+        let base = self.base.matching_exact(range);
+        self.with_changes(base, |el| el == query)
+    }
+
+    /// Iterates over all values, without collecting them first.
+    pub fn iter_values<'a>(&'a self) -> impl Iterator<Item = &'a V> + 'a {
+        self.base
+            .iter_values()
+            .filter(move |v| !self.is_removed(v))
+            .chain(self.added.iter())
+    }
+
+    pub fn all(&self) -> Vec<&V> {
+        self.iter_values().collect()
+    }
+
+    fn is_removed(&self, value: &V) -> bool {
+        !self.removed.is_empty() && self.removed.contains(&value.as_ref().to_range())
+    }
+
+    /// Drops removed values from the base query result, and adds the added
+    /// values for which the range matches the query.
+    fn with_changes<'a>(
+        &'a self,
+        mut base: Vec<&'a V>,
+        matches: impl Fn(Range<u128>) -> bool,
+    ) -> Vec<&'a V> {
+        base.retain(|v| !self.is_removed(v));
+        base.extend(self.added.iter().filter(|v| matches(v.as_ref().to_range())));
+        base
+    }
+}
+
+impl<V: AsRef<IpRange> + Clone> IpRangeOverlay<V> {
+    /// Builds a new base tree with all changes applied.
+    pub fn compact(&mut self) {
+        let mut builder = IpRangeTreeBuilder::empty();
+        for value in self.iter_values() {
+            builder.add(value.clone());
+        }
+        self.base = builder.build();
+        self.added.clear();
+        self.removed.clear();
     }
 }
 
@@ -688,6 +1642,12 @@ pub enum IpRangeError {
     #[display(fmt = "Minimum value exceeds maximum value")]
     MinExceedsMax,
 
+    #[display(fmt = "Range must contain at least one address")]
+    NoAddresses,
+
+    #[display(fmt = "Range exceeds the address space")]
+    ExceedsAddressSpace,
+
     #[display(fmt = "Expected two IP addresses separated by '-' and no whitespace")]
     MustUseDashNotation,
 
@@ -709,6 +1669,12 @@ pub enum IpPrefixError {
     #[display(fmt = "Invalid prefix length")]
     InvalidPrefixLength,
 
+    #[display(fmt = "IPv4 prefix length must be 0-32, found: {}", _0)]
+    Ipv4PrefixLength(u8),
+
+    #[display(fmt = "IPv6 prefix length must be 0-128, found: {}", _0)]
+    Ipv6PrefixLength(u8),
+
     #[display(fmt = "Base address invalid: {}", _0)]
     InvalidBaseAddress(IpAddressError),
 }
@@ -757,8 +1723,15 @@ pub enum AsnError {
     #[display(fmt = "Invalid range. Expected something like: AS1-AS3")]
     InvalidRange,
 
-    #[display(fmt = "Invalid ASN. Expected something like: 1 or AS1")]
+    #[display(fmt = "Invalid ASN. Expected something like: 1, AS1 or 1.10")]
     InvalidAsn,
+
+    #[display(
+        fmt = "Set contains {} ASNs, can enumerate at most {}",
+        _0,
+        MAX_ASNS_TO_ENUMERATE
+    )]
+    TooManyAsns(u64),
 }
 
 //------------ Tests --------------------------------------------------------
@@ -767,6 +1740,122 @@ pub enum AsnError {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_asn_is_private() {
+        let private = |val| Asn::from(val).is_private();
+
+        assert!(!private(64511));
+        assert!(private(64512));
+        assert!(private(65534));
+        assert!(!private(65535));
+
+        assert!(!private(4_199_999_999));
+        assert!(private(4_200_000_000));
+        assert!(private(4_294_967_294));
+        assert!(!private(4_294_967_295));
+    }
+
+    #[test]
+    fn test_asn_asdot() {
+        let asn = |s: &str| Asn::from_str(s).unwrap();
+
+        assert_eq!(Asn::from(65536), asn("1.0"));
+        assert_eq!(Asn::from(65546), asn("AS1.10"));
+        assert_eq!(Asn::from(4_294_967_295), asn("65535.65535"));
+        assert_eq!(Asn::from(65000), asn("0.65000"));
+        assert_eq!(Asn::from(65000), asn("AS65000"));
+
+        assert!(Asn::from_str("1.65536").is_err());
+        assert!(Asn::from_str("65536.0").is_err());
+        assert!(Asn::from_str("1.").is_err());
+        assert!(Asn::from_str("1.2.3").is_err());
+
+        assert_eq!("1.0", asn("AS65536").to_asdot());
+        assert_eq!("1.10", asn("AS65546").to_asdot());
+        assert_eq!("65000", asn("AS65000").to_asdot());
+        assert_eq!(asn("AS65536"), asn(&asn("AS65536").to_asdot()));
+        assert_eq!("AS65536", asn("1.0").to_string());
+    }
+
+    #[test]
+    fn test_serde_round_trips() {
+        fn round_trip<T>(s: &str) -> T
+        where
+            T: Serialize + for<'de> Deserialize<'de> + FromStr + fmt::Debug + PartialEq,
+            T::Err: fmt::Debug,
+        {
+            let value = T::from_str(s).unwrap();
+            let json = serde_json::to_string(&value).unwrap();
+            let read: T = serde_json::from_str(&json).unwrap();
+            assert_eq!(value, read);
+            read
+        }
+
+        assert_eq!(Asn::from(65000), round_trip::<Asn>("AS65000"));
+        round_trip::<AsnRange>("AS65000-AS65003");
+        round_trip::<AsnRange>("AS65000");
+        round_trip::<IpAddress>("10.0.0.1");
+        round_trip::<IpAddress>("2001:db8::1");
+        round_trip::<IpRange>("10.0.0.0-10.0.1.255");
+        round_trip::<IpPrefix>("10.0.0.0/16");
+        round_trip::<IpPrefix>("2001:db8::/32");
+        round_trip::<IpResourceSet>("10.0.0.0/24, 10.0.2.0-10.0.2.9, 2001:db8::/32");
+
+        // Strings are parsed as on the command line
+        let asn: Asn = serde_json::from_str("\"1.10\"").unwrap();
+        assert_eq!(Asn::from(65546), asn);
+        assert!(serde_json::from_str::<Asn>("65000").is_err());
+        assert!(serde_json::from_str::<IpPrefix>("\"10.0.0.0/33\"").is_err());
+        assert!(serde_json::from_str::<IpRange>("\"10.0.0.0\"").is_err());
+    }
+
+    #[test]
+    fn test_asn_range_iter() {
+        let range = AsnRange::from_str("AS65000-AS65003").unwrap();
+        assert_eq!(4, range.len());
+
+        let asns: Vec<String> = range.iter().map(|asn| asn.to_string()).collect();
+        assert_eq!(vec!["AS65000", "AS65001", "AS65002", "AS65003"], asns);
+
+        let single = AsnRange::from_str("AS1-AS1").unwrap();
+        assert_eq!(
+            vec![Asn::from_str("AS1").unwrap()],
+            single.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_asn_set_display() {
+        assert_eq!("", AsnSet::empty().to_string());
+        assert_eq!("AS1", AsnSet::from_str("AS1").unwrap().to_string());
+        assert_eq!(
+            "AS1, AS3-AS4",
+            AsnSet::from_str("AS1, AS3-AS4").unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn test_asn_set_asns() {
+        let set = AsnSet::from_str("AS3, AS1-AS2, AS2-AS4").unwrap();
+        let asns: Vec<String> = set
+            .asns()
+            .unwrap()
+            .iter()
+            .map(|asn| asn.to_string())
+            .collect();
+        assert_eq!(vec!["AS1", "AS2", "AS3", "AS4"], asns);
+
+        // Overlapping ranges are only counted once against the cap.
+        let overlapping = AsnSet::from_str("AS1-AS60000, AS30000-AS65000").unwrap();
+        assert_eq!(65000, overlapping.asns().unwrap().len());
+
+        let huge = AsnSet::from_str("AS0-AS4294967295").unwrap();
+        match huge.asns() {
+            Err(AsnError::TooManyAsns(total)) => assert_eq!(4_294_967_296, total),
+            _ => panic!("Expected TooManyAsns"),
+        }
+    }
+
     #[test]
     fn test_make_ipv4_from_string() {
         assert_eq!(IPV4_IN_IPV6, IpAddress::from_str("0.0.0.0").unwrap().value);
@@ -796,6 +1885,29 @@ mod tests {
         assert!(IpAddress::from_str("1.1").is_err());
     }
 
+    #[test]
+    fn test_make_ip_address_from_net_types() {
+        let v4 = net::Ipv4Addr::new(192, 168, 0, 1);
+        let address = IpAddress::from(v4);
+        assert_eq!(IpAddressFamily::Ipv4, address.ip_address_family());
+        assert_eq!(IpAddress::from_str("192.168.0.1").unwrap(), address);
+        assert_eq!(IpAddress::from_u32(0xc0a8_0001), address);
+        assert_eq!(IpAddress::from_u128(IPV4_IN_IPV6 | 0xc0a8_0001), address);
+        assert_eq!(net::IpAddr::V4(v4), address.to_net_ipaddr());
+
+        let v6 = net::Ipv6Addr::from_str("2001:db8::1").unwrap();
+        let address = IpAddress::from(v6);
+        assert_eq!(IpAddressFamily::Ipv6, address.ip_address_family());
+        assert_eq!(IpAddress::from_str("2001:db8::1").unwrap(), address);
+        assert_eq!(IpAddress::from_u128(u128::from(v6)), address);
+        assert_eq!(net::IpAddr::V6(v6), address.to_net_ipaddr());
+
+        for s in &["10.0.0.0", "255.255.255.255", "::", "2001:db8::ff"] {
+            let addr = net::IpAddr::from_str(s).unwrap();
+            assert_eq!(addr, IpAddress::from(addr).to_net_ipaddr());
+        }
+    }
+
     #[test]
     fn test_is_ipv4() {
         assert_eq!(
@@ -822,6 +1934,24 @@ mod tests {
         assert!(range.is_err());
     }
 
+    #[test]
+    fn test_range_ordering() {
+        let mut ranges = vec![
+            IpRange::from_str("10.0.1.0-10.0.1.255").unwrap(),
+            IpRange::from_str("10.0.0.0-10.0.255.255").unwrap(),
+            IpRange::from_str("10.0.0.0-10.0.0.255").unwrap(),
+        ];
+        ranges.sort();
+        assert_eq!(
+            vec![
+                IpRange::from_str("10.0.0.0-10.0.0.255").unwrap(),
+                IpRange::from_str("10.0.0.0-10.0.255.255").unwrap(),
+                IpRange::from_str("10.0.1.0-10.0.1.255").unwrap(),
+            ],
+            ranges
+        );
+    }
+
     #[test]
     fn test_range_is_prefix() {
         assert!(IpRange::from_str("10.0.0.0-10.0.255.255")
@@ -863,6 +1993,36 @@ mod tests {
             .is_prefix());
     }
 
+    #[test]
+    fn test_compact_range() {
+        let compact = |s| {
+            let range = IpRange::from_str(s).unwrap();
+            let compact = CompactIpRange::from(range);
+            (
+                compact.to_string(),
+                serde_json::to_string(&compact).unwrap(),
+            )
+        };
+
+        let expected = |s: &str| (s.to_string(), format!("\"{}\"", s));
+
+        assert_eq!(expected("10.0.0.0/24"), compact("10.0.0.0-10.0.0.255"));
+        assert_eq!(expected("10.0.0.1/32"), compact("10.0.0.1-10.0.0.1"));
+        assert_eq!(expected("0.0.0.0/0"), compact("0.0.0.0-255.255.255.255"));
+        assert_eq!(
+            expected("2001:db8::/32"),
+            compact("2001:db8::-2001:db8:ffff:ffff:ffff:ffff:ffff:ffff")
+        );
+        assert_eq!(
+            expected("10.0.0.0-10.0.2.255"),
+            compact("10.0.0.0-10.0.2.255")
+        );
+        assert_eq!(
+            expected("2001:db8::1-2001:db8::2"),
+            compact("2001:db8::1-2001:db8::2")
+        );
+    }
+
     #[test]
     fn test_range_from_start_and_number() {
         let range = IpRange::from_str("10.0.0.0-10.0.0.255").unwrap();
@@ -872,6 +2032,44 @@ mod tests {
         assert_eq!(range, range_with_number);
     }
 
+    #[test]
+    fn test_range_from_start_and_zero_number() {
+        for min in &["10.0.0.0", "::"] {
+            let min = IpAddress::from_str(min).unwrap();
+            match IpRange::from_min_and_number(min, 0) {
+                Err(IpRangeError::NoAddresses) => {}
+                other => panic!("Expected NoAddresses, got: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_range_from_start_and_number_overflow() {
+        let range =
+            |min, number| IpRange::from_min_and_number(IpAddress::from_str(min).unwrap(), number);
+
+        assert_eq!(
+            IpRange::from_str("ffff::-ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff").unwrap(),
+            range("ffff::", 1 << 112).unwrap()
+        );
+        assert_eq!(
+            IpRange::from_str("255.255.255.0-255.255.255.255").unwrap(),
+            range("255.255.255.0", 256).unwrap()
+        );
+
+        for (min, number) in &[
+            ("ffff::", (1 << 112) + 1),
+            ("ffff::", u128::MAX),
+            ("::2", u128::MAX),
+            ("255.255.255.0", 257),
+        ] {
+            match range(min, *number) {
+                Err(IpRangeError::ExceedsAddressSpace) => {}
+                other => panic!("Expected ExceedsAddressSpace, got: {:?}", other),
+            }
+        }
+    }
+
     #[test]
     fn test_parse_prefix() {
         assert!(IpPrefix::from_str("10.0.0.0/8").is_ok());
@@ -879,6 +2077,102 @@ mod tests {
         assert!(IpPrefix::from_str("0.0.0.0/-1").is_err());
         assert!(IpPrefix::from_str("10.0.0.0/6").is_err());
         assert!(IpPrefix::from_str("10.0.0.0/33").is_err());
+        assert!(IpPrefix::from_str("10.0.0.0/200").is_err());
+    }
+
+    #[test]
+    fn test_parse_prefix_length_for_family() {
+        match IpPrefix::from_str("10.0.0.0/40") {
+            Err(IpPrefixError::Ipv4PrefixLength(40)) => {}
+            other => panic!("Expected Ipv4PrefixLength, got: {:?}", other),
+        }
+        match IpPrefix::from_str("2001:db8::/129") {
+            Err(IpPrefixError::Ipv6PrefixLength(129)) => {}
+            other => panic!("Expected Ipv6PrefixLength, got: {:?}", other),
+        }
+        assert_eq!(
+            "IPv4 prefix length must be 0-32, found: 40",
+            IpPrefix::from_str("10.0.0.0/40").unwrap_err().to_string()
+        );
+        assert!(IpPrefix::from_str("10.0.0.0/32").is_ok());
+        assert!(IpPrefix::from_str("2001:db8::/128").is_ok());
+    }
+
+    #[test]
+    fn test_make_prefix() {
+        let addr = |s| IpAddress::from_str(s).unwrap();
+
+        let v4 = IpPrefix::new(addr("10.0.0.0"), 8).unwrap();
+        assert_eq!(IpPrefix::from_str("10.0.0.0/8").unwrap(), v4);
+        let v4 = IpPrefix::new(addr("10.0.0.1"), 32).unwrap();
+        assert_eq!(IpPrefix::from_str("10.0.0.1/32").unwrap(), v4);
+        let v6 = IpPrefix::new(addr("2001:db8::"), 32).unwrap();
+        assert_eq!(IpPrefix::from_str("2001:db8::/32").unwrap(), v6);
+        let v6 = IpPrefix::new(addr("::"), 0).unwrap();
+        assert_eq!(IpPrefix::from_str("::/0").unwrap(), v6);
+
+        // Misaligned, or too long for the address family
+        assert!(IpPrefix::new(addr("10.0.0.1"), 24).is_err());
+        assert!(IpPrefix::new(addr("10.0.0.0"), 6).is_err());
+        assert!(IpPrefix::new(addr("10.0.0.0"), 33).is_err());
+        assert!(IpPrefix::new(addr("10.0.0.0"), 255).is_err());
+        assert!(IpPrefix::new(addr("2001:db8::1"), 64).is_err());
+        assert!(IpPrefix::new(addr("2001:db8::"), 129).is_err());
+    }
+
+    #[test]
+    fn test_default_routes() {
+        let v4 = IpPrefix::from_str("0.0.0.0/0").unwrap();
+        assert_eq!(0, v4.length());
+        assert_eq!("0.0.0.0/0", v4.to_string());
+        assert_eq!(
+            IpRange::from_str("0.0.0.0-255.255.255.255").unwrap(),
+            v4.range
+        );
+        assert!(v4.range.is_prefix());
+
+        let v6 = IpPrefix::from_str("::/0").unwrap();
+        assert_eq!(0, v6.length());
+        assert_eq!("::/0", v6.to_string());
+        assert_eq!(
+            IpRange::from_str("::-ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff").unwrap(),
+            v6.range
+        );
+        assert!(v6.range.is_prefix());
+
+        let mut builder = IpRangeTreeBuilder::empty();
+        builder.add(v4.clone());
+        builder.add(v6.clone());
+        let tree = builder.build();
+
+        let find = |s| {
+            let range = IpPrefix::from_str(s).unwrap().range;
+            tree.matching_or_less_specific(&range)
+                .into_iter()
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec![v4.clone()], find("10.0.0.0/8"));
+        assert_eq!(vec![v4.clone()], find("0.0.0.0/0"));
+        assert_eq!(vec![v6.clone()], find("2001:db8::/32"));
+        assert_eq!(vec![v6.clone()], find("::/0"));
+
+        assert_eq!(1, tree.matching_exact(&v4.range).len());
+        assert_eq!(1, tree.matching_or_more_specific(&v6.range).len());
+        assert_eq!(1, tree.matching_or_more_specific(&v4.range).len());
+
+        let intersecting = |s| {
+            let range = IpPrefix::from_str(s).unwrap().range;
+            tree.intersecting(&range)
+                .into_iter()
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec![v4.clone()], intersecting("10.0.0.0/8"));
+        assert_eq!(vec![v6.clone()], intersecting("::/0"));
+        assert_eq!(vec![v6], intersecting("2001:db8::/32"));
     }
 
     #[test]
@@ -901,6 +2195,17 @@ mod tests {
         assert!(!range.intersects(above));
     }
 
+    #[test]
+    fn test_ip_resource_set_display() {
+        assert_eq!("", IpResourceSet::empty().to_string());
+
+        let set = IpResourceSet::from_str("10.0.0.0/24, 192.168.0.0/16").unwrap();
+        assert_eq!(
+            "10.0.0.0-10.0.0.255,192.168.0.0-192.168.255.255",
+            set.to_string()
+        );
+    }
+
     #[test]
     fn test_ip_resource_set_functions() {
         let range = IpRange::from_str("10.0.0.0-10.0.0.255").unwrap();
@@ -920,6 +2225,224 @@ mod tests {
         assert_eq!(set.ranges, vec![expected_combined_range, other_range]);
     }
 
+    #[test]
+    fn test_ip_resource_set_contains_address() {
+        let set = IpResourceSet::from_str("10.0.0.0/25, 10.0.0.128/25, ::/0").unwrap();
+        let contains = |s| set.contains_address(&IpAddress::from_str(s).unwrap());
+
+        // on the boundaries of the set, and of the merged entries
+        assert!(contains("10.0.0.0"));
+        assert!(contains("10.0.0.127"));
+        assert!(contains("10.0.0.128"));
+        assert!(contains("10.0.0.255"));
+        assert!(!contains("9.255.255.255"));
+        assert!(!contains("10.0.1.0"));
+
+        // IPv4 addresses are not in IPv6 ranges
+        assert!(contains("2001:db8::1"));
+        assert!(!contains("192.168.0.1"));
+        assert!(!IpResourceSet::empty().contains_address(&IpAddress::from_str("10.0.0.0").unwrap()));
+    }
+
+    #[test]
+    fn test_ip_resource_set_contains_range() {
+        let set = IpResourceSet::from_str("10.0.0.0/25, 10.0.0.128/25, 2001:db8::/32").unwrap();
+        let range = |s: &str| {
+            if s.contains('/') {
+                IpRange::from(IpPrefix::from_str(s).unwrap())
+            } else {
+                IpRange::from_str(s).unwrap()
+            }
+        };
+
+        // fully inside, also when spanning adjacent ranges in the set
+        assert!(set.contains_range(&range("10.0.0.0/26")));
+        assert!(set.contains_range(&range("10.0.0.0/24")));
+        assert!(set.contains_range(&range("2001:db8:1::/48")));
+
+        // partially overlapping
+        assert!(!set.contains_range(&range("10.0.0.0/23")));
+        assert!(!set.contains_range(&range("10.0.0.255-10.0.1.0")));
+        assert!(!set.contains_range(&range("2001:db8::/31")));
+
+        // outside
+        assert!(!set.contains_range(&range("192.168.0.0/24")));
+        assert!(!IpResourceSet::empty().contains_range(&range("10.0.0.0/24")));
+    }
+
+    #[test]
+    fn test_range_common_prefix() {
+        let range = |s| IpRange::from(IpPrefix::from_str(s).unwrap());
+        let common = |a, b| range(a).common_prefix(&range(b)).to_string();
+
+        assert_eq!("10.0.0.0/23", common("10.0.0.0/24", "10.0.1.0/24"));
+        assert_eq!("10.0.0.0/23", common("10.0.1.0/24", "10.0.0.0/24"));
+        assert_eq!("10.0.0.0/22", common("10.0.1.0/24", "10.0.2.0/24"));
+        assert_eq!("10.0.0.0/16", common("10.0.0.0/16", "10.0.1.0/24"));
+        assert_eq!("10.0.0.1/32", common("10.0.0.1/32", "10.0.0.1/32"));
+        assert_eq!("0.0.0.0/0", common("10.0.0.0/8", "192.168.0.0/16"));
+        assert_eq!("2001:db8::/31", common("2001:db8::/32", "2001:db9::/32"));
+
+        let range = IpRange::from_str("10.0.0.1-10.0.0.6").unwrap();
+        assert_eq!("10.0.0.0/29", range.common_prefix(&range).to_string());
+    }
+
+    #[test]
+    fn test_range_to_prefixes() {
+        let prefixes = |s| {
+            IpRange::from_str(s)
+                .unwrap()
+                .to_prefixes()
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec!["10.0.0.0/24"], prefixes("10.0.0.0-10.0.0.255"));
+        assert_eq!(vec!["10.0.0.1/32"], prefixes("10.0.0.1-10.0.0.1"));
+        assert_eq!(
+            vec!["10.0.0.1/32", "10.0.0.2/31", "10.0.0.4/30", "10.0.0.8/32"],
+            prefixes("10.0.0.1-10.0.0.8")
+        );
+        assert_eq!(
+            vec![
+                "10.0.0.5/32",
+                "10.0.0.6/31",
+                "10.0.0.8/29",
+                "10.0.0.16/30",
+                "10.0.0.20/32"
+            ],
+            prefixes("10.0.0.5-10.0.0.20")
+        );
+        assert_eq!(vec!["2001:db8::1/128"], prefixes("2001:db8::1-2001:db8::1"));
+        assert_eq!(vec!["0.0.0.0/0"], prefixes("0.0.0.0-255.255.255.255"));
+        assert_eq!(
+            vec!["::/0"],
+            prefixes("::-ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff")
+        );
+        assert_eq!(
+            vec!["2001:db8::/32", "2001:db9::/48"],
+            prefixes("2001:db8::-2001:db9:0:ffff:ffff:ffff:ffff:ffff")
+        );
+    }
+
+    #[test]
+    fn test_ip_resource_set_to_prefixes() {
+        let set = IpResourceSet::from_str(
+            "10.0.1.0/24, 10.0.0.0/24, 10.0.0.128/25, 10.0.2.0-10.0.3.255, \
+             192.168.0.0/24, 2001:db8::/33, 2001:db8:8000::/33",
+        )
+        .unwrap();
+
+        let mut aggregated = IpResourceSet::empty();
+        for range in set.ranges() {
+            aggregated.add_ip_range(*range);
+        }
+
+        let prefixes: Vec<String> = aggregated
+            .to_prefixes()
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        assert_eq!(
+            vec!["10.0.0.0/22", "192.168.0.0/24", "2001:db8::/32"],
+            prefixes
+        );
+    }
+
+    #[test]
+    fn test_ip_resource_set_intersection_and_count() {
+        let routed = IpResourceSet::from_ranges(vec![
+            IpPrefix::from_str("10.0.0.0/16").unwrap().range,
+            IpPrefix::from_str("10.0.1.0/24").unwrap().range,
+            IpPrefix::from_str("10.1.0.0/16").unwrap().range,
+            IpPrefix::from_str("2001:db8::/32").unwrap().range,
+        ]);
+        assert_eq!(2, routed.ranges().len());
+        assert_eq!(131_072, routed.address_count(IpAddressFamily::Ipv4));
+        assert_eq!(1 << 96, routed.address_count(IpAddressFamily::Ipv6));
+
+        let covering = IpResourceSet::from_str(
+            "10.0.0.0/17, 10.0.255.0-10.1.0.255, 2001:db8::/34, 192.168.0.0/16",
+        )
+        .unwrap();
+        let covered = routed.intersection(&covering);
+        assert_eq!(
+            "10.0.0.0-10.0.127.255,10.0.255.0-10.1.0.255,2001:db8::-2001:db8:3fff:ffff:ffff:ffff:ffff:ffff",
+            covered.to_string()
+        );
+        assert_eq!(32_768 + 512, covered.address_count(IpAddressFamily::Ipv4));
+        assert_eq!(1 << 94, covered.address_count(IpAddressFamily::Ipv6));
+    }
+
+    #[test]
+    fn test_ip_resource_set_union_and_intersection() {
+        let set = |s| IpResourceSet::from_str(s).unwrap();
+        let check = |left, right, union: &str, intersection: &str| {
+            let (left, right) = (set(left), set(right));
+            assert_eq!(union, left.union(&right).to_string());
+            assert_eq!(union, right.union(&left).to_string());
+            assert_eq!(intersection, left.intersection(&right).to_string());
+            assert_eq!(intersection, right.intersection(&left).to_string());
+        };
+
+        // disjoint
+        check(
+            "10.0.0.0/24",
+            "10.0.2.0/24, 2001:db8::/32",
+            "10.0.0.0-10.0.0.255,10.0.2.0-10.0.2.255,2001:db8::-2001:db8:ffff:ffff:ffff:ffff:ffff:ffff",
+            "",
+        );
+
+        // disjoint, but adjacent
+        check("10.0.0.0/24", "10.0.1.0/24", "10.0.0.0-10.0.1.255", "");
+
+        // overlapping
+        check(
+            "10.0.0.0/23",
+            "10.0.1.0-10.0.2.255",
+            "10.0.0.0-10.0.2.255",
+            "10.0.1.0-10.0.1.255",
+        );
+
+        // fully contained
+        check(
+            "10.0.0.0/16, 2001:db8::/32",
+            "10.0.1.0/24, 2001:db8:1::/48",
+            "10.0.0.0-10.0.255.255,2001:db8::-2001:db8:ffff:ffff:ffff:ffff:ffff:ffff",
+            "10.0.1.0-10.0.1.255,2001:db8:1::-2001:db8:1:ffff:ffff:ffff:ffff:ffff",
+        );
+
+        let empty = IpResourceSet::empty();
+        assert_eq!(set("10.0.0.0/24"), set("10.0.0.0/24").union(&empty));
+        assert!(set("10.0.0.0/24").intersection(&empty).is_empty());
+    }
+
+    #[test]
+    fn test_ip_resource_set_keeps_ipv4_out_of_ipv6_ranges() {
+        let all_v6 = IpResourceSet::from_str("::/0").unwrap();
+        let v4 = IpResourceSet::from_str("10.0.0.0/8").unwrap();
+
+        assert_eq!(0, all_v6.address_count(IpAddressFamily::Ipv4));
+        assert_eq!(
+            u128::MAX - 0xffff_ffff,
+            all_v6.address_count(IpAddressFamily::Ipv6)
+        );
+        assert!(all_v6.intersection(&v4).is_empty());
+        assert!(v4.intersection(&all_v6).is_empty());
+        assert!(!all_v6.contains_range(&IpPrefix::from_str("10.0.0.0/8").unwrap().range));
+        assert!(all_v6.contains_range(&IpPrefix::from_str("2001:db8::/32").unwrap().range));
+
+        // The IPv6 parts around the IPv4 mapped block are kept, the block
+        // itself only as IPv4.
+        let both = IpResourceSet::from_str("::/0, 0.0.0.0/0").unwrap();
+        let around = IpResourceSet::from_str("::/64, ffff::/16, 10.0.0.0/8").unwrap();
+        assert_eq!(
+            "::-::fffe:ffff:ffff,10.0.0.0-10.255.255.255,::1:0:0:0-::ffff:ffff:ffff:ffff,ffff::-ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff",
+            both.intersection(&around).to_string()
+        );
+    }
+
     #[test]
     fn test_ip_resource_set_remove() {
         let range = IpRange::from_str("10.0.0.0-10.0.0.255").unwrap();
@@ -1024,4 +2547,295 @@ mod tests {
         let matches = tree.matching_or_less_specific(&search);
         assert_eq!(3, matches.len());
     }
+
+    /// A value for the range trees, that is told apart from values with
+    /// the same range by its index.
+    #[derive(Clone)]
+    struct IndexedRange {
+        index: usize,
+        range: IpRange,
+    }
+
+    impl AsRef<IpRange> for IndexedRange {
+        fn as_ref(&self) -> &IpRange {
+            &self.range
+        }
+    }
+
+    /// Builds a tree with the named ranges, with the index of the name.
+    fn named_range_tree(named: &[(&str, &str)]) -> IpRangeTree<IndexedRange> {
+        let mut builder = IpRangeTreeBuilder::empty();
+        for (index, (_, range)) in named.iter().enumerate() {
+            builder.add(IndexedRange {
+                index,
+                range: IpRange::from_str(range).unwrap(),
+            });
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn test_ip_range_tree_most_specific_match() {
+        let named = [
+            ("/8", "10.0.0.0-10.255.255.255"),
+            ("/24", "10.0.0.0-10.0.0.255"),
+            ("/16", "10.0.0.0-10.0.255.255"),
+            ("other", "10.1.0.0-10.1.0.255"),
+        ];
+        let tree = named_range_tree(&named);
+
+        let find = |s| {
+            tree.most_specific_match(&IpRange::from_str(s).unwrap())
+                .map(|v| named[v.index].0)
+        };
+
+        assert_eq!(Some("/24"), find("10.0.0.0-10.0.0.127"));
+        assert_eq!(Some("/24"), find("10.0.0.0-10.0.0.255"));
+        assert_eq!(Some("/16"), find("10.0.1.0-10.0.1.255"));
+        assert_eq!(Some("/8"), find("10.2.0.0-10.2.0.255"));
+        assert_eq!(Some("other"), find("10.1.0.0-10.1.0.255"));
+        assert_eq!(None, find("11.0.0.0-11.0.0.255"));
+    }
+
+    #[test]
+    fn test_ip_range_tree_iter_values() {
+        let ranges = [
+            "10.0.0.0-10.0.255.255",
+            "10.0.0.0-10.0.0.255",
+            "10.0.0.0-10.0.0.255",
+            "10.0.1.0-10.0.1.255",
+            "2001:db8::-2001:db8::ffff",
+        ];
+
+        let mut builder = IpRangeTreeBuilder::empty();
+        for (i, range) in ranges.iter().enumerate() {
+            builder.add(IndexedRange {
+                index: i,
+                range: IpRange::from_str(range).unwrap(),
+            });
+        }
+        let tree = builder.build();
+
+        let mut seen: Vec<usize> = tree.iter_values().map(|v| v.index).collect();
+        seen.sort();
+        assert_eq!(vec![0, 1, 2, 3, 4], seen);
+        assert_eq!(tree.all().len(), tree.iter_values().count());
+    }
+
+    #[test]
+    fn test_ip_range_tree_matching_exact() {
+        let named = [
+            ("/16", "10.0.0.0-10.0.255.255"),
+            ("/24 a", "10.0.0.0-10.0.0.255"),
+            ("/24 b", "10.0.0.0-10.0.0.255"),
+            ("other", "10.0.1.0-10.0.1.255"),
+        ];
+        let tree = named_range_tree(&named);
+
+        let find = |s| {
+            let mut names: Vec<_> = tree
+                .matching_exact(&IpRange::from_str(s).unwrap())
+                .iter()
+                .map(|v| named[v.index].0)
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(vec!["/16"], find("10.0.0.0-10.0.255.255"));
+        assert_eq!(vec!["/24 a", "/24 b"], find("10.0.0.0-10.0.0.255"));
+        assert!(find("10.0.0.0-10.0.0.127").is_empty());
+        assert!(find("10.0.0.0-10.255.255.255").is_empty());
+    }
+
+    #[test]
+    fn test_ip_interval_tree_finds_single_and_last_addresses() {
+        let ranges = [
+            "10.0.0.1-10.0.0.1",
+            "10.0.0.0-10.0.0.255",
+            "2001:db8::1-2001:db8::1",
+        ];
+        let mut builder = IpRangeTreeBuilder::empty();
+        for (index, range) in ranges.iter().enumerate() {
+            builder.add(IndexedRange {
+                index,
+                range: IpRange::from_str(range).unwrap(),
+            });
+        }
+        let tree = builder.build_interval_tree();
+
+        let range = |s| IpRange::from_str(s).unwrap();
+        let indexes = |values: Vec<&IndexedRange>| {
+            let mut indexes: Vec<usize> = values.iter().map(|v| v.index).collect();
+            indexes.sort();
+            indexes
+        };
+
+        let less_specific = |s| indexes(tree.matching_or_less_specific(&range(s)));
+        assert_eq!(vec![0, 1], less_specific("10.0.0.1-10.0.0.1"));
+        assert_eq!(vec![1], less_specific("10.0.0.255-10.0.0.255"));
+        assert_eq!(vec![2], less_specific("2001:db8::1-2001:db8::1"));
+
+        let exact = indexes(tree.matching_exact(&range("10.0.0.1-10.0.0.1")));
+        assert_eq!(vec![0], exact);
+
+        let more_specific = indexes(tree.matching_or_more_specific(&range("10.0.0.0-10.0.0.255")));
+        assert_eq!(vec![0, 1], more_specific);
+
+        let intersecting = |s| indexes(tree.intersecting(&range(s)));
+        assert_eq!(vec![1], intersecting("10.0.0.255-10.0.1.0"));
+        assert!(intersecting("10.0.1.0-10.0.1.255").is_empty());
+    }
+
+    #[test]
+    fn test_ip_prefix_trie_matches_interval_tree() {
+        use crate::vrps::Vrps;
+        use std::path::PathBuf;
+
+        // The VRP prefixes, including single addresses, and some ranges
+        // that are not a prefix.
+        let vrps = Vrps::from_file(&PathBuf::from("test/20190304/vrps.csv")).unwrap();
+        let mut ranges: Vec<IpRange> = vrps.iter().map(|vrp| *vrp.as_ref()).collect();
+        let others: Vec<IpRange> = ranges
+            .iter()
+            .step_by(100)
+            .filter(|range| range.max.value - range.min.value > 2)
+            .map(|range| IpRange {
+                min: IpAddress::new(range.min.value + 1),
+                max: range.max,
+            })
+            .collect();
+        ranges.extend(others);
+
+        let builder = || {
+            let mut builder = IpRangeTreeBuilder::empty();
+            for (index, range) in ranges.iter().enumerate() {
+                builder.add(IndexedRange {
+                    index,
+                    range: *range,
+                });
+            }
+            builder
+        };
+        let tree = builder().build_interval_tree();
+        let trie = builder().build_prefix_trie();
+
+        let indexes = |values: Vec<&IndexedRange>| {
+            let mut indexes: Vec<usize> = values.iter().map(|v| v.index).collect();
+            indexes.sort();
+            indexes
+        };
+        let size =
+            |value: Option<&IndexedRange>| value.map(|v| v.range.max.value - v.range.min.value);
+
+        let mut queries: Vec<IpRange> = ranges.iter().step_by(13).cloned().collect();
+        for range in &[
+            "0.0.0.0-255.255.255.255",
+            "10.0.0.0-10.255.255.255",
+            "2001::-2001:ffff::",
+        ] {
+            queries.push(IpRange::from_str(range).unwrap());
+        }
+
+        for query in &queries {
+            assert_eq!(
+                indexes(tree.matching_or_less_specific(query)),
+                indexes(trie.matching_or_less_specific(query)),
+                "less specific of {}",
+                query
+            );
+            assert_eq!(
+                indexes(tree.matching_or_more_specific(query)),
+                indexes(trie.matching_or_more_specific(query)),
+                "more specific of {}",
+                query
+            );
+            assert_eq!(
+                indexes(tree.intersecting(query)),
+                indexes(trie.intersecting(query)),
+                "intersecting {}",
+                query
+            );
+            assert_eq!(
+                indexes(tree.matching_exact(query)),
+                indexes(trie.matching_exact(query)),
+                "exact {}",
+                query
+            );
+            assert_eq!(
+                size(tree.most_specific_match(query)),
+                size(trie.most_specific_match(query)),
+                "most specific of {}",
+                query
+            );
+        }
+
+        let starts: Vec<u128> = trie.iter_values().map(|v| v.range.min.value).collect();
+        assert!(starts.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(indexes(tree.all()), indexes(trie.all()));
+    }
+
+    #[test]
+    fn test_ip_range_overlay() {
+        let indexed = |index, range| IndexedRange {
+            index,
+            range: IpRange::from_str(range).unwrap(),
+        };
+
+        let mut builder = IpRangeTreeBuilder::empty();
+        builder.add(indexed(0, "10.0.0.0-10.0.255.255"));
+        builder.add(indexed(1, "10.0.0.0-10.0.0.255"));
+        builder.add(indexed(2, "10.0.1.0-10.0.1.255"));
+        let mut overlay = IpRangeOverlay::new(builder.build());
+
+        let range = |s| IpRange::from_str(s).unwrap();
+        let indexes = |values: Vec<&IndexedRange>| {
+            let mut res: Vec<usize> = values.iter().map(|v| v.index).collect();
+            res.sort();
+            res
+        };
+
+        overlay.add(indexed(3, "10.0.2.0-10.0.2.255"));
+        overlay.add(indexed(4, "10.0.0.0-10.255.255.255"));
+        assert_eq!(
+            vec![0, 1, 2, 3],
+            indexes(overlay.matching_or_more_specific(&range("10.0.0.0-10.0.255.255")))
+        );
+        assert_eq!(
+            vec![0, 3, 4],
+            indexes(overlay.matching_or_less_specific(&range("10.0.2.0-10.0.2.127")))
+        );
+        assert_eq!(
+            vec![3],
+            indexes(overlay.matching_exact(&range("10.0.2.0-10.0.2.255")))
+        );
+
+        overlay.remove(&range("10.0.0.0-10.0.255.255"));
+        overlay.remove(&range("10.0.2.0-10.0.2.255"));
+        assert_eq!(
+            vec![1, 2],
+            indexes(overlay.matching_or_more_specific(&range("10.0.0.0-10.0.255.255")))
+        );
+        assert_eq!(
+            vec![1, 4],
+            indexes(overlay.intersecting(&range("10.0.0.128-10.0.0.255")))
+        );
+        assert_eq!(vec![1, 2, 4], indexes(overlay.all()));
+
+        // Adding a removed range again makes it visible
+        overlay.add(indexed(5, "10.0.0.0-10.0.255.255"));
+        assert_eq!(
+            vec![5],
+            indexes(overlay.matching_exact(&range("10.0.0.0-10.0.255.255")))
+        );
+        assert_eq!(4, overlay.changes());
+
+        overlay.compact();
+        assert_eq!(0, overlay.changes());
+        assert_eq!(vec![1, 2, 4, 5], indexes(overlay.all()));
+        assert_eq!(
+            vec![1, 4, 5],
+            indexes(overlay.matching_or_less_specific(&range("10.0.0.0-10.0.0.255")))
+        );
+    }
 }
@@ -0,0 +1,307 @@
+//! Parse route and route6 objects from an IRR export, and cross-check them
+//! with the VRPs. This helps to track the migration from IRR to RPKI.
+use crate::ip::Asn;
+use crate::ip::AsnError;
+use crate::ip::IpPrefix;
+use crate::ip::IpPrefixError;
+use crate::ip::IpRange;
+use crate::ip::IpRangeTree;
+use crate::ip::IpRangeTreeBuilder;
+use crate::report::ScopeLimits;
+use crate::vrps::Vrps;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+//------------ IrrRoute ------------------------------------------------------
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct IrrRoute {
+    prefix: IpPrefix,
+    origin: Asn,
+}
+
+impl IrrRoute {
+    pub fn new(prefix: IpPrefix, origin: Asn) -> Self {
+        IrrRoute { prefix, origin }
+    }
+
+    pub fn prefix(&self) -> &IpPrefix {
+        &self.prefix
+    }
+    pub fn origin(&self) -> Asn {
+        self.origin
+    }
+}
+
+impl AsRef<IpRange> for IrrRoute {
+    fn as_ref(&self) -> &IpRange {
+        self.prefix.as_ref()
+    }
+}
+
+impl fmt::Display for IrrRoute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.prefix, self.origin)
+    }
+}
+
+//------------ IrrRoutes -----------------------------------------------------
+
+#[derive(Debug)]
+pub struct IrrRoutes {
+    tree: IpRangeTree<IrrRoute>,
+}
+
+impl IrrRoutes {
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let content = fs::read_to_string(path)
+            .map_err(|_| Error::CannotRead(path.to_string_lossy().to_string()))?;
+        Self::from_str(&content)
+    }
+
+    pub fn all(&self) -> Vec<&IrrRoute> {
+        self.tree.all()
+    }
+
+    pub fn in_scope(&self, scope: &ScopeLimits) -> Vec<&IrrRoute> {
+        let asn_set = scope.asns();
+        let in_limits = |route: &&IrrRoute| {
+            (!scope.limits_asns() || asn_set.contains(route.origin()))
+                && scope.includes_family(route.prefix().ip_address_family())
+        };
+
+        if scope.limits_ips() {
+            scope
+                .ips()
+                .ranges()
+                .iter()
+                .flat_map(|range| self.tree.matching_or_more_specific(range))
+                .filter(in_limits)
+                .collect()
+        } else {
+            self.tree.iter_values().filter(in_limits).collect()
+        }
+    }
+
+    /// Returns the route in an RPSL object, if it is a route or route6
+    /// object. Other classes, like aut-num, are skipped.
+    fn from_object(attributes: &[(String, String)]) -> Result<Option<IrrRoute>, Error> {
+        match attributes.first() {
+            Some((class, prefix)) if class == "route" || class == "route6" => {
+                let prefix = IpPrefix::from_str(prefix)?;
+                let origin = attributes
+                    .iter()
+                    .find(|(key, _)| key == "origin")
+                    .ok_or_else(|| Error::MissingOrigin(prefix.to_string()))?;
+                let origin = Asn::from_str(&origin.1)?;
+                Ok(Some(IrrRoute::new(prefix, origin)))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+impl FromStr for IrrRoutes {
+    type Err = Error;
+
+    /// Expects RPSL objects separated by empty lines. Only the first line of
+    /// attributes that continue over multiple lines is used, which is fine
+    /// for the attributes that matter here. Lines starting with '%' or '#'
+    /// are comments.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut builder = IpRangeTreeBuilder::empty();
+        let mut attributes = vec![];
+
+        for line in s.lines().chain(::std::iter::once("")) {
+            if line.trim().is_empty() {
+                if let Some(route) = Self::from_object(&attributes)? {
+                    builder.add(route);
+                }
+                attributes.clear();
+            } else if line.starts_with('%') || line.starts_with('#') {
+                continue;
+            } else if line.starts_with(char::is_whitespace) || line.starts_with('+') {
+                continue; // continuation line
+            } else if let Some(colon) = line.find(':') {
+                let key = line[..colon].trim().to_lowercase();
+                let value = line[colon + 1..].trim().to_string();
+                attributes.push((key, value));
+            } else {
+                return Err(Error::ParseError(format!(
+                    "not an RPSL attribute: {}",
+                    line
+                )));
+            }
+        }
+
+        Ok(IrrRoutes {
+            tree: builder.build(),
+        })
+    }
+}
+
+//------------ IrrConflict ---------------------------------------------------
+
+/// An IRR route that is covered by VRPs, but none of them are for the
+/// origin of the route. Announcements matching the route would be invalid.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct IrrConflict {
+    route: IrrRoute,
+    vrp_asns: Vec<Asn>,
+}
+
+impl IrrConflict {
+    pub fn route(&self) -> &IrrRoute {
+        &self.route
+    }
+    pub fn vrp_asns(&self) -> &[Asn] {
+        &self.vrp_asns
+    }
+}
+
+//------------ IrrCoverage ---------------------------------------------------
+
+/// The RPKI coverage of IRR routes: whether there are ROAs for them, and
+/// whether these ROAs are for the same origin. See the irr-check report for
+/// full validation of the routes, including their max length.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct IrrCoverage {
+    routes: usize,
+
+    /// Routes that are covered by a VRP for the same origin.
+    matching: usize,
+
+    /// Routes that are not covered by any VRP.
+    missing_roa: Vec<IrrRoute>,
+
+    /// Routes that are covered by VRPs for other origins only.
+    conflicts: Vec<IrrConflict>,
+}
+
+impl IrrCoverage {
+    /// Checks all IRR routes in scope. VRPs outside of the scope are used
+    /// as well, because less specific VRPs can cover routes in scope.
+    pub fn analyse(routes: &IrrRoutes, vrps: &Vrps, scope: &ScopeLimits) -> Self {
+        let mut check = IrrCoverage::default();
+
+        for route in routes.in_scope(scope) {
+            check.routes += 1;
+
+            let covering = vrps.containing(route.as_ref());
+            if covering.is_empty() {
+                check.missing_roa.push(route.clone());
+            } else if covering.iter().any(|vrp| vrp.asn() == route.origin()) {
+                check.matching += 1;
+            } else {
+                let mut vrp_asns: Vec<Asn> = covering.iter().map(|vrp| vrp.asn()).collect();
+                vrp_asns.sort();
+                vrp_asns.dedup();
+                check.conflicts.push(IrrConflict {
+                    route: route.clone(),
+                    vrp_asns,
+                });
+            }
+        }
+
+        check
+    }
+
+    pub fn routes(&self) -> usize {
+        self.routes
+    }
+    pub fn matching(&self) -> usize {
+        self.matching
+    }
+    pub fn missing_roa(&self) -> &[IrrRoute] {
+        &self.missing_roa
+    }
+    pub fn conflicts(&self) -> &[IrrConflict] {
+        &self.conflicts
+    }
+}
+
+impl fmt::Display for IrrCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "IRR route coverage")?;
+        writeln!(f, "  Total:            {}", self.routes)?;
+        writeln!(f, "  Matching ROA:     {}", self.matching)?;
+        writeln!(f, "  No ROA:           {}", self.missing_roa.len())?;
+        for route in &self.missing_roa {
+            writeln!(f, "    {}", route)?;
+        }
+        writeln!(f, "  Origin conflict:  {}", self.conflicts.len())?;
+        for conflict in &self.conflicts {
+            let asns: Vec<String> = conflict.vrp_asns.iter().map(Asn::to_string).collect();
+            writeln!(f, "    {} (ROAs for: {})", conflict.route, asns.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+//------------ Error --------------------------------------------------------
+
+#[derive(Debug, Display)]
+pub enum Error {
+    #[display(fmt = "Cannot read IRR file: {}", _0)]
+    CannotRead(String),
+
+    #[display(fmt = "Error parsing IRR file: {}", _0)]
+    ParseError(String),
+
+    #[display(fmt = "IRR route object without origin: {}", _0)]
+    MissingOrigin(String),
+}
+
+impl From<IpPrefixError> for Error {
+    fn from(e: IpPrefixError) -> Self {
+        Error::ParseError(e.to_string())
+    }
+}
+
+impl From<AsnError> for Error {
+    fn from(e: AsnError) -> Self {
+        Error::ParseError(e.to_string())
+    }
+}
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn should_flag_missing_roas_and_conflicts() {
+        let routes = IrrRoutes::from_file(&PathBuf::from("test/irr/routes.txt")).unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/small/vrps.csv")).unwrap();
+        assert_eq!(4, routes.all().len());
+
+        let check = IrrCoverage::analyse(&routes, &vrps, &ScopeLimits::empty());
+        assert_eq!(4, check.routes());
+        assert_eq!(2, check.matching());
+
+        let missing: Vec<String> = check.missing_roa().iter().map(|r| r.to_string()).collect();
+        assert_eq!(vec!["172.16.0.0/24 AS65002"], missing);
+
+        assert_eq!(1, check.conflicts().len());
+        let conflict = &check.conflicts()[0];
+        assert_eq!("10.0.2.0/24 AS65001", conflict.route().to_string());
+        assert_eq!(&[Asn::from(65000)], conflict.vrp_asns());
+
+        let scope = ScopeLimits::from_str("172.16.0.0/12").unwrap();
+        let check = IrrCoverage::analyse(&routes, &vrps, &scope);
+        assert_eq!(1, check.routes());
+        assert_eq!(1, check.missing_roa().len());
+    }
+
+    #[test]
+    fn should_reject_route_without_origin() {
+        match IrrRoutes::from_str("route: 10.0.0.0/16\nsource: TEST\n") {
+            Err(Error::MissingOrigin(prefix)) => assert_eq!("10.0.0.0/16", prefix),
+            _ => panic!("Expected MissingOrigin error"),
+        }
+    }
+}
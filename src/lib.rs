@@ -1,18 +1,31 @@
 extern crate actix_web;
+extern crate chrono;
 extern crate core;
 extern crate clap;
+extern crate csv;
 #[macro_use] extern crate derive_more;
 extern crate futures;
 extern crate intervaltree;
+#[macro_use] extern crate log;
+extern crate rayon;
 #[macro_use] extern crate serde_derive;
 extern crate serde;
 extern crate serde_json;
+extern crate toml;
+#[cfg(unix)]
+extern crate tokio_uds;
 
 #[macro_use] pub mod statics;
 pub mod announcements;
+pub mod cli;
+pub mod config;
 pub mod delegations;
+pub mod input;
 pub mod ip;
+pub mod irr;
+pub mod logging;
 pub mod report;
 pub mod server;
+pub mod slurm;
 pub mod validation;
 pub mod vrps;
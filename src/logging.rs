@@ -0,0 +1,206 @@
+//! Logging for the daemon, as plain text or as JSON lines for centralized
+//! logging, e.g. in containerized deployments.
+//!
+//! This is a small backend for the 'log' crate, rather than a subscriber
+//! from 'tracing-subscriber'. The code here, and actix-web, log through the
+//! 'log' macros, so this needs no new dependencies or 'tracing' bridge. The
+//! JSON lines still carry the source location that such a formatter adds.
+use chrono::Utc;
+use log::Level;
+use log::Log;
+use log::Metadata;
+use log::Record;
+use std::str::FromStr;
+
+//------------ LogFormat -----------------------------------------------------
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+pub enum LogFormat {
+    /// One human readable line per message.
+    #[default]
+    #[serde(rename = "text")]
+    Text,
+
+    /// One JSON object per line, with the keys: level, ts, msg and fields.
+    /// The fields are the target, and the module path, file and line of the
+    /// log statement when known.
+    #[serde(rename = "json")]
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(Error::UnsupportedFormat(s.to_string())),
+        }
+    }
+}
+
+//------------ Logger --------------------------------------------------------
+
+/// Writes log messages to stderr, in the given format.
+#[derive(Clone, Debug)]
+pub struct Logger {
+    format: LogFormat,
+    level: Level,
+}
+
+impl Logger {
+    pub fn new(format: LogFormat, level: Level) -> Self {
+        Logger { format, level }
+    }
+
+    /// Installs this logger for the log macros. Can only be done once.
+    pub fn init(self) -> Result<(), Error> {
+        log::set_max_level(self.level.to_level_filter());
+        log::set_logger(Box::leak(Box::new(self))).map_err(|_| Error::AlreadyInitialised)
+    }
+
+    /// Formats the record as a single line, without a line ending.
+    pub fn format_record(&self, record: &Record) -> String {
+        let ts = Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ");
+
+        match self.format {
+            LogFormat::Text => format!(
+                "{} {} {}: {}",
+                ts,
+                record.level(),
+                record.target(),
+                record.args()
+            ),
+            LogFormat::Json => {
+                let line = JsonLine {
+                    level: record.level().as_str(),
+                    ts: ts.to_string(),
+                    msg: record.args().to_string(),
+                    fields: JsonFields {
+                        target: record.target(),
+                        module_path: record.module_path(),
+                        file: record.file(),
+                        line: record.line(),
+                    },
+                };
+                // Serializing strings to JSON cannot fail
+                serde_json::to_string(&line).unwrap()
+            }
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}", self.format_record(record));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+//------------ JsonLine ------------------------------------------------------
+
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    level: &'a str,
+    ts: String,
+    msg: String,
+    fields: JsonFields<'a>,
+}
+
+#[derive(Serialize)]
+struct JsonFields<'a> {
+    target: &'a str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module_path: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<u32>,
+}
+
+//------------ Error --------------------------------------------------------
+
+#[derive(Debug, Display)]
+pub enum Error {
+    #[display(fmt = "Unsupported log format: {}. Supported are: text|json", _0)]
+    UnsupportedFormat(String),
+
+    #[display(fmt = "Logging was already initialised")]
+    AlreadyInitialised,
+}
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn json_log_line_should_have_expected_keys() {
+        let logger = Logger::new(LogFormat::Json, Level::Info);
+        let line = logger.format_record(
+            &Record::builder()
+                .args(format_args!("Listening on {}", "127.0.0.1:8080"))
+                .level(Level::Info)
+                .target("secure_routing_stats::server")
+                .module_path(Some("secure_routing_stats::server"))
+                .file(Some("src/server.rs"))
+                .line(Some(42))
+                .build(),
+        );
+
+        let value: Value = serde_json::from_str(&line).unwrap();
+        let object = value.as_object().unwrap();
+
+        let mut keys: Vec<&str> = object.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(vec!["fields", "level", "msg", "ts"], keys);
+
+        assert_eq!("INFO", object["level"]);
+        assert_eq!("Listening on 127.0.0.1:8080", object["msg"]);
+        assert_eq!("secure_routing_stats::server", object["fields"]["target"]);
+        assert_eq!(
+            "secure_routing_stats::server",
+            object["fields"]["module_path"]
+        );
+        assert_eq!("src/server.rs", object["fields"]["file"]);
+        assert_eq!(42, object["fields"]["line"]);
+        assert!(object["ts"].as_str().unwrap().ends_with('Z'));
+        assert!(!line.contains('\n'));
+    }
+
+    #[test]
+    fn json_log_line_should_skip_unknown_location() {
+        let logger = Logger::new(LogFormat::Json, Level::Info);
+        let line = logger.format_record(
+            &Record::builder()
+                .args(format_args!("Reloaded"))
+                .level(Level::Info)
+                .target("secure_routing_stats::server")
+                .build(),
+        );
+
+        let value: Value = serde_json::from_str(&line).unwrap();
+        let fields = value["fields"].as_object().unwrap();
+        assert_eq!(vec!["target"], fields.keys().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn should_parse_log_format() {
+        assert_eq!(LogFormat::Json, LogFormat::from_str("json").unwrap());
+        assert_eq!(LogFormat::Text, LogFormat::from_str("text").unwrap());
+        assert!(LogFormat::from_str("xml").is_err());
+    }
+}
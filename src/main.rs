@@ -1,12 +1,20 @@
-extern crate clap;
 #[macro_use]
 extern crate derive_more;
+extern crate log;
 extern crate secure_routing_stats;
 
-use clap::App;
-use clap::Arg;
-use clap::SubCommand;
+use log::Level;
+use secure_routing_stats::cli;
+use secure_routing_stats::logging::LogFormat;
+use secure_routing_stats::logging::Logger;
+use secure_routing_stats::report::aggregate::{self, AggregateOpts, AggregateReporter};
+use secure_routing_stats::report::debug::{self, DebugOpts, DebugReporter};
+use secure_routing_stats::report::diff::{self, DiffOpts, VrpDiffReporter};
+use secure_routing_stats::report::irr_check::{self, IrrCheckOpts, IrrCheckReporter};
+use secure_routing_stats::report::lookup::{self, LookupOpts, LookupReporter};
 use secure_routing_stats::report::resources::{self, ResourceReportOpts, ResourceReporter};
+use secure_routing_stats::report::unreachables::{self, UnreachablesOpts, UnreachablesReporter};
+use secure_routing_stats::report::validate::{self, ValidateOpts, ValidateReporter};
 use secure_routing_stats::report::world::{self, WorldStatsOpts, WorldStatsReporter};
 use secure_routing_stats::server;
 use secure_routing_stats::server::ServerOpts;
@@ -27,6 +35,23 @@ fn main() {
                     ResourceReporter::execute(&opts).map_err(Error::ResourceReportError)
                 }
                 Options::Daemon(opts) => StatsApp::run(&opts).map_err(Error::DaemonError),
+                Options::Debug(opts) => {
+                    DebugReporter::execute(&opts).map_err(Error::DebugReportError)
+                }
+                Options::Aggregate(opts) => {
+                    AggregateReporter::execute(&opts).map_err(Error::AggregateError)
+                }
+                Options::Lookup(opts) => LookupReporter::execute(&opts).map_err(Error::LookupError),
+                Options::IrrCheck(opts) => {
+                    IrrCheckReporter::execute(&opts).map_err(Error::IrrCheckError)
+                }
+                Options::Validate(opts) => {
+                    ValidateReporter::execute(&opts).map_err(Error::ValidateError)
+                }
+                Options::Unreachables(opts) => {
+                    UnreachablesReporter::execute(&opts).map_err(Error::UnreachablesError)
+                }
+                Options::Diff(opts) => VrpDiffReporter::execute(&opts).map_err(Error::DiffError),
             };
             match res {
                 Ok(()) => {}
@@ -43,125 +68,30 @@ enum Options {
     WorldStats(WorldStatsOpts),
     ResourceStats(ResourceReportOpts),
     Daemon(ServerOpts),
+    Debug(DebugOpts),
+    Aggregate(AggregateOpts),
+    Lookup(LookupOpts),
+    IrrCheck(IrrCheckOpts),
+    Validate(ValidateOpts),
+    Unreachables(UnreachablesOpts),
+    Diff(DiffOpts),
 }
 
 impl Options {
     pub fn create() -> Result<Self, Error> {
-        let matches = App::new("NLnet Labs RRDP Server")
-            .version("0.1b")
-            .about("Analyse ROA quality vs BGP")
-            .subcommand(
-                SubCommand::with_name("world")
-                    .about("Report ROA quality on a per country basis")
-                    .arg(
-                        Arg::with_name("announcements")
-                            .short("a")
-                            .long("announcements")
-                            .value_name("FILE")
-                            .help("RIS dump file(s)")
-                            .required(true)
-                            .min_values(1),
-                    )
-                    .arg(
-                        Arg::with_name("vrps")
-                            .short("v")
-                            .long("vrps")
-                            .value_name("FILE")
-                            .help("Validated ROAs Payloads CSV file.")
-                            .required(true),
-                    )
-                    .arg(
-                        Arg::with_name("delegations")
-                            .short("d")
-                            .long("delegations")
-                            .value_name("FILE")
-                            .help("Delegation stats (NRO extended delegated stats format).")
-                            .required(true),
-                    )
-                    .arg(
-                        Arg::with_name("format")
-                            .short("f")
-                            .long("format")
-                            .value_name("json | text")
-                            .help("Specify output format, defaults to json")
-                            .required(false),
-                    ),
-            )
-            .subcommand(
-                SubCommand::with_name("resources")
-                    .about("Report ROA quality on a resource basis")
-                    .arg(
-                        Arg::with_name("announcements")
-                            .short("a")
-                            .long("announcements")
-                            .value_name("FILE")
-                            .help("RIS dump file(s)")
-                            .required(true)
-                            .min_values(1),
-                    )
-                    .arg(
-                        Arg::with_name("vrps")
-                            .short("v")
-                            .long("vrps")
-                            .value_name("FILE")
-                            .help("Validated ROAs Payloads CSV file.")
-                            .required(true),
-                    )
-                    .arg(
-                        Arg::with_name("ips")
-                            .short("i")
-                            .long("ips")
-                            .value_name("comma separated prefixes/ranges")
-                            .help("Optional scope for invalid report. Default: all")
-                            .required(false),
-                    )
-                    .arg(
-                        Arg::with_name("asns")
-                            .short("x")
-                            .long("asns")
-                            .value_name("comma separated ASNs / ASN ranges")
-                            .help("Optional scope for invalid report. Default: all")
-                            .required(false),
-                    )
-                    .arg(
-                        Arg::with_name("format")
-                            .short("f")
-                            .long("format")
-                            .value_name("json | text")
-                            .help("Specify output format, defaults to json")
-                            .required(false),
-                    ),
-            )
-            .subcommand(
-                SubCommand::with_name("daemon")
-                    .about("Run as an HTTP server")
-                    .arg(
-                        Arg::with_name("announcements")
-                            .short("a")
-                            .long("announcements")
-                            .value_name("FILE")
-                            .help("RIS dump file(s)")
-                            .required(true)
-                            .min_values(1),
-                    )
-                    .arg(
-                        Arg::with_name("vrps")
-                            .short("v")
-                            .long("vrps")
-                            .value_name("FILE")
-                            .help("Validated ROAs Payloads CSV file.")
-                            .required(true),
-                    )
-                    .arg(
-                        Arg::with_name("delegations")
-                            .short("d")
-                            .long("delegations")
-                            .value_name("FILE")
-                            .help("Delegation stats (NRO extended delegated stats format).")
-                            .required(true),
-                    ),
-            )
-            .get_matches();
+        let matches = cli::app().get_matches();
+
+        // The progress is logged. The daemon sets up its own logger, in the
+        // configured format, the other commands only log when asked to.
+        let progress = matches
+            .subcommand()
+            .1
+            .is_some_and(|sub| sub.is_present("progress"));
+        if progress && matches.subcommand_name() != Some("daemon") {
+            Logger::new(LogFormat::Text, Level::Info)
+                .init()
+                .map_err(|e| Error::WithMessage(e.to_string()))?;
+        }
 
         if let Some(matches) = matches.subcommand_matches("world") {
             Ok(Options::WorldStats(WorldStatsOpts::parse(matches)?))
@@ -169,6 +99,20 @@ impl Options {
             Ok(Options::ResourceStats(ResourceReportOpts::parse(matches)?))
         } else if let Some(matches) = matches.subcommand_matches("daemon") {
             Ok(Options::Daemon(ServerOpts::parse(matches)?))
+        } else if let Some(matches) = matches.subcommand_matches("debug") {
+            Ok(Options::Debug(DebugOpts::parse(matches)?))
+        } else if let Some(matches) = matches.subcommand_matches("aggregate") {
+            Ok(Options::Aggregate(AggregateOpts::parse(matches)?))
+        } else if let Some(matches) = matches.subcommand_matches("lookup") {
+            Ok(Options::Lookup(LookupOpts::parse(matches)?))
+        } else if let Some(matches) = matches.subcommand_matches("irr-check") {
+            Ok(Options::IrrCheck(IrrCheckOpts::parse(matches)?))
+        } else if let Some(matches) = matches.subcommand_matches("validate") {
+            Ok(Options::Validate(ValidateOpts::parse(matches)?))
+        } else if let Some(matches) = matches.subcommand_matches("unreachables") {
+            Ok(Options::Unreachables(UnreachablesOpts::parse(matches)?))
+        } else if let Some(matches) = matches.subcommand_matches("diff") {
+            Ok(Options::Diff(DiffOpts::parse(matches)?))
         } else {
             Err(Error::msg("No sub-command given. See --help for options."))
         }
@@ -190,6 +134,27 @@ pub enum Error {
 
     #[display(fmt = "{}", _0)]
     DaemonError(server::Error),
+
+    #[display(fmt = "{}", _0)]
+    DebugReportError(debug::Error),
+
+    #[display(fmt = "{}", _0)]
+    AggregateError(aggregate::Error),
+
+    #[display(fmt = "{}", _0)]
+    LookupError(lookup::Error),
+
+    #[display(fmt = "{}", _0)]
+    IrrCheckError(irr_check::Error),
+
+    #[display(fmt = "{}", _0)]
+    ValidateError(validate::Error),
+
+    #[display(fmt = "{}", _0)]
+    UnreachablesError(unreachables::Error),
+
+    #[display(fmt = "{}", _0)]
+    DiffError(diff::Error),
 }
 
 impl Error {
@@ -215,3 +180,45 @@ impl From<server::Error> for Error {
         Error::DaemonError(e)
     }
 }
+
+impl From<debug::Error> for Error {
+    fn from(e: debug::Error) -> Self {
+        Error::DebugReportError(e)
+    }
+}
+
+impl From<aggregate::Error> for Error {
+    fn from(e: aggregate::Error) -> Self {
+        Error::AggregateError(e)
+    }
+}
+
+impl From<lookup::Error> for Error {
+    fn from(e: lookup::Error) -> Self {
+        Error::LookupError(e)
+    }
+}
+
+impl From<irr_check::Error> for Error {
+    fn from(e: irr_check::Error) -> Self {
+        Error::IrrCheckError(e)
+    }
+}
+
+impl From<validate::Error> for Error {
+    fn from(e: validate::Error) -> Self {
+        Error::ValidateError(e)
+    }
+}
+
+impl From<unreachables::Error> for Error {
+    fn from(e: unreachables::Error) -> Self {
+        Error::UnreachablesError(e)
+    }
+}
+
+impl From<diff::Error> for Error {
+    fn from(e: diff::Error) -> Self {
+        Error::DiffError(e)
+    }
+}
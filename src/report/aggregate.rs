@@ -0,0 +1,104 @@
+//! Merge a list of prefixes and ranges into the minimal list of prefixes
+//! covering the same address space.
+use crate::ip::IpPrefix;
+use crate::ip::IpResourceSet;
+use crate::ip::IpRespourceSetError;
+use clap::ArgMatches;
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+//------------ AggregateOpts -------------------------------------------------
+
+pub struct AggregateOpts {
+    prefixes: PathBuf,
+}
+
+impl AggregateOpts {
+    pub fn parse(matches: &ArgMatches) -> Result<Self, Error> {
+        let prefixes = PathBuf::from(matches.value_of("prefixes").unwrap());
+        Ok(AggregateOpts { prefixes })
+    }
+}
+
+//------------ AggregateReporter ---------------------------------------------
+
+pub struct AggregateReporter;
+
+impl AggregateReporter {
+    /// Reads a file with one prefix or range per line. Empty lines and lines
+    /// starting with '#' are ignored.
+    pub fn read(path: &Path) -> Result<IpResourceSet, Error> {
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+
+        let mut set = IpResourceSet::empty();
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parsed = IpResourceSet::from_str(line)
+                .map_err(|e| Error::InvalidLine(line.to_string(), e))?;
+            for range in parsed.ranges() {
+                set.add_ip_range(*range);
+            }
+        }
+        Ok(set)
+    }
+
+    pub fn aggregate(path: &Path) -> Result<Vec<IpPrefix>, Error> {
+        Ok(Self::read(path)?.to_prefixes())
+    }
+
+    pub fn execute(options: &AggregateOpts) -> Result<(), Error> {
+        for prefix in Self::aggregate(&options.prefixes)? {
+            println!("{}", prefix);
+        }
+        Ok(())
+    }
+}
+
+//------------ Error --------------------------------------------------------
+
+#[derive(Debug, Display)]
+pub enum Error {
+    #[display(fmt = "Cannot read prefixes: {}", _0)]
+    IoError(io::Error),
+
+    #[display(fmt = "Invalid prefix or range '{}': {}", _0, _1)]
+    InvalidLine(String, IpRespourceSetError),
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::IoError(e)
+    }
+}
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_aggregate_prefixes_from_file() {
+        let prefixes: Vec<String> =
+            AggregateReporter::aggregate(&PathBuf::from("test/aggregate/prefixes.txt"))
+                .unwrap()
+                .iter()
+                .map(|p| p.to_string())
+                .collect();
+
+        assert_eq!(
+            vec!["10.0.0.0/22", "192.168.0.0/24", "2001:db8::/32"],
+            prefixes
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! Summary of the validity of the announcements of a single origin ASN.
+use crate::announcements::Announcements;
+use crate::ip::Asn;
+use crate::ip::AsnSet;
+use crate::ip::IpPrefix;
+use crate::ip::IpResourceSet;
+use crate::report::ScopeLimits;
+use crate::validation::ValidatedAnnouncement;
+use crate::validation::ValidationState;
+use crate::vrps::Vrps;
+
+//------------ AsnSummary ----------------------------------------------------
+
+/// Counts of announcements by validation state for an origin ASN, with the
+/// invalid announcements and the not found prefixes, sorted by prefix.
+#[derive(Clone, Debug, Serialize)]
+pub struct AsnSummary {
+    asn: Asn,
+    valid: usize,
+    invalid_asn: usize,
+    invalid_length: usize,
+    not_found: usize,
+    invalids: Vec<ValidatedAnnouncement>,
+    not_found_prefixes: Vec<IpPrefix>,
+}
+
+impl AsnSummary {
+    fn new(asn: Asn) -> Self {
+        AsnSummary {
+            asn,
+            valid: 0,
+            invalid_asn: 0,
+            invalid_length: 0,
+            not_found: 0,
+            invalids: vec![],
+            not_found_prefixes: vec![],
+        }
+    }
+
+    fn add(&mut self, ann: ValidatedAnnouncement) {
+        match ann.state() {
+            ValidationState::Valid => self.valid += 1,
+            ValidationState::InvalidAsn => {
+                self.invalid_asn += 1;
+                self.invalids.push(ann);
+            }
+            ValidationState::InvalidLength => {
+                self.invalid_length += 1;
+                self.invalids.push(ann);
+            }
+            ValidationState::NotFound => {
+                self.not_found += 1;
+                self.not_found_prefixes
+                    .push(ann.announcement().prefix().clone());
+            }
+        }
+    }
+
+    fn sort(&mut self) {
+        self.invalids
+            .sort_by(|a, b| a.announcement().prefix().cmp(b.announcement().prefix()));
+        self.not_found_prefixes.sort();
+    }
+}
+
+//------------ AsnReporter ---------------------------------------------------
+
+pub struct AsnReporter<'a> {
+    announcements: &'a Announcements,
+    vrps: &'a Vrps,
+}
+
+impl<'a> AsnReporter<'a> {
+    pub fn new(announcements: &'a Announcements, vrps: &'a Vrps) -> Self {
+        AsnReporter {
+            announcements,
+            vrps,
+        }
+    }
+
+    /// Validates all announcements with the given origin ASN.
+    pub fn summary(&self, asn: Asn) -> AsnSummary {
+        let mut asns = AsnSet::empty();
+        asns.add_asn(asn);
+        let scope = ScopeLimits::new(IpResourceSet::empty(), asns);
+
+        let mut summary = AsnSummary::new(asn);
+        for ann in self.announcements.in_scope(&scope) {
+            let matching_roas = self.vrps.containing(ann.as_ref());
+            summary.add(ValidatedAnnouncement::create(ann, &matching_roas));
+        }
+
+        summary.sort();
+        summary
+    }
+}
@@ -0,0 +1,169 @@
+//! The share of routed address space that is covered by VRPs. Unlike the
+//! adoption based on route counts, this weighs announcements by their size.
+use crate::announcements::Announcements;
+use crate::ip::IpAddressFamily;
+use crate::ip::IpResourceSet;
+use crate::report::ScopeLimits;
+use crate::vrps::Vrps;
+use std::fmt;
+use std::fmt::Write;
+
+//------------ FamilyCoverage ------------------------------------------------
+
+/// The number of routed addresses, and how many of those are covered by at
+/// least one VRP, for an address family.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct FamilyCoverage {
+    routed: u128,
+    covered: u128,
+}
+
+impl FamilyCoverage {
+    pub fn routed(&self) -> u128 {
+        self.routed
+    }
+
+    pub fn covered(&self) -> u128 {
+        self.covered
+    }
+
+    /// Returns covered / routed, or None if nothing is routed.
+    pub fn ratio(&self) -> Option<f64> {
+        if self.routed == 0 {
+            None
+        } else {
+            Some(self.covered as f64 / self.routed as f64)
+        }
+    }
+}
+
+//------------ SpaceCoverage -------------------------------------------------
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct SpaceCoverage {
+    v4: FamilyCoverage,
+    v6: FamilyCoverage,
+}
+
+impl SpaceCoverage {
+    pub fn v4(&self) -> &FamilyCoverage {
+        &self.v4
+    }
+
+    pub fn v6(&self) -> &FamilyCoverage {
+        &self.v6
+    }
+
+    /// Returns the ratios as Prometheus gauges. Families without routed
+    /// space are left out.
+    pub fn to_prometheus(&self) -> String {
+        let mut s = String::new();
+        writeln!(s, "# TYPE rpki_stats_coverage_ratio gauge").unwrap();
+        for (family, coverage) in &[("ipv4", self.v4), ("ipv6", self.v6)] {
+            if let Some(ratio) = coverage.ratio() {
+                writeln!(
+                    s,
+                    "rpki_stats_coverage_ratio{{family=\"{}\"}} {}",
+                    family, ratio
+                )
+                .unwrap();
+            }
+        }
+        s
+    }
+}
+
+impl fmt::Display for SpaceCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Routed space covered by VRPs:")?;
+        for (family, coverage) in &[("IPv4", self.v4), ("IPv6", self.v6)] {
+            match coverage.ratio() {
+                Some(ratio) => writeln!(f, "  {}:           {:.2}%", family, ratio * 100.0)?,
+                None => writeln!(f, "  {}:           n/a", family)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+//------------ CoverageReporter ----------------------------------------------
+
+pub struct CoverageReporter<'a> {
+    announcements: &'a Announcements,
+    vrps: &'a Vrps,
+}
+
+impl<'a> CoverageReporter<'a> {
+    pub fn new(announcements: &'a Announcements, vrps: &'a Vrps) -> Self {
+        CoverageReporter {
+            announcements,
+            vrps,
+        }
+    }
+
+    /// Determines the routed space for the announcements in scope, and the
+    /// part of it that is covered by any VRP. VRPs outside of the scope are
+    /// included, because less specific VRPs can cover the routed space.
+    pub fn analyse(&self, scope: &ScopeLimits) -> SpaceCoverage {
+        let routed = IpResourceSet::from_ranges(
+            self.announcements
+                .in_scope(scope)
+                .iter()
+                .map(|ann| *ann.as_ref())
+                .collect(),
+        );
+        let vrp_space =
+            IpResourceSet::from_ranges(self.vrps.iter().map(|vrp| *vrp.as_ref()).collect());
+        let covered = routed.intersection(&vrp_space);
+
+        let family = |family| FamilyCoverage {
+            routed: routed.address_count(family),
+            covered: covered.address_count(family),
+        };
+
+        SpaceCoverage {
+            v4: family(IpAddressFamily::Ipv4),
+            v6: family(IpAddressFamily::Ipv6),
+        }
+    }
+}
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    fn reporter_for_fixture() -> (Announcements, Vrps) {
+        let announcements = Announcements::from_ris(&[
+            PathBuf::from("test/coverage/riswhoisdump.IPv4"),
+            PathBuf::from("test/coverage/riswhoisdump.IPv6"),
+        ])
+        .unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/coverage/vrps.csv")).unwrap();
+        (announcements, vrps)
+    }
+
+    #[test]
+    fn should_compute_coverage_ratio() {
+        let (announcements, vrps) = reporter_for_fixture();
+        let reporter = CoverageReporter::new(&announcements, &vrps);
+
+        let coverage = reporter.analyse(&ScopeLimits::empty());
+        assert_eq!(65_536, coverage.v4().routed());
+        assert_eq!(32_768, coverage.v4().covered());
+        assert_eq!(Some(0.5), coverage.v4().ratio());
+        assert_eq!(Some(0.25), coverage.v6().ratio());
+
+        let metrics = coverage.to_prometheus();
+        assert!(metrics.contains("rpki_stats_coverage_ratio{family=\"ipv4\"} 0.5\n"));
+        assert!(metrics.contains("rpki_stats_coverage_ratio{family=\"ipv6\"} 0.25\n"));
+
+        let scope = ScopeLimits::from_str("10.0.0.0/8").unwrap();
+        let coverage = reporter.analyse(&scope);
+        assert_eq!(Some(0.5), coverage.v4().ratio());
+        assert_eq!(None, coverage.v6().ratio());
+    }
+}
@@ -0,0 +1,305 @@
+//! Dump the loaded data for a scope, to help find out whether input files
+//! were parsed as expected.
+use crate::announcements;
+use crate::announcements::Announcement;
+use crate::announcements::Announcements;
+use crate::config;
+use crate::config::Config;
+use crate::delegations;
+use crate::delegations::IpDelegation;
+use crate::delegations::IpDelegations;
+use crate::input::Progress;
+use crate::ip::IpResourceSet;
+use crate::ip::IpRespourceSetError;
+use crate::vrps;
+use crate::vrps::ValidatedRoaPayload;
+use crate::vrps::Vrps;
+use clap::ArgMatches;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+//------------ DebugOpts -----------------------------------------------------
+
+pub struct DebugOpts {
+    announcements: Vec<PathBuf>,
+    vrps: Option<PathBuf>,
+    slurm: Option<PathBuf>,
+    dels: Option<PathBuf>,
+    ips: IpResourceSet,
+    progress: Progress,
+}
+
+impl DebugOpts {
+    pub fn parse(matches: &ArgMatches) -> Result<Self, Error> {
+        let config = Config::from_matches(matches)?;
+        let progress = Progress::from_matches(matches);
+        let announcements = config.optional_announcements();
+        let vrps = config.optional_vrps();
+        let slurm = config.optional_slurm();
+        let dels = config.optional_delegations();
+
+        let ips = IpResourceSet::from_str(matches.value_of("ips").unwrap())?;
+
+        Ok(DebugOpts {
+            announcements,
+            vrps,
+            slurm,
+            dels,
+            ips,
+            progress,
+        })
+    }
+
+    /// Loads the VRPs, if given, with the local exceptions from the SLURM
+    /// file applied, so that the dump shows the VRPs used for validation.
+    fn load_vrps(&self) -> Result<Option<Vrps>, Error> {
+        match &self.vrps {
+            Some(path) => Ok(Some(Vrps::load(
+                path,
+                self.slurm.as_deref(),
+                self.progress,
+            )?)),
+            None => Ok(None),
+        }
+    }
+}
+
+//------------ DebugReporter -------------------------------------------------
+
+/// Finds all loaded announcements, VRPs and delegations that overlap the
+/// given IP resources. Any of the sources may be left out.
+pub struct DebugReporter<'a> {
+    announcements: Option<&'a Announcements>,
+    vrps: Option<&'a Vrps>,
+    delegations: Option<&'a IpDelegations>,
+}
+
+impl<'a> DebugReporter<'a> {
+    pub fn new(
+        announcements: Option<&'a Announcements>,
+        vrps: Option<&'a Vrps>,
+        delegations: Option<&'a IpDelegations>,
+    ) -> Self {
+        DebugReporter {
+            announcements,
+            vrps,
+            delegations,
+        }
+    }
+
+    pub fn dump(&self, ips: &IpResourceSet) -> TreeDump {
+        let mut dump = TreeDump::default();
+
+        for range in ips.ranges() {
+            if let Some(announcements) = self.announcements {
+                for ann in announcements.intersecting(range) {
+                    dump.announcements.push(ann.clone());
+                }
+            }
+            if let Some(vrps) = self.vrps {
+                for vrp in vrps.intersecting(range) {
+                    dump.vrps.push(vrp.clone());
+                }
+            }
+            if let Some(delegations) = self.delegations {
+                for del in delegations.intersecting(range) {
+                    dump.delegations.push(del.clone());
+                }
+            }
+        }
+
+        dump.sort();
+        dump
+    }
+
+    pub fn execute(options: &DebugOpts) -> Result<(), Error> {
+        let announcements = if options.announcements.is_empty() {
+            None
+        } else {
+            Some(Announcements::from_ris_with_progress(
+                &options.announcements,
+                announcements::DEFAULT_MIN_PEERS,
+                options.progress,
+            )?)
+        };
+
+        let vrps = options.load_vrps()?;
+
+        let delegations = match &options.dels {
+            Some(path) => Some(IpDelegations::from_file_with_progress(
+                path,
+                options.progress,
+            )?),
+            None => None,
+        };
+
+        let reporter =
+            DebugReporter::new(announcements.as_ref(), vrps.as_ref(), delegations.as_ref());
+
+        print!("{}", reporter.dump(&options.ips));
+
+        Ok(())
+    }
+}
+
+//------------ TreeDump ------------------------------------------------------
+
+/// The entries found for a scope, sorted by range.
+#[derive(Clone, Debug, Default)]
+pub struct TreeDump {
+    announcements: Vec<Announcement>,
+    vrps: Vec<ValidatedRoaPayload>,
+    delegations: Vec<IpDelegation>,
+}
+
+impl TreeDump {
+    fn sort(&mut self) {
+        self.announcements
+            .sort_by_key(|ann| (*ann.as_ref(), ann.to_string()));
+        self.announcements
+            .dedup_by(|a, b| a.to_string() == b.to_string());
+
+        self.vrps
+            .sort_by_key(|vrp| (*vrp.as_ref(), vrp.to_string()));
+        self.vrps.dedup_by(|a, b| a.to_string() == b.to_string());
+
+        self.delegations
+            .sort_by_key(|del| (*del.range(), del.to_string()));
+        self.delegations
+            .dedup_by(|a, b| a.to_string() == b.to_string());
+    }
+}
+
+impl fmt::Display for TreeDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Announcements: {}", self.announcements.len())?;
+        for ann in &self.announcements {
+            writeln!(f, "  {}", ann)?;
+        }
+        writeln!(f)?;
+
+        writeln!(f, "Validated ROA Payloads: {}", self.vrps.len())?;
+        for vrp in &self.vrps {
+            writeln!(f, "  {}", vrp)?;
+        }
+        writeln!(f)?;
+
+        writeln!(f, "Delegations: {}", self.delegations.len())?;
+        for del in &self.delegations {
+            writeln!(f, "  {}", del)?;
+        }
+
+        Ok(())
+    }
+}
+
+//------------ Error --------------------------------------------------------
+
+#[derive(Debug, Display)]
+pub enum Error {
+    #[display(fmt = "{}", _0)]
+    IpResourceSet(IpRespourceSetError),
+
+    #[display(fmt = "{}", _0)]
+    AnnouncementsError(announcements::Error),
+
+    #[display(fmt = "{}", _0)]
+    VrpsError(vrps::Error),
+
+    #[display(fmt = "{}", _0)]
+    DelegationsError(delegations::Error),
+
+    #[display(fmt = "{}", _0)]
+    ConfigError(config::Error),
+}
+
+impl From<IpRespourceSetError> for Error {
+    fn from(e: IpRespourceSetError) -> Self {
+        Error::IpResourceSet(e)
+    }
+}
+
+impl From<announcements::Error> for Error {
+    fn from(e: announcements::Error) -> Self {
+        Error::AnnouncementsError(e)
+    }
+}
+
+impl From<vrps::Error> for Error {
+    fn from(e: vrps::Error) -> Self {
+        Error::VrpsError(e)
+    }
+}
+
+impl From<delegations::Error> for Error {
+    fn from(e: delegations::Error) -> Self {
+        Error::DelegationsError(e)
+    }
+}
+
+impl From<config::Error> for Error {
+    fn from(e: config::Error) -> Self {
+        Error::ConfigError(e)
+    }
+}
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_dump_entries_in_scope() {
+        let announcements = Announcements::from_ris(&[
+            PathBuf::from("test/small/riswhoisdump.IPv4"),
+            PathBuf::from("test/small/riswhoisdump.IPv6"),
+        ])
+        .unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/small/vrps.csv")).unwrap();
+        let delegations =
+            IpDelegations::from_file(&PathBuf::from("test/small/delegated-extended.txt")).unwrap();
+
+        let reporter = DebugReporter::new(Some(&announcements), Some(&vrps), Some(&delegations));
+
+        let ips = IpResourceSet::from_str("10.0.0.0/16").unwrap();
+        let dump = reporter.dump(&ips).to_string();
+
+        let expected = "\
+Announcements: 3
+  AS65000 10.0.0.0/16
+  AS65000 10.0.1.0/24
+  AS65001 10.0.2.0/24
+
+Validated ROA Payloads: 1
+  AS: AS65000, Prefix: 10.0.0.0/16, Max Length: 16
+
+Delegations: 1
+  10.0.0.0-10.0.255.255 ripencc NL assigned
+";
+        assert_eq!(expected, dump);
+    }
+
+    #[test]
+    fn should_apply_slurm_to_dumped_vrps() {
+        let options = DebugOpts {
+            announcements: vec![],
+            vrps: Some(PathBuf::from("test/small/vrps.csv")),
+            slurm: Some(PathBuf::from("test/small/slurm.json")),
+            dels: None,
+            ips: IpResourceSet::from_str("10.0.0.0/16, 172.16.0.0/16").unwrap(),
+            progress: Progress::Silent,
+        };
+        let vrps = options.load_vrps().unwrap();
+
+        let reporter = DebugReporter::new(None, vrps.as_ref(), None);
+        let dump = reporter.dump(&options.ips);
+
+        let vrps: Vec<String> = dump.vrps.iter().map(|vrp| vrp.to_string()).collect();
+        assert_eq!(
+            vec!["AS: AS65002, Prefix: 172.16.0.0/24, Max Length: 24"],
+            vrps
+        );
+    }
+}
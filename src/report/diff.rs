@@ -0,0 +1,481 @@
+//! Compare two VRP snapshots: which VRPs were added and removed, and, if
+//! announcements are given, which announcements change validation state.
+use crate::announcements;
+use crate::announcements::Announcement;
+use crate::announcements::Announcements;
+use crate::config;
+use crate::config::Config;
+use crate::input::Progress;
+use crate::ip::Asn;
+use crate::ip::IpPrefix;
+use crate::validation::ValidationPolicy;
+use crate::validation::ValidationState;
+use crate::vrps;
+use crate::vrps::ValidatedRoaPayload;
+use crate::vrps::Vrps;
+use clap::ArgMatches;
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+//------------ DiffOpts ------------------------------------------------------
+
+pub struct DiffOpts {
+    vrps_old: PathBuf,
+    vrps_new: PathBuf,
+    announcements: Vec<PathBuf>,
+    min_peers: u32,
+    policy: ValidationPolicy,
+    format: DiffFormat,
+    progress: Progress,
+}
+
+impl DiffOpts {
+    pub fn parse(matches: &ArgMatches) -> Result<Self, Error> {
+        let config = Config::from_matches(matches)?;
+        let progress = Progress::from_matches(matches);
+        let announcements = config.optional_announcements();
+        let min_peers = config.min_peers();
+
+        let vrps_old = matches
+            .value_of("vrps_old")
+            .map(PathBuf::from)
+            .ok_or_else(|| Error::WithMessage("Missing --vrps-old".to_string()))?;
+        let vrps_new = matches
+            .value_of("vrps_new")
+            .map(PathBuf::from)
+            .ok_or_else(|| Error::WithMessage("Missing --vrps-new".to_string()))?;
+
+        let policy = if matches.is_present("ignore_max_length") {
+            ValidationPolicy::IgnoreMaxLength
+        } else {
+            ValidationPolicy::Strict
+        };
+
+        let format = match matches.value_of("format") {
+            None | Some("json") => DiffFormat::Json,
+            Some("text") => DiffFormat::Text,
+            Some(f) => {
+                return Err(Error::WithMessage(format!(
+                    "Unsupported format: {}. Supported are: json|text",
+                    f
+                )))
+            }
+        };
+
+        Ok(DiffOpts {
+            vrps_old,
+            vrps_new,
+            announcements,
+            min_peers,
+            policy,
+            format,
+            progress,
+        })
+    }
+}
+
+pub enum DiffFormat {
+    Json,
+    Text,
+}
+
+//------------ StateChange ---------------------------------------------------
+
+/// An announcement that has a different validation state under the new
+/// VRPs than under the old VRPs.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct StateChange {
+    announcement: Announcement,
+    old: ValidationState,
+    new: ValidationState,
+}
+
+impl StateChange {
+    pub fn announcement(&self) -> &Announcement {
+        &self.announcement
+    }
+    pub fn old_state(&self) -> &ValidationState {
+        &self.old
+    }
+    pub fn new_state(&self) -> &ValidationState {
+        &self.new
+    }
+}
+
+impl fmt::Display for StateChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.announcement, self.old, self.new)
+    }
+}
+
+//------------ VrpDiff -------------------------------------------------------
+
+/// The VRPs only found in the new and only found in the old snapshot, and
+/// the announcements that changed state, if any were validated.
+#[derive(Clone, Debug, Serialize)]
+pub struct VrpDiff {
+    added: usize,
+    removed: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    changed: Option<usize>,
+    added_vrps: Vec<ValidatedRoaPayload>,
+    removed_vrps: Vec<ValidatedRoaPayload>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    changed_announcements: Option<Vec<StateChange>>,
+}
+
+impl VrpDiff {
+    pub fn added(&self) -> &[ValidatedRoaPayload] {
+        &self.added_vrps
+    }
+    pub fn removed(&self) -> &[ValidatedRoaPayload] {
+        &self.removed_vrps
+    }
+    /// The announcements that changed state, or None if no announcements
+    /// were validated.
+    pub fn changed(&self) -> Option<&[StateChange]> {
+        self.changed_announcements.as_deref()
+    }
+}
+
+impl fmt::Display for VrpDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "VRP changes")?;
+        writeln!(f, "  Added:    {}", self.added)?;
+        writeln!(f, "  Removed:  {}", self.removed)?;
+        if let Some(changed) = self.changed {
+            writeln!(f, "  Changed:  {}", changed)?;
+        }
+        if !self.added_vrps.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Added VRPs:")?;
+            for vrp in &self.added_vrps {
+                writeln!(f, "  {}", vrp)?;
+            }
+        }
+        if !self.removed_vrps.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Removed VRPs:")?;
+            for vrp in &self.removed_vrps {
+                writeln!(f, "  {}", vrp)?;
+            }
+        }
+        if let Some(changes) = &self.changed_announcements {
+            if !changes.is_empty() {
+                writeln!(f)?;
+                writeln!(f, "Changed announcements:")?;
+                for change in changes {
+                    writeln!(f, "  {}", change)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+//------------ VrpDiffReporter -----------------------------------------------
+
+/// Identifies a VRP for the diff, and sorts by prefix first. The trust
+/// anchor and validity are left out, because they do not affect route
+/// origin validation.
+type VrpKey = (IpPrefix, Asn, u8);
+
+fn vrp_key(vrp: &ValidatedRoaPayload) -> VrpKey {
+    (vrp.prefix().clone(), vrp.asn(), vrp.max_length())
+}
+
+pub struct VrpDiffReporter<'a> {
+    old: &'a Vrps,
+    new: &'a Vrps,
+    policy: ValidationPolicy,
+}
+
+impl<'a> VrpDiffReporter<'a> {
+    pub fn new(old: &'a Vrps, new: &'a Vrps) -> Self {
+        VrpDiffReporter {
+            old,
+            new,
+            policy: ValidationPolicy::default(),
+        }
+    }
+
+    /// Uses the given validation policy, instead of strict validation.
+    pub fn with_policy(mut self, policy: ValidationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Returns the VRPs in 'from' that are not in 'other', sorted by
+    /// prefix, ASN and max length.
+    fn difference(from: &'a Vrps, other: &Vrps) -> Vec<&'a ValidatedRoaPayload> {
+        let other: HashSet<VrpKey> = other.iter().map(vrp_key).collect();
+        let mut res: BTreeMap<VrpKey, &ValidatedRoaPayload> = BTreeMap::new();
+        for vrp in from.iter() {
+            let key = vrp_key(vrp);
+            if !other.contains(&key) {
+                res.entry(key).or_insert(vrp);
+            }
+        }
+        res.into_values().collect()
+    }
+
+    fn state(&self, vrps: &Vrps, ann: &Announcement) -> ValidationState {
+        vrps.validate_with_policy(ann, self.policy).state().clone()
+    }
+
+    /// Finds the added and removed VRPs, and if announcements are given,
+    /// the announcements that change state. Only announcements covered by
+    /// an added or removed VRP can change state, so only those are
+    /// validated under both snapshots.
+    pub fn analyse(&self, announcements: Option<&Announcements>) -> VrpDiff {
+        let added = Self::difference(self.new, self.old);
+        let removed = Self::difference(self.old, self.new);
+
+        let changed = announcements.map(|announcements| {
+            let mut candidates: HashSet<&Announcement> = HashSet::new();
+            for vrp in added.iter().chain(removed.iter()) {
+                candidates.extend(announcements.contained_by(vrp.as_ref()));
+            }
+
+            let mut changes: Vec<StateChange> = candidates
+                .into_iter()
+                .filter_map(|ann| {
+                    let old = self.state(self.old, ann);
+                    let new = self.state(self.new, ann);
+                    if old != new {
+                        Some(StateChange {
+                            announcement: ann.clone(),
+                            old,
+                            new,
+                        })
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            changes.sort_by(|a, b| {
+                (a.announcement.prefix(), a.announcement.asn())
+                    .cmp(&(b.announcement.prefix(), b.announcement.asn()))
+            });
+            changes
+        });
+
+        VrpDiff {
+            added: added.len(),
+            removed: removed.len(),
+            changed: changed.as_ref().map(Vec::len),
+            added_vrps: added.into_iter().cloned().collect(),
+            removed_vrps: removed.into_iter().cloned().collect(),
+            changed_announcements: changed,
+        }
+    }
+
+    pub fn execute(options: &DiffOpts) -> Result<(), Error> {
+        Self::execute_with_output(options, &mut io::stdout())
+    }
+
+    /// Like 'execute', but writes the report to the given output.
+    pub fn execute_with_output<W: io::Write>(
+        options: &DiffOpts,
+        output: &mut W,
+    ) -> Result<(), Error> {
+        let old = Vrps::from_file_with_progress(&options.vrps_old, options.progress)?;
+        let new = Vrps::from_file_with_progress(&options.vrps_new, options.progress)?;
+
+        let announcements = if options.announcements.is_empty() {
+            None
+        } else {
+            Some(Announcements::from_ris_with_progress(
+                &options.announcements,
+                options.min_peers,
+                options.progress,
+            )?)
+        };
+
+        let res = VrpDiffReporter::new(&old, &new)
+            .with_policy(options.policy)
+            .analyse(announcements.as_ref());
+
+        match options.format {
+            DiffFormat::Json => writeln!(output, "{}", serde_json::to_string(&res)?)?,
+            DiffFormat::Text => write!(output, "{}", res)?,
+        }
+        Ok(())
+    }
+}
+
+//------------ Error --------------------------------------------------------
+
+#[derive(Debug, Display)]
+pub enum Error {
+    #[display(fmt = "{}", _0)]
+    WithMessage(String),
+
+    #[display(fmt = "{}", _0)]
+    ConfigError(config::Error),
+
+    #[display(fmt = "{}", _0)]
+    AnnouncementsError(announcements::Error),
+
+    #[display(fmt = "{}", _0)]
+    VrpsError(vrps::Error),
+
+    #[display(fmt = "{}", _0)]
+    JsonError(serde_json::Error),
+
+    #[display(fmt = "{}", _0)]
+    IoError(io::Error),
+}
+
+impl From<config::Error> for Error {
+    fn from(e: config::Error) -> Self {
+        Error::ConfigError(e)
+    }
+}
+
+impl From<announcements::Error> for Error {
+    fn from(e: announcements::Error) -> Self {
+        Error::AnnouncementsError(e)
+    }
+}
+
+impl From<vrps::Error> for Error {
+    fn from(e: vrps::Error) -> Self {
+        Error::VrpsError(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::JsonError(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::IoError(e)
+    }
+}
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+
+    fn report(args: &[&str]) -> String {
+        let matches = cli::subcommand_matches(args);
+        let opts = DiffOpts::parse(&matches).unwrap();
+
+        let mut output = vec![];
+        VrpDiffReporter::execute_with_output(&opts, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn should_find_added_removed_and_changed() {
+        let old = Vrps::from_file(&PathBuf::from("test/small/vrps.csv")).unwrap();
+        let new = Vrps::from_file(&PathBuf::from("test/diff/vrps.csv")).unwrap();
+        let announcements = Announcements::from_ris(&[
+            PathBuf::from("test/small/riswhoisdump.IPv4"),
+            PathBuf::from("test/small/riswhoisdump.IPv6"),
+        ])
+        .unwrap();
+        let reporter = VrpDiffReporter::new(&old, &new);
+
+        let res = reporter.analyse(None);
+        let added: Vec<String> = res.added().iter().map(ToString::to_string).collect();
+        let removed: Vec<String> = res.removed().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            vec![
+                "AS: AS65000, Prefix: 10.0.0.0/16, Max Length: 24",
+                "AS: AS65001, Prefix: 2001:db8:1::/48, Max Length: 48",
+            ],
+            added
+        );
+        assert_eq!(
+            vec![
+                "AS: AS65000, Prefix: 10.0.0.0/16, Max Length: 16",
+                "AS: AS65003, Prefix: 192.168.1.0/24, Max Length: 24",
+            ],
+            removed
+        );
+        assert!(res.changed().is_none());
+
+        let res = reporter.analyse(Some(&announcements));
+        let changed: Vec<String> = res
+            .changed()
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            vec![
+                "AS65000 10.0.1.0/24: invalid length -> valid",
+                "AS65001 2001:db8:1::/48: invalid asn -> valid",
+            ],
+            changed
+        );
+
+        let same = reporter.analyse(Some(&announcements));
+        assert_eq!(res.changed(), same.changed());
+        let none = VrpDiffReporter::new(&old, &old).analyse(Some(&announcements));
+        assert!(none.added().is_empty());
+        assert!(none.removed().is_empty());
+        assert_eq!(Some(&[][..]), none.changed());
+    }
+
+    #[test]
+    fn should_print_json_and_text() {
+        let args = [
+            "diff",
+            "--vrps-old",
+            "test/small/vrps.csv",
+            "--vrps-new",
+            "test/diff/vrps.csv",
+        ];
+
+        let json: serde_json::Value = serde_json::from_str(&report(&args)).unwrap();
+        assert_eq!(2, json["added"]);
+        assert_eq!(2, json["removed"]);
+        assert!(json.get("changed").is_none());
+        assert_eq!(2, json["added_vrps"].as_array().unwrap().len());
+        assert_eq!(2, json["removed_vrps"].as_array().unwrap().len());
+        assert!(json.get("changed_announcements").is_none());
+
+        let mut with_announcements = args.to_vec();
+        with_announcements.extend(&[
+            "--announcements",
+            "test/small/riswhoisdump.IPv4",
+            "test/small/riswhoisdump.IPv6",
+        ]);
+        let json: serde_json::Value = serde_json::from_str(&report(&with_announcements)).unwrap();
+        assert_eq!(2, json["changed"]);
+        assert_eq!("InvalidLength", json["changed_announcements"][0]["old"]);
+
+        with_announcements.extend(&["--format", "text"]);
+        assert_eq!(
+            "VRP changes\n  \
+             Added:    2\n  \
+             Removed:  2\n  \
+             Changed:  2\n\
+             \n\
+             Added VRPs:\n  \
+             AS: AS65000, Prefix: 10.0.0.0/16, Max Length: 24\n  \
+             AS: AS65001, Prefix: 2001:db8:1::/48, Max Length: 48\n\
+             \n\
+             Removed VRPs:\n  \
+             AS: AS65000, Prefix: 10.0.0.0/16, Max Length: 16\n  \
+             AS: AS65003, Prefix: 192.168.1.0/24, Max Length: 24\n\
+             \n\
+             Changed announcements:\n  \
+             AS65000 10.0.1.0/24: invalid length -> valid\n  \
+             AS65001 2001:db8:1::/48: invalid asn -> valid\n",
+            report(&with_announcements)
+        );
+    }
+}
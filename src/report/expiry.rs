@@ -0,0 +1,103 @@
+//! Flag VRPs that are expired, or close to expiry, according to the
+//! validity window of their certificates, if the VRP input includes it.
+use crate::vrps::ValidatedRoaPayload;
+use chrono::Duration;
+use chrono::NaiveDateTime;
+use std::fmt;
+
+/// How long before expiry VRPs are flagged, unless set otherwise.
+pub const DEFAULT_EXPIRING_WITHIN_HOURS: i64 = 24;
+
+//------------ ExpiryCheck ---------------------------------------------------
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ExpiryCheck {
+    now: String,
+    expiring_within_hours: i64,
+    expired: Vec<ValidatedRoaPayload>,
+    expiring: Vec<ValidatedRoaPayload>,
+    not_yet_valid: Vec<ValidatedRoaPayload>,
+}
+
+impl ExpiryCheck {
+    /// Checks the VRPs against the given time. VRPs without a validity
+    /// window in the input are never flagged.
+    pub fn analyse(vrps: &[&ValidatedRoaPayload], now: NaiveDateTime, window: Duration) -> Self {
+        let flagged = |f: &dyn Fn(&ValidatedRoaPayload) -> bool| -> Vec<ValidatedRoaPayload> {
+            vrps.iter()
+                .filter(|vrp| f(vrp))
+                .map(|vrp| (*vrp).clone())
+                .collect()
+        };
+
+        ExpiryCheck {
+            now: now.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            expiring_within_hours: window.num_hours(),
+            expired: flagged(&|vrp| vrp.is_expired(now)),
+            expiring: flagged(&|vrp| vrp.expires_within(now, window)),
+            not_yet_valid: flagged(&|vrp| vrp.is_not_yet_valid(now)),
+        }
+    }
+
+    pub fn expired(&self) -> &[ValidatedRoaPayload] {
+        &self.expired
+    }
+    pub fn expiring(&self) -> &[ValidatedRoaPayload] {
+        &self.expiring
+    }
+    pub fn not_yet_valid(&self) -> &[ValidatedRoaPayload] {
+        &self.not_yet_valid
+    }
+}
+
+impl fmt::Display for ExpiryCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "VRP validity at {}", self.now)?;
+        let lists = [
+            ("Expired:", &self.expired),
+            ("Expiring soon:", &self.expiring),
+            ("Not yet valid:", &self.not_yet_valid),
+        ];
+        for (name, vrps) in &lists {
+            writeln!(f, "  {:<18}{}", name, vrps.len())?;
+            for vrp in vrps.iter() {
+                writeln!(f, "    {}", vrp)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vrps::parse_timestamp;
+    use crate::vrps::Vrps;
+    use std::path::PathBuf;
+
+    #[test]
+    fn should_flag_expired_vrps() {
+        let vrps = Vrps::from_file(&PathBuf::from("test/expiry/vrps.csv")).unwrap();
+        let now = parse_timestamp("2019-03-04T10:00:00Z").unwrap();
+
+        let check = ExpiryCheck::analyse(&vrps.all(), now, Duration::hours(24));
+
+        let names = |vrps: &[ValidatedRoaPayload]| -> Vec<String> {
+            let mut res: Vec<String> = vrps.iter().map(|v| v.prefix().to_string()).collect();
+            res.sort();
+            res
+        };
+        assert_eq!(vec!["10.0.0.0/16"], names(check.expired()));
+        assert_eq!(vec!["10.1.0.0/16"], names(check.expiring()));
+        assert_eq!(vec!["10.2.0.0/16"], names(check.not_yet_valid()));
+
+        let json = serde_json::to_string(&check).unwrap();
+        assert!(json.starts_with("{\"now\":\"2019-03-04T10:00:00Z\",\"expiring_within_hours\":24,"));
+        assert!(json.contains("\"not_after\":\"2019-03-01T00:00:00Z\""));
+
+        let check = ExpiryCheck::analyse(&vrps.all(), now, Duration::hours(1));
+        assert!(check.expiring().is_empty());
+    }
+}
@@ -0,0 +1,358 @@
+//! Validate all route objects in an IRR dump against the VRPs, to find IRR
+//! entries that are inconsistent with RPKI.
+use crate::announcements::Announcement;
+use crate::config;
+use crate::config::Config;
+use crate::input::Progress;
+use crate::irr;
+use crate::irr::IrrRoute;
+use crate::irr::IrrRoutes;
+use crate::report;
+use crate::report::ScopeLimits;
+use crate::validation::ValidatedAnnouncement;
+use crate::validation::ValidationPolicy;
+use crate::validation::ValidationState;
+use crate::vrps;
+use crate::vrps::Vrps;
+use clap::ArgMatches;
+use std::fmt;
+use std::path::PathBuf;
+
+//------------ IrrCheckOpts --------------------------------------------------
+
+pub struct IrrCheckOpts {
+    vrps: PathBuf,
+    slurm: Option<PathBuf>,
+    irr: PathBuf,
+    scope: ScopeLimits,
+    policy: ValidationPolicy,
+    format: IrrCheckFormat,
+    progress: Progress,
+}
+
+impl IrrCheckOpts {
+    pub fn parse(matches: &ArgMatches) -> Result<Self, Error> {
+        let config = Config::from_matches(matches)?;
+        let progress = Progress::from_matches(matches);
+        let vrps = config.vrps()?;
+        let slurm = config.optional_slurm();
+        let irr = config.irr()?;
+        let scope = ScopeLimits::from_matches(matches)?;
+
+        let policy = if matches.is_present("ignore_max_length") {
+            ValidationPolicy::IgnoreMaxLength
+        } else {
+            ValidationPolicy::Strict
+        };
+
+        let format = match matches.value_of("format") {
+            None | Some("json") => IrrCheckFormat::Json,
+            Some("text") => IrrCheckFormat::Text,
+            Some(f) => {
+                return Err(Error::WithMessage(format!(
+                    "Unsupported format: {}. Supported are: json|text",
+                    f
+                )))
+            }
+        };
+
+        Ok(IrrCheckOpts {
+            vrps,
+            slurm,
+            irr,
+            scope,
+            policy,
+            format,
+            progress,
+        })
+    }
+}
+
+pub enum IrrCheckFormat {
+    Json,
+    Text,
+}
+
+//------------ IrrInvalid ----------------------------------------------------
+
+/// An IRR route that would be RPKI invalid if it were announced.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct IrrInvalid {
+    route: IrrRoute,
+    state: ValidationState,
+}
+
+impl IrrInvalid {
+    pub fn route(&self) -> &IrrRoute {
+        &self.route
+    }
+    pub fn state(&self) -> &ValidationState {
+        &self.state
+    }
+}
+
+//------------ IrrValidation -------------------------------------------------
+
+/// The validity of all IRR routes, as if they were announced. Only the
+/// invalid routes are listed.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct IrrValidation {
+    routes: usize,
+    valid: usize,
+    invalid_asn: usize,
+    invalid_length: usize,
+    not_found: usize,
+    invalids: Vec<IrrInvalid>,
+}
+
+impl IrrValidation {
+    fn add(&mut self, route: &IrrRoute, state: ValidationState) {
+        self.routes += 1;
+        match state {
+            ValidationState::Valid => self.valid += 1,
+            ValidationState::NotFound => self.not_found += 1,
+            ValidationState::InvalidAsn => self.invalid_asn += 1,
+            ValidationState::InvalidLength => self.invalid_length += 1,
+        }
+        match state {
+            ValidationState::InvalidAsn | ValidationState::InvalidLength => {
+                self.invalids.push(IrrInvalid {
+                    route: route.clone(),
+                    state,
+                })
+            }
+            ValidationState::Valid | ValidationState::NotFound => {}
+        }
+    }
+
+    pub fn routes(&self) -> usize {
+        self.routes
+    }
+    pub fn valid(&self) -> usize {
+        self.valid
+    }
+    pub fn not_found(&self) -> usize {
+        self.not_found
+    }
+    pub fn invalids(&self) -> &[IrrInvalid] {
+        &self.invalids
+    }
+}
+
+impl fmt::Display for IrrValidation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "IRR routes")?;
+        writeln!(f, "  Total:            {}", self.routes)?;
+        writeln!(f, "  Valid:            {}", self.valid)?;
+        writeln!(f, "  Invalid ASN:      {}", self.invalid_asn)?;
+        writeln!(f, "  Invalid length:   {}", self.invalid_length)?;
+        writeln!(f, "  Not found:        {}", self.not_found)?;
+        if !self.invalids.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Invalid IRR routes:")?;
+            for invalid in &self.invalids {
+                writeln!(f, "  {} {}", invalid.route, invalid.state)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+//------------ IrrCheckReporter ----------------------------------------------
+
+pub struct IrrCheckReporter<'a> {
+    routes: &'a IrrRoutes,
+    vrps: &'a Vrps,
+    policy: ValidationPolicy,
+}
+
+impl<'a> IrrCheckReporter<'a> {
+    pub fn new(routes: &'a IrrRoutes, vrps: &'a Vrps) -> Self {
+        IrrCheckReporter {
+            routes,
+            vrps,
+            policy: ValidationPolicy::default(),
+        }
+    }
+
+    /// Uses the given validation policy, instead of strict validation.
+    pub fn with_policy(mut self, policy: ValidationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Validates the IRR routes in scope as if they were announced by their
+    /// origin.
+    pub fn analyse(&self, scope: &ScopeLimits) -> IrrValidation {
+        let mut res = IrrValidation::default();
+        for route in self.routes.in_scope(scope) {
+            let ann = Announcement::new(route.prefix().clone(), route.origin());
+            let vrps = self.vrps.containing(ann.as_ref());
+            let validated = ValidatedAnnouncement::create_with_policy(&ann, &vrps, self.policy);
+            res.add(route, validated.state().clone());
+        }
+        res
+    }
+
+    pub fn execute(options: &IrrCheckOpts) -> Result<(), Error> {
+        let vrps = Vrps::load(&options.vrps, options.slurm.as_deref(), options.progress)?;
+        let routes = IrrRoutes::from_file(&options.irr)?;
+
+        let res = IrrCheckReporter::new(&routes, &vrps)
+            .with_policy(options.policy)
+            .analyse(&options.scope);
+
+        match options.format {
+            IrrCheckFormat::Json => println!("{}", serde_json::to_string(&res)?),
+            IrrCheckFormat::Text => print!("{}", res),
+        }
+        Ok(())
+    }
+}
+
+//------------ Error --------------------------------------------------------
+
+#[derive(Debug, Display)]
+pub enum Error {
+    #[display(fmt = "{}", _0)]
+    WithMessage(String),
+
+    #[display(fmt = "{}", _0)]
+    ConfigError(config::Error),
+
+    #[display(fmt = "{}", _0)]
+    VrpsError(vrps::Error),
+
+    #[display(fmt = "{}", _0)]
+    IrrError(irr::Error),
+
+    #[display(fmt = "{}", _0)]
+    JsonError(serde_json::Error),
+
+    #[display(fmt = "{}", _0)]
+    ScopeError(report::Error),
+}
+
+impl From<config::Error> for Error {
+    fn from(e: config::Error) -> Self {
+        Error::ConfigError(e)
+    }
+}
+
+impl From<vrps::Error> for Error {
+    fn from(e: vrps::Error) -> Self {
+        Error::VrpsError(e)
+    }
+}
+
+impl From<irr::Error> for Error {
+    fn from(e: irr::Error) -> Self {
+        Error::IrrError(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::JsonError(e)
+    }
+}
+
+impl From<report::Error> for Error {
+    fn from(e: report::Error) -> Self {
+        Error::ScopeError(e)
+    }
+}
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+    use std::path::Path;
+    use std::str::FromStr;
+
+    fn parse(args: &[&str]) -> Result<IrrCheckOpts, Error> {
+        let matches = cli::subcommand_matches(args);
+        IrrCheckOpts::parse(&matches)
+    }
+
+    #[test]
+    fn should_parse_scope() {
+        let opts = parse(&[
+            "irr-check",
+            "--vrps",
+            "test/small/vrps.csv",
+            "--irr",
+            "test/irr-check/routes.txt",
+            "--ips",
+            "10.0.0.0/16",
+            "--family",
+            "ipv4",
+        ])
+        .unwrap();
+        assert_eq!(
+            ScopeLimits::from_str("10.0.0.0/16, ipv4").unwrap(),
+            opts.scope
+        );
+
+        let routes = IrrRoutes::from_file(&opts.irr).unwrap();
+        let vrps = Vrps::from_file(&opts.vrps).unwrap();
+        let res = IrrCheckReporter::new(&routes, &vrps).analyse(&opts.scope);
+        assert_eq!(3, res.routes());
+        assert!(res.invalids().iter().all(|invalid| invalid
+            .route()
+            .prefix()
+            .to_string()
+            .starts_with("10.0.")));
+
+        assert_eq!(
+            ScopeLimits::empty(),
+            parse(&["irr-check", "--vrps", "v.csv", "--irr", "r.txt"])
+                .unwrap()
+                .scope
+        );
+        assert!(parse(&[
+            "irr-check",
+            "--vrps",
+            "v.csv",
+            "--irr",
+            "r.txt",
+            "--family",
+            "ip"
+        ])
+        .is_err());
+        assert!(parse(&["irr-check", "--vrps", "v.csv"]).is_err());
+    }
+
+    #[test]
+    fn should_validate_irr_routes() {
+        let routes = IrrRoutes::from_file(Path::new("test/irr-check/routes.txt")).unwrap();
+        let vrps = Vrps::from_file(Path::new("test/small/vrps.csv")).unwrap();
+
+        let res = IrrCheckReporter::new(&routes, &vrps).analyse(&ScopeLimits::empty());
+
+        assert_eq!(5, res.routes());
+        assert_eq!(2, res.valid());
+        assert_eq!(1, res.not_found());
+
+        let mut invalids: Vec<String> = res
+            .invalids()
+            .iter()
+            .map(|invalid| format!("{} {}", invalid.route(), invalid.state()))
+            .collect();
+        invalids.sort();
+        assert_eq!(
+            vec![
+                "10.0.1.0/24 AS65000 invalid length",
+                "10.0.2.0/24 AS65001 invalid asn",
+            ],
+            invalids
+        );
+
+        let routes = IrrCheckReporter::new(&routes, &vrps)
+            .with_policy(ValidationPolicy::IgnoreMaxLength)
+            .analyse(&ScopeLimits::empty());
+        assert_eq!(1, routes.invalids().len());
+    }
+}
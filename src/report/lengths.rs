@@ -0,0 +1,67 @@
+//! Histograms of the prefix lengths used in announcements and VRPs.
+use crate::announcements::Announcements;
+use crate::ip::IpAddressFamily;
+use crate::ip::IpPrefix;
+use crate::report::ScopeLimits;
+use crate::vrps::Vrps;
+use std::collections::BTreeMap;
+
+//------------ PrefixLengthHistogram -----------------------------------------
+
+/// Counts the number of prefixes per prefix length, split by address
+/// family. Lengths that do not occur are left out.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct PrefixLengthHistogram {
+    v4: BTreeMap<u8, usize>,
+    v6: BTreeMap<u8, usize>,
+}
+
+impl PrefixLengthHistogram {
+    pub fn add(&mut self, prefix: &IpPrefix) {
+        let buckets = match prefix.ip_address_family() {
+            IpAddressFamily::Ipv4 => &mut self.v4,
+            IpAddressFamily::Ipv6 => &mut self.v6,
+        };
+        *buckets.entry(prefix.length()).or_default() += 1;
+    }
+}
+
+//------------ PrefixLengthResult --------------------------------------------
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct PrefixLengthResult {
+    announcements: PrefixLengthHistogram,
+    vrps: PrefixLengthHistogram,
+}
+
+//------------ PrefixLengthReporter ------------------------------------------
+
+pub struct PrefixLengthReporter<'a> {
+    announcements: &'a Announcements,
+    vrps: &'a Vrps,
+}
+
+impl<'a> PrefixLengthReporter<'a> {
+    pub fn new(announcements: &'a Announcements, vrps: &'a Vrps) -> Self {
+        PrefixLengthReporter {
+            announcements,
+            vrps,
+        }
+    }
+
+    /// Counts the prefix lengths of all announcements and VRPs in the given
+    /// scope. For VRPs the length of the prefix is used, not the max length.
+    pub fn analyse(&self, scope: &ScopeLimits) -> PrefixLengthResult {
+        let mut res = PrefixLengthResult::default();
+
+        for ann in self.announcements.in_scope(scope) {
+            res.announcements.add(ann.prefix());
+        }
+
+        for vrp in self.vrps.in_scope(scope) {
+            res.vrps.add(vrp.prefix());
+        }
+
+        res
+    }
+}
@@ -0,0 +1,194 @@
+//! Look up the delegation of a batch of prefixes, for bulk attribution of
+//! prefixes to countries and registries.
+use crate::config;
+use crate::config::Config;
+use crate::delegations;
+use crate::delegations::DelegationState;
+use crate::delegations::IpDelegations;
+use crate::delegations::Registry;
+use crate::input::Progress;
+use crate::ip::IpPrefix;
+use crate::ip::IpPrefixError;
+use clap::ArgMatches;
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+//------------ LookupOpts ----------------------------------------------------
+
+pub struct LookupOpts {
+    dels: PathBuf,
+    prefixes: PathBuf,
+    progress: Progress,
+}
+
+impl LookupOpts {
+    pub fn parse(matches: &ArgMatches) -> Result<Self, Error> {
+        let config = Config::from_matches(matches)?;
+        let dels = config.delegations()?;
+        let prefixes = PathBuf::from(matches.value_of("prefixes").unwrap());
+        let progress = Progress::from_matches(matches);
+        Ok(LookupOpts {
+            dels,
+            prefixes,
+            progress,
+        })
+    }
+}
+
+//------------ DelegationLookup ----------------------------------------------
+
+/// The delegation found for a prefix. Prefixes that are not in any
+/// delegation get country code 'XX', and no registry or state.
+#[derive(Clone, Debug, Serialize)]
+pub struct DelegationLookup {
+    prefix: IpPrefix,
+    cc: String,
+    registry: Option<Registry>,
+    state: Option<DelegationState>,
+}
+
+impl DelegationLookup {
+    pub fn prefix(&self) -> &IpPrefix {
+        &self.prefix
+    }
+    pub fn cc(&self) -> &str {
+        &self.cc
+    }
+    pub fn registry(&self) -> Option<Registry> {
+        self.registry
+    }
+    pub fn state(&self) -> Option<&DelegationState> {
+        self.state.as_ref()
+    }
+}
+
+//------------ LookupReporter ------------------------------------------------
+
+pub struct LookupReporter<'a> {
+    delegations: &'a IpDelegations,
+}
+
+impl<'a> LookupReporter<'a> {
+    pub fn new(delegations: &'a IpDelegations) -> Self {
+        LookupReporter { delegations }
+    }
+
+    pub fn lookup(&self, prefixes: &[IpPrefix]) -> Vec<DelegationLookup> {
+        prefixes
+            .iter()
+            .map(|prefix| {
+                let range = prefix.as_ref();
+                DelegationLookup {
+                    prefix: prefix.clone(),
+                    cc: self.delegations.find_cc(range).to_string(),
+                    registry: self.delegations.find_registry(range),
+                    state: self.delegations.find_state(range).cloned(),
+                }
+            })
+            .collect()
+    }
+
+    /// Reads a file with one prefix per line. Empty lines and lines starting
+    /// with '#' are ignored.
+    pub fn read(path: &Path) -> Result<Vec<IpPrefix>, Error> {
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+
+        let mut prefixes = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let prefix =
+                IpPrefix::from_str(line).map_err(|e| Error::InvalidLine(line.to_string(), e))?;
+            prefixes.push(prefix);
+        }
+        Ok(prefixes)
+    }
+
+    pub fn execute(options: &LookupOpts) -> Result<(), Error> {
+        let delegations = IpDelegations::from_file_with_progress(&options.dels, options.progress)?;
+        let prefixes = Self::read(&options.prefixes)?;
+
+        let res = LookupReporter::new(&delegations).lookup(&prefixes);
+        println!("{}", serde_json::to_string(&res)?);
+        Ok(())
+    }
+}
+
+//------------ Error --------------------------------------------------------
+
+#[derive(Debug, Display)]
+pub enum Error {
+    #[display(fmt = "Cannot read prefixes: {}", _0)]
+    IoError(io::Error),
+
+    #[display(fmt = "Invalid prefix '{}': {}", _0, _1)]
+    InvalidLine(String, IpPrefixError),
+
+    #[display(fmt = "{}", _0)]
+    DelegationsError(delegations::Error),
+
+    #[display(fmt = "{}", _0)]
+    JsonError(serde_json::Error),
+
+    #[display(fmt = "{}", _0)]
+    ConfigError(config::Error),
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::IoError(e)
+    }
+}
+
+impl From<delegations::Error> for Error {
+    fn from(e: delegations::Error) -> Self {
+        Error::DelegationsError(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::JsonError(e)
+    }
+}
+
+impl From<config::Error> for Error {
+    fn from(e: config::Error) -> Self {
+        Error::ConfigError(e)
+    }
+}
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_look_up_batch() {
+        let delegations =
+            IpDelegations::from_file(&PathBuf::from("test/small/delegated-extended.txt")).unwrap();
+        let prefixes = LookupReporter::read(&PathBuf::from("test/lookup/prefixes.txt")).unwrap();
+
+        let res = LookupReporter::new(&delegations).lookup(&prefixes);
+        let json = serde_json::to_string(&res).unwrap();
+
+        assert_eq!(
+            "[{\"prefix\":\"10.0.0.0/24\",\"cc\":\"NL\",\"registry\":\"ripencc\",\"state\":\"assigned\"},\
+             {\"prefix\":\"192.168.1.0/24\",\"cc\":\"US\",\"registry\":\"arin\",\"state\":\"assigned\"},\
+             {\"prefix\":\"172.16.0.0/24\",\"cc\":\"ZZ\",\"registry\":\"ripencc\",\"state\":\"reserved\"},\
+             {\"prefix\":\"203.0.113.0/24\",\"cc\":\"XX\",\"registry\":null,\"state\":null},\
+             {\"prefix\":\"2001:db8:1::/48\",\"cc\":\"NL\",\"registry\":\"ripencc\",\"state\":\"assigned\"}]",
+            json
+        );
+    }
+}
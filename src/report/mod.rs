@@ -1,6 +1,9 @@
 use crate::ip::AsnSet;
 use crate::ip::IpResourceSet;
+use clap::ArgMatches;
+use std::fmt;
 use std::str::FromStr;
+use std::time::Duration;
 use ip::IpAddress;
 use ip::IpRange;
 use ip::AsnRange;
@@ -10,14 +13,30 @@ use ip::IpAddressError;
 use ip::AsnError;
 use ip::IpPrefix;
 use ip::IpPrefixError;
+use ip::CompactIpRange;
+use ip::IpAddressFamily;
+use ip::IpRespourceSetError;
 
+pub mod aggregate;
+pub mod asn;
+pub mod coverage;
+pub mod debug;
+pub mod diff;
+pub mod expiry;
+pub mod irr_check;
+pub mod lengths;
+pub mod lookup;
+pub mod reject;
 pub mod resources;
+pub mod unreachables;
+pub mod validate;
 pub mod world;
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize)]
 pub struct ScopeLimits {
-    ips:  IpResourceSet,
-    asns: AsnSet,
+    ips:    IpResourceSet,
+    asns:   AsnSet,
+    family: Option<IpAddressFamily>,
 }
 
 impl FromStr for ScopeLimits {
@@ -27,11 +46,25 @@ impl FromStr for ScopeLimits {
         let line = s.replace(" ", "");
         let mut ips = IpResourceSet::empty();
         let mut asns = AsnSet::empty();
+        let mut family = None;
 
         for el in line.split(',') {
+            let el_family = match el.to_lowercase().as_str() {
+                "ipv4" => Some(IpAddressFamily::Ipv4),
+                "ipv6" => Some(IpAddressFamily::Ipv6),
+                _ => None
+            };
+
             if el.is_empty() {
                 continue
-            } else if el.contains('.') || el.contains(':') {
+            } else if el_family.is_some() {
+                if family.is_some() && family != el_family {
+                    return Err(Error::ParseError(
+                        "scope can only be limited to one address family".to_string()
+                    ))
+                }
+                family = el_family;
+            } else if !Self::is_asn(el) && (el.contains('.') || el.contains(':')) {
                 // IPv4 or IPv6
                 if el.contains('-') {
                     let range = IpRange::from_str(el)?;
@@ -56,17 +89,64 @@ impl FromStr for ScopeLimits {
             }
         }
 
-        Ok(ScopeLimits { ips, asns })
+        Ok(ScopeLimits { ips, asns, family })
+    }
+}
+
+impl ScopeLimits {
+    /// Returns whether the scope element is an ASN or ASN range, including
+    /// ASNs in asdot notation like 'AS1.10' or '1.10', which would otherwise
+    /// be taken for an IPv4 address.
+    fn is_asn(el: &str) -> bool {
+        let is_asdot = |s: &str| {
+            let parts: Vec<&str> = s.split('.').collect();
+            parts.len() == 2 && parts.iter().all(|p| u16::from_str(p).is_ok())
+        };
+
+        el.to_lowercase().starts_with("as") || el.split('-').all(is_asdot)
     }
 }
 
 
 impl ScopeLimits {
     pub fn empty() -> Self {
-        ScopeLimits { ips: IpResourceSet::empty(), asns: AsnSet::empty() }
+        ScopeLimits {
+            ips: IpResourceSet::empty(),
+            asns: AsnSet::empty(),
+            family: None
+        }
     }
     pub fn new(ips: IpResourceSet, asns: AsnSet) -> Self {
-        ScopeLimits { ips, asns }
+        ScopeLimits { ips, asns, family: None }
+    }
+
+    /// Parses the scope from the 'ips', 'asns' and 'family' command line
+    /// arguments. Arguments that are not given do not limit the scope.
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, Error> {
+        let ips = match matches.value_of("ips") {
+            Some(ips) => IpResourceSet::from_str(ips)?,
+            None => IpResourceSet::empty()
+        };
+
+        let asns = match matches.value_of("asns") {
+            Some(asns) => AsnSet::from_str(asns)?,
+            None => AsnSet::empty()
+        };
+
+        let scope = ScopeLimits::new(ips, asns);
+        match matches.value_of("family") {
+            None => Ok(scope),
+            Some("ipv4") => Ok(scope.with_family(IpAddressFamily::Ipv4)),
+            Some("ipv6") => Ok(scope.with_family(IpAddressFamily::Ipv6)),
+            Some(family) => Err(Error::InvalidFamily(family.to_string()))
+        }
+    }
+
+    /// Limits the scope to one address family, on top of any IP and ASN
+    /// limits. E.g. use IPv6 with no other limits to analyse all of IPv6.
+    pub fn with_family(mut self, family: IpAddressFamily) -> Self {
+        self.family = Some(family);
+        self
     }
 
     pub fn limits_ips(&self) -> bool {
@@ -77,11 +157,125 @@ impl ScopeLimits {
         !self.asns.is_empty()
     }
 
+    pub fn limits_family(&self) -> bool {
+        self.family.is_some()
+    }
+
+    /// Returns whether resources of the family are in scope.
+    pub fn includes_family(&self, family: IpAddressFamily) -> bool {
+        self.family.is_none_or(|f| f == family)
+    }
+
     pub fn ips(&self) -> &IpResourceSet{ &self.ips }
 
     pub fn asns(&self) -> &AsnSet { &self.asns }
 }
 
+//------------ ReportScope --------------------------------------------------
+
+/// Describes the scope of a report, so that it is clear from the output
+/// whether counts are limited to a scope. Announcements outside of the
+/// scope are not evaluated at all, which is not the same as not found.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ReportScope {
+    limited: bool,
+    ips:     Option<String>,
+    asns:    Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    family:  Option<String>,
+}
+
+impl ReportScope {
+    pub fn is_limited(&self) -> bool { self.limited }
+}
+
+impl<'a> From<&'a ScopeLimits> for ReportScope {
+    fn from(scope: &'a ScopeLimits) -> Self {
+        let ips = if scope.limits_ips() {
+            let ranges: Vec<String> = scope.ips().ranges().iter()
+                .map(|range| CompactIpRange::from(*range).to_string())
+                .collect();
+            Some(ranges.join(", "))
+        } else {
+            None
+        };
+        let asns = if scope.limits_asns() {
+            Some(scope.asns().to_string())
+        } else {
+            None
+        };
+        let family = scope.family.map(|family| family.to_string());
+        let limited = ips.is_some() || asns.is_some() || family.is_some();
+        ReportScope { limited, ips, asns, family }
+    }
+}
+
+impl fmt::Display for ReportScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.limited {
+            return write!(f, "Scope: all resources")
+        }
+
+        write!(f, "Scope:")?;
+        if let Some(ips) = &self.ips {
+            write!(f, " ips: {}", ips)?;
+        }
+        if self.ips.is_some() && self.asns.is_some() {
+            write!(f, ",")?;
+        }
+        if let Some(asns) = &self.asns {
+            write!(f, " asns: {}", asns)?;
+        }
+        if let Some(family) = &self.family {
+            if self.ips.is_some() || self.asns.is_some() {
+                write!(f, ",")?;
+            }
+            write!(f, " family: {}", family)?;
+        }
+        write!(f, " (announcements and VRPs outside of the scope are not counted)")
+    }
+}
+
+//------------ RunSummary ---------------------------------------------------
+
+/// A short human readable summary of a report run, printed to stderr with
+/// --stats so that it does not get mixed with the machine readable output.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RunSummary {
+    announcements: usize,
+    invalids:      usize,
+    vrps:          usize,
+    unseen:        usize,
+    elapsed:       Duration,
+}
+
+impl RunSummary {
+    pub fn new(
+        announcements: usize,
+        invalids: usize,
+        vrps: usize,
+        unseen: usize,
+        elapsed: Duration
+    ) -> Self {
+        RunSummary { announcements, invalids, vrps, unseen, elapsed }
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Announcements: {}, invalid: {}, VRPs: {}, unseen: {}, took: {:.2}s",
+            self.announcements,
+            self.invalids,
+            self.vrps,
+            self.unseen,
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
 //------------ Error --------------------------------------------------------
 
 #[derive(Debug, Display)]
@@ -100,6 +294,12 @@ pub enum Error {
 
     #[display(fmt = "{}", _0)]
     AsnError(AsnError),
+
+    #[display(fmt = "{}", _0)]
+    IpResourceSetError(IpRespourceSetError),
+
+    #[display(fmt = "Invalid address family: {}, expected ipv4 or ipv6", _0)]
+    InvalidFamily(String),
 }
 
 impl From<IpPrefixError> for Error {
@@ -118,6 +318,10 @@ impl From<AsnError> for Error {
     fn from(e: AsnError) -> Self { Error::AsnError(e) }
 }
 
+impl From<IpRespourceSetError> for Error {
+    fn from(e: IpRespourceSetError) -> Self { Error::IpResourceSetError(e) }
+}
+
 
 //------------ Tests --------------------------------------------------------
 
@@ -137,6 +341,32 @@ mod tests {
                 AsnSet::empty()
             ),
             set);
+
+        let set = ScopeLimits::from_str("IPv6, AS65000").unwrap();
+        assert!(set.limits_family());
+        assert!(set.includes_family(IpAddressFamily::Ipv6));
+        assert!(!set.includes_family(IpAddressFamily::Ipv4));
+
+        assert!(ScopeLimits::from_str("ipv4, ipv6").is_err());
+    }
+
+    #[test]
+    fn should_parse_asdot_asns() {
+        let set = ScopeLimits::from_str(
+            "AS1.10, as1.11, 1.12, 1.20-1.30, AS2.0-AS2.5, 10.0.0.0/8"
+        ).unwrap();
+        assert_eq!(
+            ScopeLimits::new(
+                IpResourceSet::from_str("10.0.0.0/8").unwrap(),
+                AsnSet::from_str(
+                    "AS65546, AS65547, AS65548, AS65556-AS65566, AS131072-AS131077"
+                ).unwrap()
+            ),
+            set);
+
+        assert!(ScopeLimits::from_str("AS1.65536").is_err());
+        assert!(ScopeLimits::from_str("10.0.0.0.1").is_err());
+        assert!(ScopeLimits::from_str("10.0.0.1").is_ok());
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,211 @@
+//! Find the announced space that loses its only route if RPKI invalid
+//! announcements are rejected.
+use crate::announcements::Announcements;
+use crate::ip::Asn;
+use crate::ip::IpPrefix;
+use crate::ip::IpRange;
+use crate::ip::IpResourceSet;
+use crate::report::ScopeLimits;
+use crate::validation::ValidationCache;
+use crate::validation::ValidationPolicy;
+use crate::validation::ValidationState;
+use crate::vrps::Vrps;
+
+//------------ RejectImpact --------------------------------------------------
+
+/// The number of invalid announcements in scope, and the space they cover
+/// that no valid or not found announcement covers too.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct RejectImpact {
+    invalids: usize,
+    unreachable: Vec<IpPrefix>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    withdrawn: Option<Vec<WithdrawnRoute>>,
+}
+
+impl RejectImpact {
+    pub fn invalids(&self) -> usize {
+        self.invalids
+    }
+
+    pub fn unreachable(&self) -> &[IpPrefix] {
+        &self.unreachable
+    }
+
+    /// The invalid announcements that make up the unreachable space, if
+    /// they were asked for.
+    pub fn withdrawn(&self) -> Option<&[WithdrawnRoute]> {
+        self.withdrawn.as_deref()
+    }
+}
+
+//------------ WithdrawnRoute ------------------------------------------------
+
+/// An invalid announcement that would be withdrawn, and that was the only
+/// route for some of its space.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct WithdrawnRoute {
+    prefix: IpPrefix,
+    origin: Asn,
+    state: ValidationState,
+}
+
+impl WithdrawnRoute {
+    pub fn prefix(&self) -> &IpPrefix {
+        &self.prefix
+    }
+    pub fn origin(&self) -> &Asn {
+        &self.origin
+    }
+    pub fn state(&self) -> &ValidationState {
+        &self.state
+    }
+}
+
+//------------ RejectImpactReporter ------------------------------------------
+
+pub struct RejectImpactReporter<'a> {
+    announcements: &'a Announcements,
+    vrps: &'a Vrps,
+    policy: ValidationPolicy,
+    withdrawn: bool,
+}
+
+impl<'a> RejectImpactReporter<'a> {
+    pub fn new(announcements: &'a Announcements, vrps: &'a Vrps) -> Self {
+        RejectImpactReporter {
+            announcements,
+            vrps,
+            policy: ValidationPolicy::default(),
+            withdrawn: false,
+        }
+    }
+
+    /// Uses the given validation policy, instead of strict validation.
+    pub fn with_policy(mut self, policy: ValidationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Also lists the invalid announcements that cause the unreachable
+    /// space, so that they can be reviewed one by one.
+    pub fn with_withdrawn(mut self) -> Self {
+        self.withdrawn = true;
+        self
+    }
+
+    /// Finds the space of the invalid announcements in scope that would
+    /// no longer be routed if invalids were dropped. Space stays routed if
+    /// a valid or not found announcement covers it, either a less specific
+    /// or a more specific, also when that announcement is out of scope.
+    pub fn analyse(&self, scope: &ScopeLimits) -> RejectImpact {
+        let mut cache = ValidationCache::with_policy(self.policy);
+        let mut state_of = |ann| {
+            cache
+                .validate(ann, || self.vrps.containing(ann.as_ref()))
+                .state()
+                .clone()
+        };
+        let is_invalid = |state: &ValidationState| match state {
+            ValidationState::InvalidAsn | ValidationState::InvalidLength => true,
+            ValidationState::Valid | ValidationState::NotFound => false,
+        };
+
+        let mut invalids = 0;
+        let mut unreachable: Vec<IpRange> = vec![];
+        let mut withdrawn = vec![];
+
+        for ann in self.announcements.in_scope(scope) {
+            let state = state_of(ann);
+            if !is_invalid(&state) {
+                continue;
+            }
+            invalids += 1;
+
+            let range = *ann.as_ref();
+            let mut lost = IpResourceSet::from_ranges(vec![range]);
+            for other in self.announcements.intersecting(&range) {
+                if !is_invalid(&state_of(other)) {
+                    lost.remove_ip_range(*other.as_ref());
+                }
+            }
+
+            if !lost.is_empty() {
+                withdrawn.push(WithdrawnRoute {
+                    prefix: ann.prefix().clone(),
+                    origin: ann.asn(),
+                    state,
+                });
+            }
+            unreachable.extend(lost.ranges());
+        }
+
+        RejectImpact {
+            invalids,
+            unreachable: IpResourceSet::from_ranges(unreachable).to_prefixes(),
+            withdrawn: if self.withdrawn {
+                Some(withdrawn)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    #[test]
+    fn should_find_space_without_other_routes() {
+        let announcements =
+            Announcements::from_ris(&[PathBuf::from("test/reject/riswhoisdump.IPv4")]).unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/reject/vrps.csv")).unwrap();
+        let reporter = RejectImpactReporter::new(&announcements, &vrps);
+
+        let unreachable = |scope: &str| {
+            let impact = reporter.analyse(&ScopeLimits::from_str(scope).unwrap());
+            let prefixes: Vec<String> =
+                impact.unreachable().iter().map(|p| p.to_string()).collect();
+            (impact.invalids(), prefixes)
+        };
+
+        // The invalid 10.0.0.0/23 keeps the half covered by a valid /24.
+        // The invalid 172.16.0.0/23 is covered by a not found /22.
+        assert_eq!((3, vec!["10.0.1.0/24".to_string()]), unreachable(""));
+        assert_eq!((2, vec![]), unreachable("172.16.0.0/16"));
+        assert_eq!((0, vec![]), unreachable("192.168.0.0/16"));
+    }
+
+    #[test]
+    fn should_list_withdrawn_routes_causing_unreachable_space() {
+        let announcements =
+            Announcements::from_ris(&[PathBuf::from("test/reject/riswhoisdump.IPv4")]).unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/reject/vrps.csv")).unwrap();
+
+        let impact =
+            RejectImpactReporter::new(&announcements, &vrps).analyse(&ScopeLimits::empty());
+        assert_eq!(None, impact.withdrawn());
+
+        // Only the invalid 10.0.0.0/23 leaves space unreachable, the other
+        // invalids are covered by the not found 172.16.0.0/22.
+        let impact = RejectImpactReporter::new(&announcements, &vrps)
+            .with_withdrawn()
+            .analyse(&ScopeLimits::empty());
+        assert_eq!(
+            Some(
+                &[WithdrawnRoute {
+                    prefix: IpPrefix::from_str("10.0.0.0/23").unwrap(),
+                    origin: Asn::from_str("AS65001").unwrap(),
+                    state: ValidationState::InvalidAsn,
+                }][..]
+            ),
+            impact.withdrawn()
+        );
+    }
+}
@@ -1,28 +1,65 @@
 use crate::announcements;
 use crate::announcements::Announcements;
+use crate::config;
+use crate::config::Config;
+use crate::input::Progress;
+use crate::ip::Asn;
 use crate::ip::AsnError;
-use crate::ip::AsnSet;
+use crate::ip::IpAddressFamily;
+use crate::ip::IpRange;
 use crate::ip::IpResourceSet;
 use crate::ip::IpRespourceSetError;
+use crate::irr;
+use crate::irr::IrrCoverage;
+use crate::irr::IrrRoutes;
+use crate::report;
+use crate::report::coverage::CoverageReporter;
+use crate::report::coverage::SpaceCoverage;
+use crate::report::expiry::ExpiryCheck;
+use crate::report::expiry::DEFAULT_EXPIRING_WITHIN_HOURS;
+use crate::report::ReportScope;
+use crate::report::RunSummary;
 use crate::report::ScopeLimits;
 use crate::validation::ValidatedAnnouncement;
+use crate::validation::ValidationPolicy;
 use crate::validation::ValidationState;
 use crate::validation::VrpImpact;
 use crate::vrps;
 use crate::vrps::ValidatedRoaPayload;
 use crate::vrps::Vrps;
+use chrono::NaiveDateTime;
 use clap::ArgMatches;
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::collections::HashSet;
 use std::fmt;
+use std::fmt::Write;
+use std::io;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
+use std::time::Instant;
 
 //------------ ResourceReportOpts --------------------------------------------
 
 pub struct ResourceReportOpts {
     announcements: Vec<PathBuf>,
+    min_peers: u32,
     vrps: PathBuf,
+    slurm: Option<PathBuf>,
+    irr: Option<PathBuf>,
     scope: ScopeLimits,
+    max_invalids: Option<usize>,
+    stats: bool,
+    policy: ValidationPolicy,
+    ta: Option<String>,
+    rank_by: RankBy,
+    top: usize,
+    now: Option<NaiveDateTime>,
+    expiring_within: chrono::Duration,
     format: ReportFormat,
+    progress: Progress,
 }
 
 impl ResourceReportOpts {
@@ -31,40 +68,73 @@ impl ResourceReportOpts {
     }
 
     pub fn parse(matches: &ArgMatches) -> Result<Self, Error> {
-        let mut announcements = vec![];
-        for name in matches.values_of("announcements").unwrap().into_iter() {
-            announcements.push(PathBuf::from(name))
-        }
+        let config = Config::from_matches(matches)?;
+        let progress = Progress::from_matches(matches);
+        let announcements = config.announcements()?;
+        let min_peers = config.min_peers();
+        let vrps = config.vrps()?;
+        let slurm = config.optional_slurm();
+        let irr = config.optional_irr();
 
-        let vrps_file = matches.value_of("vrps").unwrap();
-        let vrps = PathBuf::from(vrps_file);
+        let scope = ScopeLimits::from_matches(matches)?;
 
-        let ips = {
-            if let Some(ips) = matches.value_of("ips") {
-                IpResourceSet::from_str(ips)?
-            } else {
-                IpResourceSet::empty()
-            }
+        let max_invalids = match matches.value_of("max_invalids") {
+            Some(max) => Some(usize::from_str(max).map_err(|_| {
+                Error::WithMessage(format!("Invalid maximum number of invalids: {}", max))
+            })?),
+            None => None,
         };
 
-        let asns = {
-            if let Some(asns) = matches.value_of("asns") {
-                AsnSet::from_str(asns)?
-            } else {
-                AsnSet::empty()
+        let stats = matches.is_present("stats");
+        let policy = if matches.is_present("ignore_max_length") {
+            ValidationPolicy::IgnoreMaxLength
+        } else {
+            ValidationPolicy::Strict
+        };
+        let ta = matches.value_of("ta").map(|ta| ta.to_string());
+
+        let rank_by = match matches.value_of("rank_by") {
+            None | Some("count") => RankBy::Count,
+            Some("space") => RankBy::Space,
+            Some(r) => {
+                return Err(Error::WithMessage(format!(
+                    "Unsupported ranking: {}. Supported are: count|space",
+                    r
+                )))
             }
         };
 
-        let scope = ScopeLimits::new(ips, asns);
+        let top = match matches.value_of("top") {
+            Some(top) => usize::from_str(top).map_err(|_| {
+                Error::WithMessage(format!("Invalid number of invalid origins: {}", top))
+            })?,
+            None => DEFAULT_TOP_INVALID_ORIGINS,
+        };
+
+        let now = match matches.value_of("now") {
+            Some(now) => Some(vrps::parse_timestamp(now)?),
+            None => None,
+        };
+
+        let expiring_within = match matches.value_of("expiring_within") {
+            Some(hours) => i64::from_str(hours)
+                .map_err(|_| Error::WithMessage(format!("Invalid number of hours: {}", hours)))?,
+            None => DEFAULT_EXPIRING_WITHIN_HOURS,
+        };
+        let expiring_within = chrono::Duration::hours(expiring_within);
 
         let format = {
             if let Some(format) = matches.value_of("format") {
                 match format {
                     "json" => ReportFormat::Json,
                     "text" => ReportFormat::Text,
+                    "rpsl" => ReportFormat::Rpsl,
+                    "csv" => ReportFormat::Csv,
+                    "invalids-csv" => ReportFormat::InvalidsCsv,
+                    "summary" => ReportFormat::Summary,
                     f => {
                         return Err(Error::WithMessage(format!(
-                            "Unsupported format: {}. Supported are: json|text",
+                            "Unsupported format: {}. Supported are: json|text|rpsl|csv|invalids-csv|summary",
                             f
                         )))
                     }
@@ -76,16 +146,40 @@ impl ResourceReportOpts {
 
         Ok(ResourceReportOpts {
             announcements,
+            min_peers,
             vrps,
+            slurm,
+            irr,
             scope,
+            max_invalids,
+            stats,
+            policy,
+            ta,
+            rank_by,
+            top,
+            now,
+            expiring_within,
             format,
+            progress,
         })
     }
 }
 
+/// The number of invalid origins listed, unless set with --top.
+pub const DEFAULT_TOP_INVALID_ORIGINS: usize = 10;
+
 pub enum ReportFormat {
     Json,
     Text,
+    Rpsl,
+    Csv,
+
+    /// The invalid announcements and the totals as CSV, see
+    /// ResourceReportResult::to_csv.
+    InvalidsCsv,
+
+    /// A flat JSON object with the totals only, see ResourceSummary.
+    Summary,
 }
 
 //------------ ResourceReporter ---------------------------------------------
@@ -93,6 +187,12 @@ pub enum ReportFormat {
 pub struct ResourceReporter<'a> {
     announcements: &'a Announcements,
     vrps: &'a Vrps,
+    policy: ValidationPolicy,
+    ta: Option<String>,
+    rank_by: RankBy,
+    top: usize,
+    irr: Option<&'a IrrRoutes>,
+    expiry: Option<(NaiveDateTime, chrono::Duration)>,
 }
 
 impl<'a> ResourceReporter<'a> {
@@ -100,41 +200,193 @@ impl<'a> ResourceReporter<'a> {
         ResourceReporter {
             announcements,
             vrps,
+            policy: ValidationPolicy::default(),
+            ta: None,
+            rank_by: RankBy::Count,
+            top: DEFAULT_TOP_INVALID_ORIGINS,
+            irr: None,
+            expiry: None,
         }
     }
 
+    /// Uses the given validation policy, instead of strict validation.
+    pub fn with_policy(mut self, policy: ValidationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Only reports on the visibility of VRPs from the given trust anchor.
+    /// This does not affect the validation of announcements.
+    pub fn with_ta(mut self, ta: Option<String>) -> Self {
+        self.ta = ta;
+        self
+    }
+
+    /// Ranks the invalid origins in the report as given, and lists the top
+    /// ones only.
+    pub fn with_ranking(mut self, rank_by: RankBy, top: usize) -> Self {
+        self.rank_by = rank_by;
+        self.top = top;
+        self
+    }
+
+    /// Cross-checks the given IRR routes with the VRPs as well.
+    pub fn with_irr(mut self, irr: Option<&'a IrrRoutes>) -> Self {
+        self.irr = irr;
+        self
+    }
+
+    /// Checks the validity window of the VRPs in scope at the given time,
+    /// and flags VRPs that expire within the given duration.
+    pub fn with_expiry_check(mut self, now: NaiveDateTime, window: chrono::Duration) -> Self {
+        self.expiry = Some((now, window));
+        self
+    }
+
+    /// Returns the VRPs in scope, and from the trust anchor if one is set.
+    fn vrps_in_scope(&self, scope: &ScopeLimits) -> Vec<&'a ValidatedRoaPayload> {
+        let mut vrps = self.vrps.in_scope(scope);
+        if let Some(ta) = &self.ta {
+            vrps.retain(|vrp| vrp.is_from_ta(ta));
+        }
+        vrps
+    }
+
     pub fn analyse(&self, scope: &ScopeLimits) -> ResourceReportResult {
-        let mut anns_res = AnnouncementsResult::default();
+        self.analyse_with_max_invalids(scope, None)
+    }
+
+    /// Analyses the scope, but keeps at most 'max_invalids' invalid
+    /// announcements in the detailed list, to limit memory use on large
+    /// scopes. All invalids are still counted in the totals.
+    ///
+    /// The scope may have overlapping IP ranges, so announcements found for
+    /// more than one range are only counted once. The cap applies to the
+    /// distinct invalids.
+    pub fn analyse_with_max_invalids(
+        &self,
+        scope: &ScopeLimits,
+        max_invalids: Option<usize>,
+    ) -> ResourceReportResult {
+        let mut anns_res = AnnouncementsResult::with_max_invalids(max_invalids);
+        let mut seen = HashSet::new();
         for ann in self.announcements.in_scope(scope) {
+            if !seen.insert(ann) {
+                continue;
+            }
             let matching_roas = self.vrps.containing(ann.as_ref());
-            let validated = ValidatedAnnouncement::create(ann, &matching_roas);
+            let validated =
+                ValidatedAnnouncement::create_with_policy(ann, &matching_roas, self.policy);
             anns_res.add(validated);
         }
 
-        let mut vrps_res = VisibilityResult::default();
-        for vrp in self.vrps.in_scope(scope) {
-            let matching_anns = self.announcements.contained_by(vrp.as_ref());
-            let impact = VrpImpact::evaluate(vrp, &matching_anns);
-            vrps_res.add(vrp, &impact);
-        }
+        let invalid_origins = anns_res.rank_invalid_origins(self.rank_by, self.top);
+        let vrps_res = self.visibility(scope);
+
+        let coverage = CoverageReporter::new(self.announcements, self.vrps).analyse(scope);
+        let irr = self
+            .irr
+            .map(|routes| IrrCoverage::analyse(routes, self.vrps, scope));
+        let expiry = self
+            .expiry
+            .map(|(now, window)| ExpiryCheck::analyse(&self.vrps_in_scope(scope), now, window));
 
         ResourceReportResult {
+            scope: ReportScope::from(scope),
+            coverage,
+            invalid_origins,
             announcements: anns_res,
             vrps: vrps_res,
+            irr,
+            expiry,
         }
     }
 
+    /// Returns the number of VRPs in scope, and the VRPs that are not seen
+    /// in BGP, without validating the announcements.
+    ///
+    /// VRPs are evaluated in parallel, using the current rayon thread pool.
+    /// The unseen VRPs are listed in the same order as in a serial run.
+    pub fn visibility(&self, scope: &ScopeLimits) -> VisibilityResult {
+        self.vrps_in_scope(scope)
+            .par_iter()
+            .fold(VisibilityResult::default, |mut res, vrp| {
+                let matching_anns = self.announcements.contained_by(vrp.as_ref());
+                let impact = VrpImpact::evaluate_with_policy(vrp, &matching_anns, self.policy);
+                res.add(vrp, &impact);
+                res
+            })
+            .reduce(VisibilityResult::default, |mut res, other| {
+                res.merge(other);
+                res
+            })
+    }
+
+    /// Returns all VRPs in the scope, with the number of announcements that
+    /// each VRP makes valid.
+    pub fn vrp_visibility(&self, scope: &ScopeLimits) -> Vec<VrpVisibility> {
+        self.vrps_in_scope(scope)
+            .into_iter()
+            .map(|vrp| {
+                let matching_anns = self.announcements.contained_by(vrp.as_ref());
+                let impact = VrpImpact::evaluate_with_policy(vrp, &matching_anns, self.policy);
+                VrpVisibility {
+                    vrp: vrp.clone(),
+                    matched: impact.seen_count(),
+                }
+            })
+            .collect()
+    }
+
     pub fn execute(options: &ResourceReportOpts) -> Result<(), Error> {
-        let announcements = Announcements::from_ris(&options.announcements)?;
-        let vrps = Vrps::from_file(&options.vrps)?;
+        Self::execute_with_summary(options, &mut io::stderr())
+    }
 
-        let reporter = ResourceReporter::new(&announcements, &vrps);
+    /// Like 'execute', but writes the summary asked for with --stats to the
+    /// given writer, rather than to stderr.
+    pub fn execute_with_summary<W: io::Write>(
+        options: &ResourceReportOpts,
+        summary: &mut W,
+    ) -> Result<(), Error> {
+        let start = Instant::now();
+
+        let announcements = Announcements::from_ris_with_progress(
+            &options.announcements,
+            options.min_peers,
+            options.progress,
+        )?;
+        let vrps = Vrps::load(&options.vrps, options.slurm.as_deref(), options.progress)?;
+        let irr = match &options.irr {
+            Some(path) => Some(IrrRoutes::from_file(path)?),
+            None => None,
+        };
+
+        let mut reporter = ResourceReporter::new(&announcements, &vrps)
+            .with_policy(options.policy)
+            .with_ta(options.ta.clone())
+            .with_ranking(options.rank_by, options.top)
+            .with_irr(irr.as_ref());
+        if let Some(now) = options.now {
+            reporter = reporter.with_expiry_check(now, options.expiring_within);
+        }
 
-        let res = reporter.analyse(options.scope());
+        let res = reporter.analyse_with_max_invalids(options.scope(), options.max_invalids);
 
         match options.format {
             ReportFormat::Json => println!("{}", serde_json::to_string(&res)?),
             ReportFormat::Text => print!("{}", res),
+            ReportFormat::Rpsl => print!("{}", res.to_rpsl()),
+            ReportFormat::Csv => {
+                let visibility = reporter.vrp_visibility(options.scope());
+                print!("{}", VrpVisibility::to_csv(&visibility))
+            }
+            ReportFormat::InvalidsCsv => print!("{}", res.to_csv()),
+            ReportFormat::Summary => println!("{}", serde_json::to_string(&res.summary())?),
+        }
+
+        if options.stats {
+            writeln!(summary, "{}", res.run_summary(start.elapsed()))
+                .map_err(|e| Error::WithMessage(format!("Cannot write summary: {}", e)))?;
         }
 
         Ok(())
@@ -145,15 +397,128 @@ impl<'a> ResourceReporter<'a> {
 
 #[derive(Clone, Debug, Serialize)]
 pub struct ResourceReportResult {
+    scope: ReportScope,
+    coverage: SpaceCoverage,
+    invalid_origins: Vec<InvalidOrigin>,
     announcements: AnnouncementsResult,
     vrps: VisibilityResult,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    irr: Option<IrrCoverage>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expiry: Option<ExpiryCheck>,
+}
+
+impl ResourceReportResult {
+    pub fn summary(&self) -> ResourceSummary {
+        let anns = &self.announcements;
+        ResourceSummary {
+            valid: anns.valid,
+            invalid_asn: anns.invalid_asn,
+            invalid_length: anns.invalid_length,
+            not_found: anns.not_found,
+            unseen: self.vrps.unseen.len(),
+            total: anns.total(),
+        }
+    }
+
+    pub fn run_summary(&self, elapsed: Duration) -> RunSummary {
+        let anns = &self.announcements;
+        RunSummary::new(
+            anns.total(),
+            anns.invalid_asn + anns.invalid_length,
+            self.vrps.total,
+            self.vrps.unseen.len(),
+            elapsed,
+        )
+    }
+
+    /// Returns the invalid announcements as RPSL route and route6 objects,
+    /// separated by empty lines, so they can be compared with IRR data.
+    pub fn to_rpsl(&self) -> String {
+        let mut s = String::new();
+        for ann in &self.announcements.invalids {
+            let announcement = ann.announcement();
+            let class = match announcement.prefix().ip_address_family() {
+                IpAddressFamily::Ipv4 => "route:",
+                IpAddressFamily::Ipv6 => "route6:",
+            };
+            writeln!(s, "{:<16}{}", class, announcement.prefix()).unwrap();
+            writeln!(s, "{:<16}{}", "origin:", announcement.asn()).unwrap();
+            writeln!(s, "{:<16}RPKI {}", "remarks:", ann.state()).unwrap();
+            writeln!(s).unwrap();
+        }
+        s
+    }
+
+    /// Returns the invalid announcements as CSV, one row per announcement
+    /// with the origin and max length of the VRP that made it invalid,
+    /// followed by an empty line and the totals with their own header:
+    ///
+    ///   IP Prefix,ASN,State,ROA ASN,ROA Max Length
+    ///   10.0.1.0/24,AS65000,invalid length,AS65000,16
+    ///
+    ///   Valid,Invalid ASN,Invalid Length,Not Found,Total
+    ///   2,2,1,2,7
+    pub fn to_csv(&self) -> String {
+        let mut s = String::new();
+        writeln!(s, "IP Prefix,ASN,State,ROA ASN,ROA Max Length").unwrap();
+        for ann in &self.announcements.invalids {
+            let announcement = ann.announcement();
+            write!(
+                s,
+                "{},{},{},",
+                announcement.prefix(),
+                announcement.asn(),
+                ann.state()
+            )
+            .unwrap();
+            match ann.covering_vrp() {
+                Some(vrp) => writeln!(s, "{},{}", vrp.asn(), vrp.max_length()).unwrap(),
+                None => writeln!(s, ",").unwrap(),
+            }
+        }
+
+        let anns = &self.announcements;
+        writeln!(s).unwrap();
+        writeln!(s, "Valid,Invalid ASN,Invalid Length,Not Found,Total").unwrap();
+        writeln!(
+            s,
+            "{},{},{},{},{}",
+            anns.valid,
+            anns.invalid_asn,
+            anns.invalid_length,
+            anns.not_found,
+            anns.total()
+        )
+        .unwrap();
+        s
+    }
 }
 
 impl fmt::Display for ResourceReportResult {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.scope)?;
+        writeln!(f)?;
+        writeln!(f, "{}", self.coverage)?;
         writeln!(f, "{}", self.announcements)?;
         writeln!(f)?;
-        writeln!(f, "{}", self.vrps)
+        if !self.invalid_origins.is_empty() {
+            writeln!(f, "Top invalid origins:")?;
+            for origin in &self.invalid_origins {
+                writeln!(f, "    {}", origin)?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "{}", self.vrps)?;
+        if let Some(irr) = &self.irr {
+            writeln!(f, "{}", irr)?;
+        }
+        if let Some(expiry) = &self.expiry {
+            writeln!(f, "{}", expiry)?;
+        }
+        Ok(())
     }
 }
 
@@ -165,25 +530,76 @@ struct AnnouncementsResult {
     invalid_asn: usize,
     invalid_length: usize,
     not_found: usize,
+
+    /// Announcements with a private ASN as origin, whatever their validity.
+    private_origin: usize,
+
     invalids: Vec<ValidatedAnnouncement>,
+
+    /// Set if invalids were left out of the list, because of the cap.
+    truncated: bool,
+
+    #[serde(skip)]
+    max_invalids: Option<usize>,
+
+    /// All invalid prefixes per origin, also when the list is truncated.
+    #[serde(skip)]
+    invalid_ranges: BTreeMap<Asn, Vec<IpRange>>,
 }
 
 impl AnnouncementsResult {
+    fn with_max_invalids(max_invalids: Option<usize>) -> Self {
+        AnnouncementsResult {
+            max_invalids,
+            ..Default::default()
+        }
+    }
+
     pub fn add(&mut self, ann: ValidatedAnnouncement) {
+        if ann.announcement().asn().is_private() {
+            self.private_origin += 1;
+        }
         match ann.state() {
             ValidationState::Valid => self.valid += 1,
             ValidationState::InvalidLength => {
                 self.invalid_length += 1;
-                self.invalids.push(ann);
+                self.add_invalid(ann);
             }
             ValidationState::InvalidAsn => {
                 self.invalid_asn += 1;
-                self.invalids.push(ann);
+                self.add_invalid(ann);
             }
             ValidationState::NotFound => self.not_found += 1,
         }
     }
 
+    fn add_invalid(&mut self, ann: ValidatedAnnouncement) {
+        let announcement = ann.announcement();
+        self.invalid_ranges
+            .entry(announcement.asn())
+            .or_default()
+            .push(*announcement.as_ref());
+
+        match self.max_invalids {
+            Some(max) if self.invalids.len() >= max => self.truncated = true,
+            _ => self.invalids.push(ann),
+        }
+    }
+
+    /// Returns the 'top' invalid origins, ranked as asked. Ties are broken
+    /// by the other measure, and then by ASN, so the order is stable.
+    fn rank_invalid_origins(&self, rank_by: RankBy, top: usize) -> Vec<InvalidOrigin> {
+        let mut origins: Vec<InvalidOrigin> = self
+            .invalid_ranges
+            .iter()
+            .map(|(asn, ranges)| InvalidOrigin::new(*asn, ranges))
+            .collect();
+
+        origins.sort_by(|a, b| rank_by.compare(a, b).then(a.asn.cmp(&b.asn)));
+        origins.truncate(top);
+        origins
+    }
+
     fn total(&self) -> usize {
         self.valid + self.invalid_asn + self.invalid_length + self.not_found
     }
@@ -198,17 +614,97 @@ impl fmt::Display for AnnouncementsResult {
         writeln!(f, "    invalid asn:    {}", self.invalid_asn)?;
         writeln!(f, "    not found:      {}", self.not_found)?;
         writeln!(f, "    total:          {}", self.total())?;
+        writeln!(f, "  Private origin:   {}", self.private_origin)?;
         if !self.invalids.is_empty() {
             writeln!(f)?;
             writeln!(f, "  Invalids:")?;
             for ann in &self.invalids {
                 writeln!(f, "    {}", ann)?;
             }
+            if self.truncated {
+                writeln!(f, "    ... (list truncated)")?;
+            }
         }
         Ok(())
     }
 }
 
+//------------ RankBy -------------------------------------------------------
+
+/// How invalid origins are ranked: by the number of distinct invalid
+/// prefixes, i.e. how much they deaggregate, or by invalid address space.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RankBy {
+    Count,
+    Space,
+}
+
+impl RankBy {
+    /// Orders origins from the highest to the lowest rank.
+    fn compare(self, a: &InvalidOrigin, b: &InvalidOrigin) -> Ordering {
+        let count = b.prefixes.cmp(&a.prefixes);
+        let space = (b.space_v4, b.space_v6).cmp(&(a.space_v4, a.space_v6));
+        match self {
+            RankBy::Count => count.then(space),
+            RankBy::Space => space.then(count),
+        }
+    }
+}
+
+//------------ InvalidOrigin ------------------------------------------------
+
+/// An origin ASN with its number of distinct invalid prefixes, and the
+/// invalid address space per address family. When ranking by space, IPv4
+/// space goes first, because any IPv6 prefix would outweigh all of IPv4.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct InvalidOrigin {
+    asn: Asn,
+    prefixes: usize,
+    space_v4: u128,
+    space_v6: u128,
+}
+
+impl InvalidOrigin {
+    fn new(asn: Asn, ranges: &[IpRange]) -> Self {
+        let mut distinct = ranges.to_vec();
+        distinct.sort();
+        distinct.dedup();
+
+        let set = IpResourceSet::from_ranges(distinct.clone());
+
+        InvalidOrigin {
+            asn,
+            prefixes: distinct.len(),
+            space_v4: set.address_count(IpAddressFamily::Ipv4),
+            space_v6: set.address_count(IpAddressFamily::Ipv6),
+        }
+    }
+}
+
+impl fmt::Display for InvalidOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} prefixes, {} IPv4 addresses, {} IPv6 addresses",
+            self.asn, self.prefixes, self.space_v4, self.space_v6
+        )
+    }
+}
+
+//------------ ResourceSummary ----------------------------------------------
+
+/// The totals of a resource report as a flat object, for simple dashboards.
+/// The total is the number of announcements, unseen the number of VRPs.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ResourceSummary {
+    valid: usize,
+    invalid_asn: usize,
+    invalid_length: usize,
+    not_found: usize,
+    unseen: usize,
+    total: usize,
+}
+
 //------------ VisibilityResult ---------------------------------------------
 
 #[derive(Clone, Debug, Default, Serialize)]
@@ -218,12 +714,26 @@ pub struct VisibilityResult {
 }
 
 impl VisibilityResult {
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    pub fn unseen(&self) -> &[ValidatedRoaPayload] {
+        &self.unseen
+    }
+
     pub fn add(&mut self, vrp: &ValidatedRoaPayload, impact: &VrpImpact) {
         self.total += 1;
         if impact.is_unseen() {
             self.unseen.push(vrp.clone())
         }
     }
+
+    /// Adds the results for VRPs that come after the VRPs in this result.
+    fn merge(&mut self, other: VisibilityResult) {
+        self.total += other.total;
+        self.unseen.extend(other.unseen);
+    }
 }
 
 impl fmt::Display for VisibilityResult {
@@ -244,6 +754,39 @@ impl fmt::Display for VisibilityResult {
     }
 }
 
+//------------ VrpVisibility ------------------------------------------------
+
+/// A VRP with the number of announcements it makes valid.
+#[derive(Clone, Debug)]
+pub struct VrpVisibility {
+    vrp: ValidatedRoaPayload,
+    matched: usize,
+}
+
+impl VrpVisibility {
+    pub fn is_seen(&self) -> bool {
+        self.matched > 0
+    }
+
+    pub fn to_csv(visibility: &[VrpVisibility]) -> String {
+        let mut s = String::new();
+        writeln!(s, "ASN,IP Prefix,Max Length,Seen,Announcements").unwrap();
+        for el in visibility {
+            writeln!(
+                s,
+                "{},{},{},{},{}",
+                el.vrp.asn(),
+                el.vrp.prefix(),
+                el.vrp.max_length(),
+                el.is_seen(),
+                el.matched
+            )
+            .unwrap();
+        }
+        s
+    }
+}
+
 //------------ Error --------------------------------------------------------
 
 #[derive(Debug, Display)]
@@ -263,8 +806,17 @@ pub enum Error {
     #[display(fmt = "{}", _0)]
     VrpsError(vrps::Error),
 
+    #[display(fmt = "{}", _0)]
+    IrrError(irr::Error),
+
     #[display(fmt = "{}", _0)]
     JsonError(serde_json::Error),
+
+    #[display(fmt = "{}", _0)]
+    ConfigError(config::Error),
+
+    #[display(fmt = "{}", _0)]
+    ScopeError(report::Error),
 }
 
 impl Error {
@@ -297,8 +849,432 @@ impl From<vrps::Error> for Error {
     }
 }
 
+impl From<irr::Error> for Error {
+    fn from(e: irr::Error) -> Self {
+        Error::IrrError(e)
+    }
+}
+
 impl From<serde_json::Error> for Error {
     fn from(e: serde_json::Error) -> Self {
         Error::JsonError(e)
     }
 }
+
+impl From<config::Error> for Error {
+    fn from(e: config::Error) -> Self {
+        Error::ConfigError(e)
+    }
+}
+
+impl From<report::Error> for Error {
+    fn from(e: report::Error) -> Self {
+        Error::ScopeError(e)
+    }
+}
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+    use crate::ip::AsnSet;
+
+    #[test]
+    fn should_cap_invalids_but_count_all() {
+        let announcements = Announcements::from_ris(&[
+            PathBuf::from("test/small/riswhoisdump.IPv4"),
+            PathBuf::from("test/small/riswhoisdump.IPv6"),
+        ])
+        .unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/small/vrps.csv")).unwrap();
+        let reporter = ResourceReporter::new(&announcements, &vrps);
+
+        let all = reporter.analyse(&ScopeLimits::empty()).announcements;
+        assert_eq!(3, all.invalids.len());
+        assert!(!all.truncated);
+
+        let capped = reporter
+            .analyse_with_max_invalids(&ScopeLimits::empty(), Some(1))
+            .announcements;
+        assert_eq!(1, capped.invalids.len());
+        assert!(capped.truncated);
+        assert_eq!(all.invalid_asn, capped.invalid_asn);
+        assert_eq!(all.invalid_length, capped.invalid_length);
+        assert_eq!(all.total(), capped.total());
+
+        let json = serde_json::to_string(&capped).unwrap();
+        assert!(json.contains("\"truncated\":true"));
+        assert!(!json.contains("max_invalids"));
+    }
+
+    #[test]
+    fn should_cap_invalids_after_dedup() {
+        let announcements = Announcements::from_ris(&[
+            PathBuf::from("test/small/riswhoisdump.IPv4"),
+            PathBuf::from("test/small/riswhoisdump.IPv6"),
+        ])
+        .unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/small/vrps.csv")).unwrap();
+        let reporter = ResourceReporter::new(&announcements, &vrps);
+
+        let scope = |ips| ScopeLimits::new(IpResourceSet::from_str(ips).unwrap(), AsnSet::empty());
+        let single = reporter.analyse(&scope("0.0.0.0/0")).announcements;
+        let overlapping = scope("0.0.0.0/0, 10.0.0.0/8, 192.168.0.0/16");
+
+        let all = reporter.analyse(&overlapping).announcements;
+        assert_eq!(single.total(), all.total());
+        assert_eq!(single.invalids.len(), all.invalids.len());
+        assert!(!all.truncated);
+
+        let max = single.invalids.len();
+        let capped = reporter
+            .analyse_with_max_invalids(&overlapping, Some(max))
+            .announcements;
+        assert_eq!(max, capped.invalids.len());
+        assert!(!capped.truncated);
+    }
+
+    #[test]
+    fn should_include_scope_in_output() {
+        let announcements = Announcements::from_ris(&[
+            PathBuf::from("test/small/riswhoisdump.IPv4"),
+            PathBuf::from("test/small/riswhoisdump.IPv6"),
+        ])
+        .unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/small/vrps.csv")).unwrap();
+        let reporter = ResourceReporter::new(&announcements, &vrps);
+
+        let all = reporter.analyse(&ScopeLimits::empty());
+        let json = serde_json::to_string(&all).unwrap();
+        assert!(json.starts_with("{\"scope\":{\"limited\":false,\"ips\":null,\"asns\":null}"));
+        assert!(all.to_string().starts_with("Scope: all resources\n"));
+
+        let scope = ScopeLimits::from_str("10.0.0.0/16, AS65000").unwrap();
+        let scoped = reporter.analyse(&scope);
+        let json = serde_json::to_string(&scoped).unwrap();
+        assert!(json.starts_with(
+            "{\"scope\":{\"limited\":true,\"ips\":\"10.0.0.0/16\",\"asns\":\"AS65000\"}"
+        ));
+        assert!(scoped
+            .to_string()
+            .starts_with("Scope: ips: 10.0.0.0/16, asns: AS65000 (announcements and VRPs"));
+    }
+
+    #[test]
+    fn should_export_invalids_as_csv() {
+        let announcements = Announcements::from_ris(&[
+            PathBuf::from("test/small/riswhoisdump.IPv4"),
+            PathBuf::from("test/small/riswhoisdump.IPv6"),
+        ])
+        .unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/small/vrps.csv")).unwrap();
+        let res = ResourceReporter::new(&announcements, &vrps).analyse(&ScopeLimits::empty());
+
+        let csv = res.to_csv();
+        let mut blocks = csv.split("\n\n");
+        let invalids = blocks.next().unwrap();
+        let totals = blocks.next().unwrap();
+
+        let mut reader = csv::ReaderBuilder::new().from_reader(invalids.as_bytes());
+        assert_eq!(
+            vec!["IP Prefix", "ASN", "State", "ROA ASN", "ROA Max Length"],
+            reader.headers().unwrap().iter().collect::<Vec<_>>()
+        );
+        let mut rows: Vec<Vec<String>> = reader
+            .records()
+            .map(|record| record.unwrap().iter().map(str::to_string).collect())
+            .collect();
+        rows.sort();
+        assert_eq!(
+            vec![
+                vec!["10.0.1.0/24", "AS65000", "invalid length", "AS65000", "16"],
+                vec!["10.0.2.0/24", "AS65001", "invalid asn", "AS65000", "16"],
+                vec!["2001:db8:1::/48", "AS65001", "invalid asn", "AS65000", "32"],
+            ],
+            rows
+        );
+
+        assert_eq!(
+            "Valid,Invalid ASN,Invalid Length,Not Found,Total\n2,2,1,2,7\n",
+            totals
+        );
+    }
+
+    #[test]
+    fn should_summarise_run() {
+        let announcements = Announcements::from_ris(&[
+            PathBuf::from("test/small/riswhoisdump.IPv4"),
+            PathBuf::from("test/small/riswhoisdump.IPv6"),
+        ])
+        .unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/small/vrps.csv")).unwrap();
+        let res = ResourceReporter::new(&announcements, &vrps).analyse(&ScopeLimits::empty());
+
+        assert_eq!(
+            "Announcements: 7, invalid: 3, VRPs: 3, unseen: 1, took: 1.50s",
+            res.run_summary(Duration::from_millis(1500)).to_string()
+        );
+    }
+
+    #[test]
+    fn should_write_summary_only_with_stats_flag() {
+        let summary = |extra: &[&str]| {
+            let mut args = vec![
+                "resources",
+                "--announcements",
+                "test/small/riswhoisdump.IPv4",
+                "test/small/riswhoisdump.IPv6",
+                "--vrps",
+                "test/small/vrps.csv",
+                "--format",
+                "summary",
+            ];
+            args.extend(extra);
+            let matches = cli::subcommand_matches(&args);
+            let options = ResourceReportOpts::parse(&matches).unwrap();
+
+            let mut out = vec![];
+            ResourceReporter::execute_with_summary(&options, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert!(summary(&["--stats"])
+            .starts_with("Announcements: 7, invalid: 3, VRPs: 3, unseen: 1, took: "));
+        assert_eq!("", summary(&[]));
+    }
+
+    #[test]
+    fn should_list_all_vrps_with_visibility() {
+        let announcements = Announcements::from_ris(&[
+            PathBuf::from("test/small/riswhoisdump.IPv4"),
+            PathBuf::from("test/small/riswhoisdump.IPv6"),
+        ])
+        .unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/small/vrps.csv")).unwrap();
+        let reporter = ResourceReporter::new(&announcements, &vrps);
+
+        let visibility = reporter.vrp_visibility(&ScopeLimits::empty());
+        let csv = VrpVisibility::to_csv(&visibility);
+
+        let mut rows: Vec<&str> = csv.lines().skip(1).collect();
+        rows.sort();
+        assert_eq!(
+            vec![
+                "AS65000,10.0.0.0/16,16,true,1",
+                "AS65000,2001:db8::/32,32,true,1",
+                "AS65003,192.168.1.0/24,24,false,0",
+            ],
+            rows
+        );
+        assert_eq!(
+            Some("ASN,IP Prefix,Max Length,Seen,Announcements"),
+            csv.lines().next()
+        );
+    }
+
+    #[test]
+    fn should_emit_invalids_as_rpsl() {
+        let announcements = Announcements::from_ris(&[
+            PathBuf::from("test/small/riswhoisdump.IPv4"),
+            PathBuf::from("test/small/riswhoisdump.IPv6"),
+        ])
+        .unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/small/vrps.csv")).unwrap();
+        let res = ResourceReporter::new(&announcements, &vrps).analyse(&ScopeLimits::empty());
+
+        let rpsl = res.to_rpsl();
+        let stanzas: Vec<&str> = rpsl.trim_end().split("\n\n").collect();
+        assert_eq!(res.announcements.invalids.len(), stanzas.len());
+
+        for (stanza, ann) in stanzas.iter().zip(res.announcements.invalids.iter()) {
+            let announcement = ann.announcement();
+            let lines: Vec<&str> = stanza.lines().collect();
+            assert_eq!(3, lines.len());
+
+            let class = match announcement.prefix().ip_address_family() {
+                IpAddressFamily::Ipv4 => "route:",
+                IpAddressFamily::Ipv6 => "route6:",
+            };
+            assert_eq!(format!("{:<16}{}", class, announcement.prefix()), lines[0]);
+            assert_eq!(format!("origin:         {}", announcement.asn()), lines[1]);
+            assert!(lines[2].starts_with("remarks:        RPKI invalid"));
+        }
+    }
+
+    #[test]
+    fn should_filter_unseen_vrps_by_ta() {
+        let announcements = Announcements::from_ris(&[
+            PathBuf::from("test/small/riswhoisdump.IPv4"),
+            PathBuf::from("test/small/riswhoisdump.IPv6"),
+        ])
+        .unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/ta/vrps.csv")).unwrap();
+
+        let all = ResourceReporter::new(&announcements, &vrps).analyse(&ScopeLimits::empty());
+        assert_eq!(4, all.vrps.unseen.len());
+
+        let reporter =
+            ResourceReporter::new(&announcements, &vrps).with_ta(Some("arin".to_string()));
+        let res = reporter.analyse(&ScopeLimits::empty());
+
+        let mut unseen: Vec<String> = res.vrps.unseen.iter().map(|v| v.to_string()).collect();
+        unseen.sort();
+        assert_eq!(
+            vec![
+                "AS: AS65003, Prefix: 192.168.1.0/24, Max Length: 24",
+                "AS: AS65004, Prefix: 198.51.100.0/24, Max Length: 24",
+            ],
+            unseen
+        );
+        assert_eq!(2, res.vrps.total);
+        assert_eq!(all.announcements.total(), res.announcements.total());
+
+        let visibility = reporter.vrp_visibility(&ScopeLimits::empty());
+        assert_eq!(2, visibility.len());
+    }
+
+    #[test]
+    fn should_count_private_origins() {
+        let announcements =
+            Announcements::from_ris(&[PathBuf::from("test/private/riswhoisdump.IPv4")]).unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/small/vrps.csv")).unwrap();
+        let res = ResourceReporter::new(&announcements, &vrps).analyse(&ScopeLimits::empty());
+
+        assert_eq!(8, res.announcements.total());
+        assert_eq!(4, res.announcements.private_origin);
+        assert!(res.to_string().contains("  Private origin:   4\n"));
+
+        let json = serde_json::to_string(&res).unwrap();
+        assert!(json.contains("\"private_origin\":4"));
+    }
+
+    #[test]
+    fn should_rank_invalid_origins() {
+        let announcements =
+            Announcements::from_ris(&[PathBuf::from("test/ranking/riswhoisdump.IPv4")]).unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/ranking/vrps.csv")).unwrap();
+
+        let ranked = |rank_by| -> Vec<String> {
+            ResourceReporter::new(&announcements, &vrps)
+                .with_ranking(rank_by, 10)
+                .analyse(&ScopeLimits::empty())
+                .invalid_origins
+                .iter()
+                .map(|origin| origin.to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            vec![
+                "AS65003: 3 prefixes, 1024 IPv4 addresses, 0 IPv6 addresses",
+                "AS65001: 3 prefixes, 768 IPv4 addresses, 0 IPv6 addresses",
+                "AS65002: 1 prefixes, 65536 IPv4 addresses, 0 IPv6 addresses",
+            ],
+            ranked(RankBy::Count)
+        );
+        assert_eq!(
+            vec![
+                "AS65002: 1 prefixes, 65536 IPv4 addresses, 0 IPv6 addresses",
+                "AS65003: 3 prefixes, 1024 IPv4 addresses, 0 IPv6 addresses",
+                "AS65001: 3 prefixes, 768 IPv4 addresses, 0 IPv6 addresses",
+            ],
+            ranked(RankBy::Space)
+        );
+
+        let top = ResourceReporter::new(&announcements, &vrps)
+            .with_ranking(RankBy::Space, 1)
+            .analyse_with_max_invalids(&ScopeLimits::empty(), Some(1));
+        assert_eq!(1, top.invalid_origins.len());
+        assert_eq!(Asn::from(65002), top.invalid_origins[0].asn);
+    }
+
+    #[test]
+    fn should_report_invalid_space_per_family() {
+        use crate::ip::IpPrefix;
+
+        let range = |s| *IpPrefix::from_str(s).unwrap().as_ref();
+        let mixed = InvalidOrigin::new(
+            Asn::from(65001),
+            &[range("10.0.0.0/24"), range("2001:db8::/32")],
+        );
+        let v4 = InvalidOrigin::new(Asn::from(65002), &[range("10.1.0.0/23")]);
+
+        assert_eq!(
+            "AS65001: 2 prefixes, 256 IPv4 addresses, 79228162514264337593543950336 IPv6 addresses",
+            mixed.to_string()
+        );
+
+        // The IPv6 space does not outweigh the larger IPv4 space.
+        assert_eq!(Ordering::Less, RankBy::Space.compare(&v4, &mixed));
+        assert_eq!(Ordering::Less, RankBy::Count.compare(&mixed, &v4));
+    }
+
+    #[test]
+    fn parallel_visibility_should_match_serial() {
+        let announcements = Announcements::from_ris(&[
+            PathBuf::from("test/20190304/riswhoisdump.IPv4"),
+            PathBuf::from("test/20190304/riswhoisdump.IPv6"),
+        ])
+        .unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/20190304/vrps.csv")).unwrap();
+        let reporter = ResourceReporter::new(&announcements, &vrps);
+        let scope = ScopeLimits::empty();
+
+        let mut serial = VisibilityResult::default();
+        for vrp in reporter.vrps_in_scope(&scope) {
+            let matching_anns = announcements.contained_by(vrp.as_ref());
+            let impact = VrpImpact::evaluate_with_policy(vrp, &matching_anns, reporter.policy);
+            serial.add(vrp, &impact);
+        }
+
+        let parallel = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap()
+            .install(|| reporter.visibility(&scope));
+
+        assert!(!serial.unseen().is_empty());
+        assert_eq!(serial.total(), parallel.total());
+        assert_eq!(
+            serde_json::to_string(&serial).unwrap(),
+            serde_json::to_string(&parallel).unwrap()
+        );
+    }
+
+    #[test]
+    fn summary_should_be_flat() {
+        let announcements = Announcements::from_ris(&[
+            PathBuf::from("test/small/riswhoisdump.IPv4"),
+            PathBuf::from("test/small/riswhoisdump.IPv6"),
+        ])
+        .unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/small/vrps.csv")).unwrap();
+        let res = ResourceReporter::new(&announcements, &vrps).analyse(&ScopeLimits::empty());
+
+        let summary = serde_json::to_value(res.summary()).unwrap();
+        let object = summary.as_object().unwrap();
+
+        let mut keys: Vec<&str> = object.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(
+            vec![
+                "invalid_asn",
+                "invalid_length",
+                "not_found",
+                "total",
+                "unseen",
+                "valid"
+            ],
+            keys
+        );
+
+        assert_eq!(2, object["valid"]);
+        assert_eq!(2, object["invalid_asn"]);
+        assert_eq!(1, object["invalid_length"]);
+        assert_eq!(2, object["not_found"]);
+        assert_eq!(1, object["unseen"]);
+        assert_eq!(7, object["total"]);
+    }
+}
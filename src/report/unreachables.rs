@@ -0,0 +1,413 @@
+//! Report the space that would become unreachable under strict RPKI reject,
+//! with its size, to estimate the impact of dropping invalid announcements.
+use crate::announcements;
+use crate::announcements::Announcements;
+use crate::config;
+use crate::config::Config;
+use crate::input::Progress;
+use crate::ip::IpAddressFamily;
+use crate::ip::IpResourceSet;
+use crate::report;
+use crate::report::reject::RejectImpactReporter;
+use crate::report::reject::WithdrawnRoute;
+use crate::report::ScopeLimits;
+use crate::validation::ValidationPolicy;
+use crate::vrps;
+use crate::vrps::Vrps;
+use clap::ArgMatches;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+//------------ UnreachablesOpts ----------------------------------------------
+
+pub struct UnreachablesOpts {
+    announcements: Vec<PathBuf>,
+    min_peers: u32,
+    vrps: PathBuf,
+    slurm: Option<PathBuf>,
+    scope: ScopeLimits,
+    policy: ValidationPolicy,
+    withdrawn: bool,
+    format: UnreachablesFormat,
+    progress: Progress,
+}
+
+impl UnreachablesOpts {
+    pub fn parse(matches: &ArgMatches) -> Result<Self, Error> {
+        let config = Config::from_matches(matches)?;
+        let progress = Progress::from_matches(matches);
+        let announcements = config.announcements()?;
+        let min_peers = config.min_peers();
+        let vrps = config.vrps()?;
+        let slurm = config.optional_slurm();
+        let scope = ScopeLimits::from_matches(matches)?;
+
+        let policy = if matches.is_present("ignore_max_length") {
+            ValidationPolicy::IgnoreMaxLength
+        } else {
+            ValidationPolicy::Strict
+        };
+
+        let withdrawn = matches.is_present("withdrawn");
+
+        let format = match matches.value_of("format") {
+            None | Some("json") => UnreachablesFormat::Json,
+            Some("text") => UnreachablesFormat::Text,
+            Some(f) => {
+                return Err(Error::WithMessage(format!(
+                    "Unsupported format: {}. Supported are: json|text",
+                    f
+                )))
+            }
+        };
+
+        Ok(UnreachablesOpts {
+            announcements,
+            min_peers,
+            vrps,
+            slurm,
+            scope,
+            policy,
+            withdrawn,
+            format,
+            progress,
+        })
+    }
+}
+
+pub enum UnreachablesFormat {
+    Json,
+    Text,
+}
+
+//------------ Unreachables --------------------------------------------------
+
+/// The space of the invalid announcements in scope that no valid or not
+/// found announcement covers, and its number of addresses per family.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Unreachables {
+    invalids: usize,
+    unreachable: IpResourceSet,
+    addresses_v4: u128,
+    addresses_v6: u128,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    withdrawn: Option<Vec<WithdrawnRoute>>,
+}
+
+impl Unreachables {
+    pub fn invalids(&self) -> usize {
+        self.invalids
+    }
+    pub fn unreachable(&self) -> &IpResourceSet {
+        &self.unreachable
+    }
+    pub fn addresses_v4(&self) -> u128 {
+        self.addresses_v4
+    }
+    pub fn addresses_v6(&self) -> u128 {
+        self.addresses_v6
+    }
+
+    /// The invalid announcements that make up the unreachable space, if
+    /// they were asked for.
+    pub fn withdrawn(&self) -> Option<&[WithdrawnRoute]> {
+        self.withdrawn.as_deref()
+    }
+}
+
+impl fmt::Display for Unreachables {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Unreachable under strict reject")?;
+        writeln!(f, "  Invalid announcements:  {}", self.invalids)?;
+        writeln!(f, "  IPv4 addresses:         {}", self.addresses_v4)?;
+        writeln!(f, "  IPv6 addresses:         {}", self.addresses_v6)?;
+        if !self.unreachable.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Unreachable prefixes:")?;
+            for prefix in self.unreachable.to_prefixes() {
+                writeln!(f, "  {}", prefix)?;
+            }
+        }
+        if let Some(withdrawn) = &self.withdrawn {
+            if !withdrawn.is_empty() {
+                writeln!(f)?;
+                writeln!(f, "Withdrawn routes:")?;
+                for route in withdrawn {
+                    writeln!(
+                        f,
+                        "  {} {} ({})",
+                        route.prefix(),
+                        route.origin(),
+                        route.state()
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+//------------ UnreachablesReporter ------------------------------------------
+
+pub struct UnreachablesReporter<'a> {
+    announcements: &'a Announcements,
+    vrps: &'a Vrps,
+    policy: ValidationPolicy,
+    withdrawn: bool,
+}
+
+impl<'a> UnreachablesReporter<'a> {
+    pub fn new(announcements: &'a Announcements, vrps: &'a Vrps) -> Self {
+        UnreachablesReporter {
+            announcements,
+            vrps,
+            policy: ValidationPolicy::default(),
+            withdrawn: false,
+        }
+    }
+
+    /// Uses the given validation policy, instead of strict validation.
+    pub fn with_policy(mut self, policy: ValidationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Also lists the invalid announcements that cause the unreachable
+    /// space, see RejectImpactReporter::with_withdrawn.
+    pub fn with_withdrawn(mut self) -> Self {
+        self.withdrawn = true;
+        self
+    }
+
+    /// Finds the unreachable space for the invalid announcements in scope.
+    /// Announcements out of scope still keep space reachable, see the
+    /// RejectImpactReporter.
+    pub fn analyse(&self, scope: &ScopeLimits) -> Unreachables {
+        let mut reporter =
+            RejectImpactReporter::new(self.announcements, self.vrps).with_policy(self.policy);
+        if self.withdrawn {
+            reporter = reporter.with_withdrawn();
+        }
+        let impact = reporter.analyse(scope);
+
+        let unreachable = IpResourceSet::from_ranges(
+            impact
+                .unreachable()
+                .iter()
+                .map(|prefix| *prefix.as_ref())
+                .collect(),
+        );
+
+        Unreachables {
+            invalids: impact.invalids(),
+            addresses_v4: unreachable.address_count(IpAddressFamily::Ipv4),
+            addresses_v6: unreachable.address_count(IpAddressFamily::Ipv6),
+            unreachable,
+            withdrawn: impact.withdrawn().map(|routes| routes.to_vec()),
+        }
+    }
+
+    pub fn execute(options: &UnreachablesOpts) -> Result<(), Error> {
+        Self::execute_with_output(options, &mut io::stdout())
+    }
+
+    /// Like 'execute', but writes the report to the given output.
+    pub fn execute_with_output<W: io::Write>(
+        options: &UnreachablesOpts,
+        output: &mut W,
+    ) -> Result<(), Error> {
+        let announcements = Announcements::from_ris_with_progress(
+            &options.announcements,
+            options.min_peers,
+            options.progress,
+        )?;
+        let vrps = Vrps::load(&options.vrps, options.slurm.as_deref(), options.progress)?;
+
+        let mut reporter =
+            UnreachablesReporter::new(&announcements, &vrps).with_policy(options.policy);
+        if options.withdrawn {
+            reporter = reporter.with_withdrawn();
+        }
+        let res = reporter.analyse(&options.scope);
+
+        match options.format {
+            UnreachablesFormat::Json => writeln!(output, "{}", serde_json::to_string(&res)?)?,
+            UnreachablesFormat::Text => write!(output, "{}", res)?,
+        }
+        Ok(())
+    }
+}
+
+//------------ Error --------------------------------------------------------
+
+#[derive(Debug, Display)]
+pub enum Error {
+    #[display(fmt = "{}", _0)]
+    WithMessage(String),
+
+    #[display(fmt = "{}", _0)]
+    ConfigError(config::Error),
+
+    #[display(fmt = "{}", _0)]
+    AnnouncementsError(announcements::Error),
+
+    #[display(fmt = "{}", _0)]
+    VrpsError(vrps::Error),
+
+    #[display(fmt = "{}", _0)]
+    JsonError(serde_json::Error),
+
+    #[display(fmt = "{}", _0)]
+    ScopeError(report::Error),
+
+    #[display(fmt = "{}", _0)]
+    IoError(io::Error),
+}
+
+impl From<config::Error> for Error {
+    fn from(e: config::Error) -> Self {
+        Error::ConfigError(e)
+    }
+}
+
+impl From<announcements::Error> for Error {
+    fn from(e: announcements::Error) -> Self {
+        Error::AnnouncementsError(e)
+    }
+}
+
+impl From<vrps::Error> for Error {
+    fn from(e: vrps::Error) -> Self {
+        Error::VrpsError(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::JsonError(e)
+    }
+}
+
+impl From<report::Error> for Error {
+    fn from(e: report::Error) -> Self {
+        Error::ScopeError(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::IoError(e)
+    }
+}
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+    use std::str::FromStr;
+
+    fn report(args: &[&str]) -> String {
+        let matches = cli::subcommand_matches(args);
+        let opts = UnreachablesOpts::parse(&matches).unwrap();
+
+        let mut output = vec![];
+        UnreachablesReporter::execute_with_output(&opts, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn should_count_unreachable_space() {
+        let announcements =
+            Announcements::from_ris(&[PathBuf::from("test/reject/riswhoisdump.IPv4")]).unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/reject/vrps.csv")).unwrap();
+        let reporter = UnreachablesReporter::new(&announcements, &vrps);
+
+        let res = reporter.analyse(&ScopeLimits::empty());
+        assert_eq!(3, res.invalids());
+        assert_eq!(
+            &IpResourceSet::from_str("10.0.1.0/24").unwrap(),
+            res.unreachable()
+        );
+        assert_eq!(256, res.addresses_v4());
+        assert_eq!(0, res.addresses_v6());
+
+        let res = reporter.analyse(&ScopeLimits::from_str("172.16.0.0/16").unwrap());
+        assert_eq!(2, res.invalids());
+        assert!(res.unreachable().is_empty());
+        assert_eq!(0, res.addresses_v4());
+    }
+
+    #[test]
+    fn should_print_json_and_text() {
+        let args = [
+            "unreachables",
+            "--announcements",
+            "test/reject/riswhoisdump.IPv4",
+            "--vrps",
+            "test/reject/vrps.csv",
+        ];
+
+        assert_eq!(
+            "{\"invalids\":3,\"unreachable\":\"10.0.1.0-10.0.1.255\",\
+             \"addresses_v4\":256,\"addresses_v6\":0}\n",
+            report(&args)
+        );
+
+        let mut text_args = args.to_vec();
+        text_args.extend(&["--format", "text"]);
+        assert_eq!(
+            "Unreachable under strict reject\n  \
+             Invalid announcements:  3\n  \
+             IPv4 addresses:         256\n  \
+             IPv6 addresses:         0\n\
+             \n\
+             Unreachable prefixes:\n  \
+             10.0.1.0/24\n",
+            report(&text_args)
+        );
+
+        let mut scoped_args = args.to_vec();
+        scoped_args.extend(&["--ips", "192.168.0.0/16", "--format", "text"]);
+        assert_eq!(
+            "Unreachable under strict reject\n  \
+             Invalid announcements:  0\n  \
+             IPv4 addresses:         0\n  \
+             IPv6 addresses:         0\n",
+            report(&scoped_args)
+        );
+    }
+
+    #[test]
+    fn should_list_withdrawn_routes_if_asked() {
+        let args = [
+            "unreachables",
+            "--announcements",
+            "test/reject/riswhoisdump.IPv4",
+            "--vrps",
+            "test/reject/vrps.csv",
+            "--withdrawn",
+        ];
+
+        assert_eq!(
+            "{\"invalids\":3,\"unreachable\":\"10.0.1.0-10.0.1.255\",\
+             \"addresses_v4\":256,\"addresses_v6\":0,\
+             \"withdrawn\":[{\"prefix\":\"10.0.0.0/23\",\"origin\":\"AS65001\",\
+             \"state\":\"InvalidAsn\"}]}\n",
+            report(&args)
+        );
+
+        let mut text_args = args.to_vec();
+        text_args.extend(&["--format", "text"]);
+        assert!(report(&text_args).ends_with(
+            "Unreachable prefixes:\n  \
+             10.0.1.0/24\n\
+             \n\
+             Withdrawn routes:\n  \
+             10.0.0.0/23 AS65001 (invalid asn)\n"
+        ));
+    }
+}
@@ -0,0 +1,189 @@
+//! Validate a single prefix and origin against the VRPs, for quick ad-hoc
+//! and scripted checks. Only the VRPs are loaded, no announcements.
+use crate::announcements::Announcement;
+use crate::config;
+use crate::config::Config;
+use crate::input::Progress;
+use crate::ip::Asn;
+use crate::ip::AsnError;
+use crate::ip::IpPrefix;
+use crate::ip::IpPrefixError;
+use crate::validation::ValidatedAnnouncement;
+use crate::validation::ValidationPolicy;
+use crate::vrps;
+use crate::vrps::Vrps;
+use clap::ArgMatches;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+//------------ ValidateOpts --------------------------------------------------
+
+pub struct ValidateOpts {
+    vrps: PathBuf,
+    slurm: Option<PathBuf>,
+    announcement: Announcement,
+    policy: ValidationPolicy,
+    progress: Progress,
+}
+
+impl ValidateOpts {
+    pub fn parse(matches: &ArgMatches) -> Result<Self, Error> {
+        let config = Config::from_matches(matches)?;
+        let progress = Progress::from_matches(matches);
+        let vrps = config.vrps()?;
+        let slurm = config.optional_slurm();
+
+        let prefix = IpPrefix::from_str(matches.value_of("prefix").unwrap())?;
+        let asn = Asn::from_str(matches.value_of("asn").unwrap())?;
+        let announcement = Announcement::new(prefix, asn);
+
+        let policy = if matches.is_present("ignore_max_length") {
+            ValidationPolicy::IgnoreMaxLength
+        } else {
+            ValidationPolicy::Strict
+        };
+
+        Ok(ValidateOpts {
+            vrps,
+            slurm,
+            announcement,
+            policy,
+            progress,
+        })
+    }
+}
+
+//------------ ValidateReporter ----------------------------------------------
+
+pub struct ValidateReporter<'a> {
+    vrps: &'a Vrps,
+    policy: ValidationPolicy,
+}
+
+impl<'a> ValidateReporter<'a> {
+    pub fn new(vrps: &'a Vrps) -> Self {
+        ValidateReporter {
+            vrps,
+            policy: ValidationPolicy::default(),
+        }
+    }
+
+    /// Uses the given validation policy, instead of strict validation.
+    pub fn with_policy(mut self, policy: ValidationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn validate(&self, ann: &Announcement) -> ValidatedAnnouncement {
+        self.vrps.validate_with_policy(ann, self.policy)
+    }
+
+    pub fn execute(options: &ValidateOpts) -> Result<(), Error> {
+        Self::execute_with_output(options, &mut io::stdout())
+    }
+
+    /// Prints the validation state, e.g. "invalid asn", on a single line to
+    /// the given output.
+    pub fn execute_with_output<W: io::Write>(
+        options: &ValidateOpts,
+        output: &mut W,
+    ) -> Result<(), Error> {
+        let vrps = Vrps::load(&options.vrps, options.slurm.as_deref(), options.progress)?;
+        let validated = ValidateReporter::new(&vrps)
+            .with_policy(options.policy)
+            .validate(&options.announcement);
+        writeln!(output, "{}", validated.state())?;
+        Ok(())
+    }
+}
+
+//------------ Error --------------------------------------------------------
+
+#[derive(Debug, Display)]
+pub enum Error {
+    #[display(fmt = "{}", _0)]
+    ConfigError(config::Error),
+
+    #[display(fmt = "{}", _0)]
+    VrpsError(vrps::Error),
+
+    #[display(fmt = "{}", _0)]
+    IpPrefixError(IpPrefixError),
+
+    #[display(fmt = "{}", _0)]
+    AsnError(AsnError),
+
+    #[display(fmt = "{}", _0)]
+    IoError(io::Error),
+}
+
+impl From<config::Error> for Error {
+    fn from(e: config::Error) -> Self {
+        Error::ConfigError(e)
+    }
+}
+
+impl From<vrps::Error> for Error {
+    fn from(e: vrps::Error) -> Self {
+        Error::VrpsError(e)
+    }
+}
+
+impl From<IpPrefixError> for Error {
+    fn from(e: IpPrefixError) -> Self {
+        Error::IpPrefixError(e)
+    }
+}
+
+impl From<AsnError> for Error {
+    fn from(e: AsnError) -> Self {
+        Error::AsnError(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::IoError(e)
+    }
+}
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+
+    fn validate(args: &[&str]) -> String {
+        let matches = cli::subcommand_matches(args);
+        let opts = ValidateOpts::parse(&matches).unwrap();
+
+        let mut output = vec![];
+        ValidateReporter::execute_with_output(&opts, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn should_print_state() {
+        let state =
+            |prefix, asn| validate(&["validate", "--vrps", "test/small/vrps.csv", prefix, asn]);
+
+        assert_eq!("valid\n", state("10.0.0.0/16", "AS65000"));
+        assert_eq!("invalid asn\n", state("10.0.0.0/16", "AS65001"));
+        assert_eq!("invalid length\n", state("10.0.1.0/24", "AS65000"));
+        assert_eq!("not found\n", state("172.16.0.0/24", "AS65000"));
+
+        assert_eq!(
+            "valid\n",
+            validate(&[
+                "validate",
+                "--vrps",
+                "test/small/vrps.csv",
+                "--ignore-maxlength",
+                "10.0.1.0/24",
+                "AS65000",
+            ])
+        );
+    }
+}
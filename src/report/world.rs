@@ -1,18 +1,165 @@
 //! Reporting of the stats found
+use crate::announcements;
 use crate::announcements::Announcements;
+use crate::config;
+use crate::config::Config;
+use crate::delegations;
 use crate::delegations::IpDelegations;
+use crate::delegations::Registry;
+use crate::input::Progress;
+use crate::ip::AsnError;
+use crate::ip::AsnSet;
+use crate::ip::IpAddressFamily;
+use crate::ip::IpPrefix;
+use crate::ip::IpRange;
+use crate::ip::IpResourceSet;
 use crate::ip::IpRespourceSetError;
+use crate::report::RunSummary;
+use crate::report::ScopeLimits;
+use crate::statics;
 use crate::validation::ValidatedAnnouncement;
+use crate::validation::ValidationCache;
+use crate::validation::ValidationPolicy;
 use crate::validation::ValidationState;
 use crate::validation::VrpImpact;
+use crate::vrps;
+use crate::vrps::ValidatedRoaPayload;
 use crate::vrps::Vrps;
 use clap::ArgMatches;
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use rayon::ThreadPoolBuildError;
+use rayon::ThreadPoolBuilder;
+use serde::Serialize;
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt;
 use std::fmt::Display;
 use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use std::time::Instant;
+
+//------------ FamilyStat ---------------------------------------------------
+
+/// Route counts for a single address family.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct FamilyStat {
+    routes_valid: usize,
+    routes_inv_l: usize,
+    routes_inv_a: usize,
+    routes_not_f: usize,
+    routes_not_f_unassigned: usize,
+}
+
+impl FamilyStat {
+    fn add_ann(&mut self, ann: &ValidatedAnnouncement, assigned: bool) {
+        match ann.state() {
+            ValidationState::Valid => self.routes_valid += 1,
+            ValidationState::InvalidLength => self.routes_inv_l += 1,
+            ValidationState::InvalidAsn => self.routes_inv_a += 1,
+            ValidationState::NotFound => {
+                self.routes_not_f += 1;
+                if !assigned {
+                    self.routes_not_f_unassigned += 1;
+                }
+            }
+        }
+    }
+
+    fn merge(&mut self, other: &FamilyStat) {
+        self.routes_valid += other.routes_valid;
+        self.routes_inv_l += other.routes_inv_l;
+        self.routes_inv_a += other.routes_inv_a;
+        self.routes_not_f += other.routes_not_f;
+        self.routes_not_f_unassigned += other.routes_not_f_unassigned;
+    }
+
+    fn total(&self) -> usize {
+        self.routes_valid + self.routes_inv_l + self.routes_inv_a + self.routes_not_f
+    }
+
+    fn covered(&self) -> usize {
+        self.routes_valid + self.routes_inv_a + self.routes_inv_l
+    }
+
+    pub fn f_adoption(&self) -> f32 {
+        self.f_adoption_with(AdoptionDenominator::All)
+    }
+
+    /// Returns the adoption percentage, using the given mode to determine
+    /// which announcements are counted in the denominator.
+    pub fn f_adoption_with(&self, denominator: AdoptionDenominator) -> f32 {
+        adoption(
+            self.covered(),
+            self.total(),
+            self.routes_not_f_unassigned,
+            denominator,
+        )
+    }
+
+    pub fn f_quality(&self) -> Option<f32> {
+        if self.covered() > 0 {
+            Some((self.routes_valid * 10000 / self.covered()) as f32 / 100.)
+        } else {
+            None
+        }
+    }
+}
+
+//------------ AdoptionDenominator ------------------------------------------
+
+/// Determines which announcements are counted in the denominator when
+/// calculating adoption.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum AdoptionDenominator {
+    /// All announcements are counted.
+    #[default]
+    #[serde(rename = "all")]
+    All,
+
+    /// Not found announcements for space that is not assigned, e.g. space
+    /// that is reserved or available, are left out.
+    #[serde(rename = "assigned")]
+    AssignedOnly,
+}
+
+impl FromStr for AdoptionDenominator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(AdoptionDenominator::All),
+            "assigned" => Ok(AdoptionDenominator::AssignedOnly),
+            _ => Err(Error::WithMessage(format!(
+                "Unsupported denominator: {}. Supported are: all|assigned",
+                s
+            ))),
+        }
+    }
+}
+
+/// Returns the covered routes as a percentage of the total, leaving out the
+/// unassigned not found routes if the denominator asks for that.
+fn adoption(
+    covered: usize,
+    total: usize,
+    not_f_unassigned: usize,
+    denominator: AdoptionDenominator,
+) -> f32 {
+    let total = match denominator {
+        AdoptionDenominator::All => total,
+        AdoptionDenominator::AssignedOnly => total - not_f_unassigned,
+    };
+
+    (covered * 10000)
+        .checked_div(total)
+        .map_or(0_f32, |f| f as f32 / 100.)
+}
 
 //------------ CountryStat --------------------------------------------------
 
@@ -22,20 +169,46 @@ pub struct CountryStat {
     routes_inv_l: usize,
     routes_inv_a: usize,
     routes_not_f: usize,
+    routes_not_f_unassigned: usize,
     vrps_seen: usize,
     vrps_unseen: usize,
+    v4: FamilyStat,
+    v6: FamilyStat,
 }
 
 impl CountryStat {
-    pub fn add_ann(&mut self, ann: &ValidatedAnnouncement) {
+    /// Adds a ValidatedAnnouncement. The 'assigned' flag indicates whether
+    /// the announced space is assigned by a registry, so that it can be
+    /// excluded from adoption if desired.
+    pub fn add_ann(&mut self, ann: &ValidatedAnnouncement, assigned: bool) {
         match ann.state() {
             ValidationState::Valid => self.routes_valid += 1,
             ValidationState::InvalidLength => self.routes_inv_l += 1,
             ValidationState::InvalidAsn => self.routes_inv_a += 1,
-            ValidationState::NotFound => self.routes_not_f += 1,
+            ValidationState::NotFound => {
+                self.routes_not_f += 1;
+                if !assigned {
+                    self.routes_not_f_unassigned += 1;
+                }
+            }
+        }
+
+        match ann.announcement().prefix().ip_address_family() {
+            IpAddressFamily::Ipv4 => self.v4.add_ann(ann, assigned),
+            IpAddressFamily::Ipv6 => self.v6.add_ann(ann, assigned),
         }
     }
 
+    /// Returns the route counts for IPv4 announcements only.
+    pub fn v4(&self) -> &FamilyStat {
+        &self.v4
+    }
+
+    /// Returns the route counts for IPv6 announcements only.
+    pub fn v6(&self) -> &FamilyStat {
+        &self.v6
+    }
+
     pub fn add_impact(&mut self, impact: &VrpImpact) {
         if impact.is_unseen() {
             self.vrps_unseen += 1;
@@ -44,6 +217,19 @@ impl CountryStat {
         }
     }
 
+    /// Adds all counts from the other stat to this stat.
+    pub fn merge(&mut self, other: &CountryStat) {
+        self.routes_valid += other.routes_valid;
+        self.routes_inv_l += other.routes_inv_l;
+        self.routes_inv_a += other.routes_inv_a;
+        self.routes_not_f += other.routes_not_f;
+        self.routes_not_f_unassigned += other.routes_not_f_unassigned;
+        self.vrps_seen += other.vrps_seen;
+        self.vrps_unseen += other.vrps_unseen;
+        self.v4.merge(&other.v4);
+        self.v6.merge(&other.v6);
+    }
+
     fn total(&self) -> usize {
         self.routes_valid + self.routes_inv_l + self.routes_inv_a + self.routes_not_f
     }
@@ -53,11 +239,18 @@ impl CountryStat {
     }
 
     pub fn f_adoption(&self) -> f32 {
-        if self.total() == 0 {
-            0_f32
-        } else {
-            (self.covered() * 10000 / self.total()) as f32 / 100.
-        }
+        self.f_adoption_with(AdoptionDenominator::All)
+    }
+
+    /// Returns the adoption percentage, using the given mode to determine
+    /// which announcements are counted in the denominator.
+    pub fn f_adoption_with(&self, denominator: AdoptionDenominator) -> f32 {
+        adoption(
+            self.covered(),
+            self.total(),
+            self.routes_not_f_unassigned,
+            denominator,
+        )
     }
 
     pub fn has_adoption(&self) -> bool {
@@ -82,11 +275,9 @@ impl CountryStat {
 
     pub fn f_seen(&self) -> Option<f32> {
         let total = self.vrps_seen + self.vrps_unseen;
-        if total > 0 {
-            Some((self.vrps_seen * 10000 / total) as f32 / 100.)
-        } else {
-            None
-        }
+        (self.vrps_seen * 10000)
+            .checked_div(total)
+            .map(|f| f as f32 / 100.)
     }
 }
 
@@ -112,32 +303,59 @@ VRPS unseen: {}",
 
 //------------ CountryStats -------------------------------------------------
 
-/// This type keeps a map of country code to CountryStat.
-#[derive(Clone, Debug, Serialize)]
+/// This type keeps a map of country code to CountryStat. The denominator
+/// determines how adoption is rendered in the CSV, GeoJSON and HTML output.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
 pub struct CountryStats {
     stats: HashMap<String, CountryStat>,
+    denominator: AdoptionDenominator,
 }
 
 impl Default for CountryStats {
     fn default() -> Self {
         let mut stats = HashMap::new();
         stats.insert("all".to_string(), CountryStat::default());
-        CountryStats { stats }
+        CountryStats {
+            stats,
+            denominator: AdoptionDenominator::default(),
+        }
     }
 }
 
 impl CountryStats {
+    /// Uses the given denominator when rendering adoption.
+    pub fn with_denominator(mut self, denominator: AdoptionDenominator) -> Self {
+        self.denominator = denominator;
+        self
+    }
+
+    pub fn denominator(&self) -> AdoptionDenominator {
+        self.denominator
+    }
+
     fn get_cc(&mut self, cc: &str) -> &mut CountryStat {
-        self.stats
-            .entry(cc.to_string())
-            .or_insert_with(CountryStat::default)
+        self.stats.entry(cc.to_string()).or_default()
+    }
+
+    /// Adds all counts from the other stats to these stats, per country
+    /// code, including 'all'. This can combine stats computed separately,
+    /// e.g. per thread or per announcements file.
+    pub fn merge(&mut self, other: &CountryStats) {
+        for (cc, stat) in other.stats.iter() {
+            self.get_cc(cc).merge(stat);
+        }
+    }
+
+    /// Summarises the totals for all countries.
+    pub fn run_summary(&self, elapsed: Duration) -> RunSummary {
+        run_summary(self.stats.get("all"), elapsed)
     }
 
     /// Adds a ValidatedAnnouncement to the stats for the given country code.
     /// Also adds this to the overall 'all' countries category.
-    pub fn add_ann(&mut self, ann: &ValidatedAnnouncement, cc: &str) {
-        self.get_cc(cc).add_ann(ann);
-        self.get_cc("all").add_ann(ann);
+    pub fn add_ann(&mut self, ann: &ValidatedAnnouncement, cc: &str, assigned: bool) {
+        self.get_cc(cc).add_ann(ann, assigned);
+        self.get_cc("all").add_ann(ann, assigned);
     }
 
     /// Adds a ValidatedAnnouncement to the stats for the given country code.
@@ -155,7 +373,13 @@ impl CountryStats {
         for cc in self.stats.keys() {
             let cs = &self.stats[&cc.to_string()];
             if cc != "all" {
-                writeln!(&mut s, "          ['{}', {}],", cc, cs.f_adoption()).unwrap();
+                writeln!(
+                    &mut s,
+                    "          ['{}', {}],",
+                    cc,
+                    cs.f_adoption_with(self.denominator)
+                )
+                .unwrap();
             }
         }
         s
@@ -206,7 +430,7 @@ impl CountryStats {
         s
     }
 
-    fn get_sorted_countries(&self) -> Vec<CountryStatWithCode> {
+    fn get_sorted_countries(&self) -> Vec<CountryStatWithCode<'_>> {
         let mut countries: Vec<CountryStatWithCode> = vec![];
 
         for (cc, stat) in self.stats.iter() {
@@ -221,20 +445,166 @@ impl CountryStats {
 
     pub fn to_csv(&self) -> String {
         let mut s = String::new();
-        writeln!(s, "iso2,coverage,accuracy,seen").unwrap();
+        writeln!(
+            s,
+            "iso2,coverage,accuracy,seen,coverage_v4,accuracy_v4,coverage_v6,accuracy_v6"
+        )
+        .unwrap();
 
         let countries = self.get_sorted_countries();
 
         for country in countries {
-            let coverage = country.stat.f_adoption();
-            let accuracy = country.stat.f_quality().unwrap_or(0.);
-            let seen = country.stat.f_seen().unwrap_or(0.);
+            let stat = country.stat;
+            let coverage = stat.f_adoption_with(self.denominator);
+            let accuracy = stat.f_quality().unwrap_or(0.);
+            let seen = stat.f_seen().unwrap_or(0.);
+            let coverage_v4 = stat.v4.f_adoption_with(self.denominator);
+            let accuracy_v4 = stat.v4.f_quality().unwrap_or(0.);
+            let coverage_v6 = stat.v6.f_adoption_with(self.denominator);
+            let accuracy_v6 = stat.v6.f_quality().unwrap_or(0.);
+
+            if stat.has_adoption() {
+                writeln!(
+                    s,
+                    "{},{},{},{},{},{},{},{}",
+                    country.cc,
+                    coverage,
+                    accuracy,
+                    seen,
+                    coverage_v4,
+                    accuracy_v4,
+                    coverage_v6,
+                    accuracy_v6
+                )
+                .unwrap();
+            }
+        }
+
+        s
+    }
+
+    /// Returns the stats as tab separated rows, without a header, so that
+    /// they can be loaded into a database as they are, e.g. with COPY. Each
+    /// row has one metric, with the columns:
+    ///
+    ///   iso2, family, metric, value
+    ///
+    /// The family is 'all', 'ipv4' or 'ipv6'. The metrics are the route
+    /// counts 'valid', 'invalid_length', 'invalid_asn' and 'not_found' for
+    /// each family, and 'vrps_seen' and 'vrps_unseen' for family 'all'. The
+    /// overall totals come first with iso2 'all', then the countries sorted
+    /// by code.
+    pub fn to_tsv(&self) -> String {
+        let mut s = String::new();
 
-            if country.stat.has_adoption() {
-                writeln!(s, "{},{},{},{}", country.cc, coverage, accuracy, seen).unwrap();
+        let mut countries = vec![("all", &self.stats["all"])];
+        countries.extend(
+            self.get_sorted_countries()
+                .into_iter()
+                .map(|country| (country.cc, country.stat)),
+        );
+
+        for (cc, stat) in countries {
+            let families = [
+                (
+                    "all",
+                    stat.routes_valid,
+                    stat.routes_inv_l,
+                    stat.routes_inv_a,
+                    stat.routes_not_f,
+                ),
+                (
+                    "ipv4",
+                    stat.v4.routes_valid,
+                    stat.v4.routes_inv_l,
+                    stat.v4.routes_inv_a,
+                    stat.v4.routes_not_f,
+                ),
+                (
+                    "ipv6",
+                    stat.v6.routes_valid,
+                    stat.v6.routes_inv_l,
+                    stat.v6.routes_inv_a,
+                    stat.v6.routes_not_f,
+                ),
+            ];
+            for (family, valid, inv_l, inv_a, not_f) in &families {
+                writeln!(s, "{}\t{}\tvalid\t{}", cc, family, valid).unwrap();
+                writeln!(s, "{}\t{}\tinvalid_length\t{}", cc, family, inv_l).unwrap();
+                writeln!(s, "{}\t{}\tinvalid_asn\t{}", cc, family, inv_a).unwrap();
+                writeln!(s, "{}\t{}\tnot_found\t{}", cc, family, not_f).unwrap();
             }
+            writeln!(s, "{}\tall\tvrps_seen\t{}", cc, stat.vrps_seen).unwrap();
+            writeln!(s, "{}\tall\tvrps_unseen\t{}", cc, stat.vrps_unseen).unwrap();
+        }
+
+        s
+    }
+
+    /// Returns the stats as a GeoJSON FeatureCollection with one feature per
+    /// country. The features have no geometry, so mapping tools need to join
+    /// them to their own country shapes, using the 'iso2' property.
+    pub fn to_geojson(&self) -> GeoJson {
+        let features = self
+            .get_sorted_countries()
+            .into_iter()
+            .map(|country| GeoJsonFeature::for_country(country.cc, country.stat, self.denominator))
+            .collect();
+
+        GeoJson {
+            kind: "FeatureCollection",
+            features,
         }
+    }
+
+    /// Returns the route and VRP counts as gauges in the Prometheus text
+    /// format, labelled with the lower case country code. The overall totals
+    /// come first with cc="all", then the countries sorted by code.
+    pub fn to_prometheus(&self) -> String {
+        let metrics = [
+            ("routes_valid", "Valid announcements"),
+            (
+                "routes_invalid_length",
+                "Announcements with an invalid length",
+            ),
+            ("routes_invalid_asn", "Announcements with an invalid ASN"),
+            ("routes_not_found", "Announcements not covered by any VRP"),
+            ("vrps_seen", "VRPs that validate at least one announcement"),
+            ("vrps_unseen", "VRPs that validate no announcements"),
+        ];
+        let values = |stat: &CountryStat| {
+            [
+                stat.routes_valid,
+                stat.routes_inv_l,
+                stat.routes_inv_a,
+                stat.routes_not_f,
+                stat.vrps_seen,
+                stat.vrps_unseen,
+            ]
+        };
 
+        let mut countries = vec![("all", &self.stats["all"])];
+        countries.extend(
+            self.get_sorted_countries()
+                .into_iter()
+                .map(|country| (country.cc, country.stat)),
+        );
+
+        let mut s = String::new();
+        for (i, (name, help)) in metrics.iter().enumerate() {
+            writeln!(s, "# HELP rpki_stats_{} {}", name, help).unwrap();
+            writeln!(s, "# TYPE rpki_stats_{} gauge", name).unwrap();
+            for (cc, stat) in &countries {
+                writeln!(
+                    s,
+                    "rpki_stats_{}{{cc=\"{}\"}} {}",
+                    name,
+                    cc.to_lowercase(),
+                    values(stat)[i]
+                )
+                .unwrap();
+            }
+        }
         s
     }
 }
@@ -255,15 +625,57 @@ impl Display for CountryStats {
     }
 }
 
-#[derive(Eq, PartialEq)]
+/// Summarises the overall totals, if there are any.
+fn run_summary(all: Option<&CountryStat>, elapsed: Duration) -> RunSummary {
+    match all {
+        Some(all) => RunSummary::new(
+            all.total(),
+            all.routes_inv_a + all.routes_inv_l,
+            all.vrps_seen + all.vrps_unseen,
+            all.vrps_unseen,
+            elapsed,
+        ),
+        None => RunSummary::new(0, 0, 0, 0, elapsed),
+    }
+}
+
 struct CountryStatWithCode<'a> {
     cc: &'a str,
     stat: &'a CountryStat,
 }
 
+impl<'a> CountryStatWithCode<'a> {
+    /// The key used to order entries with the same code, so that the order
+    /// does not depend on the order of the underlying map. Larger totals
+    /// go first.
+    fn tie_breaker(&self) -> (usize, usize, usize, usize, usize, usize, usize) {
+        let stat = self.stat;
+        (
+            stat.total(),
+            stat.covered(),
+            stat.routes_valid,
+            stat.routes_inv_l,
+            stat.routes_not_f_unassigned,
+            stat.vrps_seen,
+            stat.vrps_unseen,
+        )
+    }
+}
+
+impl<'a> PartialEq for CountryStatWithCode<'a> {
+    /// Compares the same key as Ord, so that equality agrees with the order.
+    fn eq(&self, other: &Self) -> bool {
+        self.cc == other.cc && self.tie_breaker() == other.tie_breaker()
+    }
+}
+
+impl<'a> Eq for CountryStatWithCode<'a> {}
+
 impl<'a> Ord for CountryStatWithCode<'a> {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.cc.cmp(other.cc)
+        self.cc
+            .cmp(other.cc)
+            .then_with(|| other.tie_breaker().cmp(&self.tie_breaker()))
     }
 }
 
@@ -273,37 +685,270 @@ impl<'a> PartialOrd for CountryStatWithCode<'a> {
     }
 }
 
+//------------ RegistryStats ------------------------------------------------
+
+/// The same counts as CountryStats, but keyed by the name of the registry
+/// that delegated the space, e.g. 'ripencc'. Space that is not found in the
+/// delegations is counted under 'unknown'.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct RegistryStats {
+    stats: HashMap<String, CountryStat>,
+    denominator: AdoptionDenominator,
+}
+
+/// The key for space that is not delegated by any registry.
+pub const UNKNOWN_REGISTRY: &str = "unknown";
+
+impl RegistryStats {
+    /// Returns the stats for the registry, or 'all' for the totals.
+    pub fn get(&self, registry: &str) -> Option<&CountryStat> {
+        self.stats.get(registry)
+    }
+
+    pub fn denominator(&self) -> AdoptionDenominator {
+        self.denominator
+    }
+
+    /// Summarises the totals for all registries.
+    pub fn run_summary(&self, elapsed: Duration) -> RunSummary {
+        run_summary(self.stats.get("all"), elapsed)
+    }
+
+    fn sorted_registries(&self) -> Vec<(&str, &CountryStat)> {
+        let mut registries: Vec<(&str, &CountryStat)> = self
+            .stats
+            .iter()
+            .filter(|(reg, _)| reg.as_str() != "all")
+            .map(|(reg, stat)| (reg.as_str(), stat))
+            .collect();
+        registries.sort_by_key(|(reg, _)| *reg);
+        registries
+    }
+}
+
+impl From<CountryStats> for RegistryStats {
+    /// Uses CountryStats that were keyed by registry, rather than country.
+    fn from(stats: CountryStats) -> Self {
+        RegistryStats {
+            stats: stats.stats,
+            denominator: stats.denominator,
+        }
+    }
+}
+
+impl Display for RegistryStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Overall")?;
+        writeln!(f, "  {}", &self.stats["all"])?;
+        writeln!(f)?;
+        writeln!(f, "Per RIR:")?;
+
+        for (registry, stat) in self.sorted_registries() {
+            writeln!(f, "{}: {}", registry, stat)?;
+        }
+
+        Ok(())
+    }
+}
+
+//------------ GeoJson ------------------------------------------------------
+
+/// A GeoJSON FeatureCollection, see RFC 7946.
+#[derive(Clone, Debug, Serialize)]
+pub struct GeoJson {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    features: Vec<GeoJsonFeature>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct GeoJsonFeature {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    geometry: Option<()>,
+    properties: GeoJsonProperties,
+}
+
+impl GeoJsonFeature {
+    fn for_country(cc: &str, stat: &CountryStat, denominator: AdoptionDenominator) -> Self {
+        let properties = GeoJsonProperties {
+            iso2: cc.to_string(),
+            coverage: stat.f_adoption_with(denominator),
+            accuracy: stat.f_quality().unwrap_or(0.),
+            seen: stat.f_seen().unwrap_or(0.),
+            coverage_v4: stat.v4.f_adoption_with(denominator),
+            accuracy_v4: stat.v4.f_quality().unwrap_or(0.),
+            coverage_v6: stat.v6.f_adoption_with(denominator),
+            accuracy_v6: stat.v6.f_quality().unwrap_or(0.),
+        };
+
+        GeoJsonFeature {
+            kind: "Feature",
+            geometry: None,
+            properties,
+        }
+    }
+}
+
+/// Uses the same values as the CSV export.
+#[derive(Clone, Debug, Serialize)]
+pub struct GeoJsonProperties {
+    iso2: String,
+    coverage: f32,
+    accuracy: f32,
+    seen: f32,
+    coverage_v4: f32,
+    accuracy_v4: f32,
+    coverage_v6: f32,
+    accuracy_v6: f32,
+}
+
+//------------ NotFoundSpace ------------------------------------------------
+
+/// Announced space that is not covered by any VRP, as prefixes per country.
+/// Prefixes that are covered by a less specific not found announcement in
+/// the same country are left out.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct NotFoundSpace {
+    countries: HashMap<String, Vec<IpPrefix>>,
+}
+
+impl NotFoundSpace {
+    fn add(&mut self, prefix: &IpPrefix, cc: &str) {
+        self.countries
+            .entry(cc.to_string())
+            .or_default()
+            .push(prefix.clone());
+    }
+
+    fn normalise(&mut self) {
+        for prefixes in self.countries.values_mut() {
+            // Sort on start address, with less specifics before the more
+            // specifics they contain.
+            prefixes.sort_by(|a, b| {
+                let a = a.as_ref().to_range();
+                let b = b.as_ref().to_range();
+                a.start.cmp(&b.start).then(b.end.cmp(&a.end))
+            });
+
+            let mut kept: Vec<IpPrefix> = vec![];
+            for prefix in prefixes.drain(..) {
+                let covered = match kept.last() {
+                    Some(last) => last.as_ref().contains(&prefix.as_ref().to_range()),
+                    None => false,
+                };
+                if !covered {
+                    kept.push(prefix);
+                }
+            }
+            *prefixes = kept;
+        }
+    }
+
+    /// Returns the not found prefixes for the country, if any.
+    pub fn prefixes(&self, cc: &str) -> Option<&Vec<IpPrefix>> {
+        self.countries.get(cc)
+    }
+}
+
+//------------ CrossRegistryVrp ---------------------------------------------
+
+/// A VRP that covers space delegated by more than one RIR.
+#[derive(Clone, Debug, Serialize)]
+pub struct CrossRegistryVrp {
+    vrp: ValidatedRoaPayload,
+    registries: Vec<Registry>,
+}
+
 //------------ WorldStatsOpts -----------------------------------------------
 
 /// Options for the WorldStatsReport
 pub struct WorldStatsOpts {
     announcements: Vec<PathBuf>,
+    min_peers: u32,
     vrps: PathBuf,
+    slurm: Option<PathBuf>,
     dels: PathBuf,
+    scope: ScopeLimits,
+    threads: Option<usize>,
+    deterministic: bool,
+    stats: bool,
+    policy: ValidationPolicy,
+    denominator: AdoptionDenominator,
+    exclude_dark_vrps: bool,
+    by: WorldStatsBy,
     format: WorldStatsFormat,
+    html_dir: Option<PathBuf>,
+    progress: Progress,
 }
 
 impl WorldStatsOpts {
+    pub fn scope(&self) -> &ScopeLimits {
+        &self.scope
+    }
+
     pub fn parse(matches: &ArgMatches) -> Result<Self, Error> {
-        let mut announcements = vec![];
-        for name in matches.values_of("announcements").unwrap().into_iter() {
-            announcements.push(PathBuf::from(name))
-        }
+        let config = Config::from_matches(matches)?;
+        let progress = Progress::from_matches(matches);
+        let announcements = config.announcements()?;
+        let min_peers = config.min_peers();
+        let vrps = config.vrps()?;
+        let slurm = config.optional_slurm();
+        let dels = config.delegations()?;
+        let denominator = config.denominator();
+
+        let ips = {
+            if let Some(ips) = matches.value_of("ips") {
+                IpResourceSet::from_str(ips)?
+            } else {
+                IpResourceSet::empty()
+            }
+        };
 
-        let vrps_file = matches.value_of("vrps").unwrap();
-        let vrps = PathBuf::from(vrps_file);
+        let asns = {
+            if let Some(asns) = matches.value_of("asns") {
+                AsnSet::from_str(asns)?
+            } else {
+                AsnSet::empty()
+            }
+        };
 
-        let dels_file = matches.value_of("delegations").unwrap();
-        let dels = PathBuf::from(dels_file);
+        let scope = ScopeLimits::new(ips, asns);
+
+        let threads = match matches.value_of("threads") {
+            Some(threads) => match usize::from_str(threads) {
+                Ok(threads) if threads > 0 => Some(threads),
+                _ => {
+                    return Err(Error::WithMessage(format!(
+                        "Invalid number of threads: {}",
+                        threads
+                    )))
+                }
+            },
+            None => None,
+        };
+
+        let deterministic = matches.is_present("deterministic");
+        let stats = matches.is_present("stats");
+        let exclude_dark_vrps = matches.is_present("exclude_dark_vrps");
+        let policy = if matches.is_present("ignore_max_length") {
+            ValidationPolicy::IgnoreMaxLength
+        } else {
+            ValidationPolicy::Strict
+        };
 
         let format = {
             if let Some(format) = matches.value_of("format") {
                 match format {
                     "json" => WorldStatsFormat::Json,
                     "text" => WorldStatsFormat::Text,
+                    "not-found" => WorldStatsFormat::NotFound,
+                    "geojson" => WorldStatsFormat::GeoJson,
+                    "cross-rir" => WorldStatsFormat::CrossRir,
+                    "tsv" => WorldStatsFormat::Tsv,
                     f => {
                         return Err(Error::WithMessage(format!(
-                            "Unsupported format: {}. Supported are: json|html|text",
+                            "Unsupported format: {}. Supported are: json|text|not-found|geojson|cross-rir|tsv",
                             f
                         )))
                     }
@@ -313,21 +958,81 @@ impl WorldStatsOpts {
             }
         };
 
+        let html_dir = matches.value_of("html_dir").map(PathBuf::from);
+
+        let by = match matches.value_of("by") {
+            None | Some("country") => WorldStatsBy::Country,
+            Some("rir") => WorldStatsBy::Registry,
+            Some(by) => {
+                return Err(Error::WithMessage(format!(
+                    "Unsupported grouping: {}. Supported are: country|rir",
+                    by
+                )))
+            }
+        };
+
+        if by == WorldStatsBy::Registry {
+            match format {
+                WorldStatsFormat::Json | WorldStatsFormat::Text if html_dir.is_none() => {}
+                _ => {
+                    return Err(Error::msg(
+                        "The stats per RIR are only supported in json and text format",
+                    ))
+                }
+            }
+        }
+
         Ok(WorldStatsOpts {
             announcements,
+            min_peers,
             vrps,
+            slurm,
             dels,
+            scope,
+            threads,
+            deterministic,
+            stats,
+            policy,
+            denominator,
+            exclude_dark_vrps,
+            by,
             format,
+            html_dir,
+            progress,
         })
     }
 }
 
+//------------ WorldStatsBy --------------------------------------------------
+
+/// How the stats are grouped.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WorldStatsBy {
+    /// Per country code, see CountryStats.
+    Country,
+
+    /// Per registry, see RegistryStats.
+    Registry,
+}
+
 //------------ WorldStatsFormat ----------------------------------------------
 
 /// Output format. The HTML uses the template in ['templates/world.html'].
 pub enum WorldStatsFormat {
     Json,
     Text,
+
+    /// JSON map of country code to not found announced prefixes.
+    NotFound,
+
+    /// GeoJSON FeatureCollection with the stats per country as properties.
+    GeoJson,
+
+    /// JSON list of VRPs that span space delegated by more than one RIR.
+    CrossRir,
+
+    /// Tab separated rows with one metric each, see CountryStats::to_tsv.
+    Tsv,
 }
 
 //------------ WorldStatsReporter --------------------------------------------
@@ -338,6 +1043,9 @@ pub struct WorldStatsReporter<'a> {
     announcements: &'a Announcements,
     vrps: &'a Vrps,
     delegations: &'a IpDelegations,
+    policy: ValidationPolicy,
+    denominator: AdoptionDenominator,
+    exclude_dark_vrps: bool,
 }
 
 impl<'a> WorldStatsReporter<'a> {
@@ -350,56 +1058,291 @@ impl<'a> WorldStatsReporter<'a> {
             announcements,
             vrps,
             delegations,
+            policy: ValidationPolicy::default(),
+            denominator: AdoptionDenominator::default(),
+            exclude_dark_vrps: false,
         }
     }
 
-    pub fn analyse(&self) -> CountryStats {
-        let mut country_stats = CountryStats::default();
+    /// Uses the given validation policy, instead of strict validation.
+    pub fn with_policy(mut self, policy: ValidationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Uses the given denominator for the adoption in the analysed stats,
+    /// instead of counting all announcements.
+    pub fn with_denominator(mut self, denominator: AdoptionDenominator) -> Self {
+        self.denominator = denominator;
+        self
+    }
+
+    /// Leaves out VRPs for dark space, i.e. space without any announcement,
+    /// from the seen and unseen VRP counts. Such VRPs cannot be seen either
+    /// way, so that the remaining unseen VRPs are those for announced space
+    /// that do not match the announcements.
+    pub fn with_exclude_dark_vrps(mut self, exclude: bool) -> Self {
+        self.exclude_dark_vrps = exclude;
+        self
+    }
 
-        for ann in self.announcements.all() {
-            let matching_roas = self.vrps.containing(ann.as_ref());
-            let validated = ValidatedAnnouncement::create(ann, &matching_roas);
-            let cc = self.delegations.find_cc(ann.as_ref());
+    fn is_dark(&self, vrp: &ValidatedRoaPayload) -> bool {
+        self.announcements.intersecting(vrp.as_ref()).is_empty()
+    }
 
-            country_stats.add_ann(&validated, cc);
-        }
+    /// Analyses all announcements and VRPs in the given scope. Use an empty
+    /// scope to include everything.
+    ///
+    /// Announcements are validated, and VRPs are evaluated, in parallel
+    /// using the current rayon thread pool. See 'thread_pool' to limit the
+    /// number of threads.
+    pub fn analyse(&self, scope: &ScopeLimits) -> CountryStats {
+        let delegations = self.delegations;
+        self.analyse_by(scope, |range| delegations.find_cc(range))
+    }
+
+    /// Like 'analyse', but counts per registry instead of per country.
+    pub fn analyse_by_registry(&self, scope: &ScopeLimits) -> RegistryStats {
+        let delegations = self.delegations;
+        let stats = self.analyse_by(scope, |range| {
+            delegations
+                .find_registry(range)
+                .map_or(UNKNOWN_REGISTRY, |registry| registry.name())
+        });
+        RegistryStats::from(stats)
+    }
+
+    /// Analyses the announcements and VRPs in scope, and counts them under
+    /// the key that the given function returns for their prefix.
+    fn analyse_by<F>(&self, scope: &ScopeLimits, key: F) -> CountryStats
+    where
+        F: Fn(&IpRange) -> &'a str + Sync,
+    {
+        let mut country_stats = self
+            .announcements
+            .iter_in_scope(scope)
+            .par_bridge()
+            .fold(
+                || {
+                    (
+                        CountryStats::default(),
+                        ValidationCache::with_policy(self.policy),
+                    )
+                },
+                |(mut stats, mut cache), ann| {
+                    let validated = cache.validate(ann, || self.vrps.containing(ann.as_ref()));
+                    let cc = key(ann.as_ref());
+                    let assigned = self.delegations.is_assigned(ann.as_ref());
+
+                    stats.add_ann(&validated, cc, assigned);
+                    (stats, cache)
+                },
+            )
+            .map(|(stats, _)| stats)
+            .reduce(CountryStats::default, |mut stats, other| {
+                stats.merge(&other);
+                stats
+            });
+
+        let impact_stats = self
+            .vrps
+            .iter_in_scope(scope)
+            .par_bridge()
+            .filter(|vrp| !self.exclude_dark_vrps || !self.is_dark(vrp))
+            .fold(CountryStats::default, |mut stats, vrp| {
+                let anns = self.announcements.contained_by(vrp.as_ref());
+
+                let impact = VrpImpact::evaluate_with_policy(vrp, &anns, self.policy);
+                let cc = key(vrp.as_ref());
+
+                stats.add_impact(&impact, cc);
+                stats
+            })
+            .reduce(CountryStats::default, |mut stats, other| {
+                stats.merge(&other);
+                stats
+            });
+
+        country_stats.merge(&impact_stats);
+        country_stats.with_denominator(self.denominator)
+    }
+
+    /// Finds the announced space in the given scope that is not covered by
+    /// any VRP, per country.
+    pub fn not_found_space(&self, scope: &ScopeLimits) -> NotFoundSpace {
+        let mut space = NotFoundSpace::default();
+        let mut cache = ValidationCache::with_policy(self.policy);
 
-        for vrp in self.vrps.all() {
-            let anns = self.announcements.contained_by(vrp.as_ref());
+        for ann in self.announcements.in_scope(scope) {
+            let validated = cache.validate(ann, || self.vrps.containing(ann.as_ref()));
 
-            let impact = VrpImpact::evaluate(vrp, &anns);
-            let cc = self.delegations.find_cc(vrp.as_ref());
+            if validated.state() == &ValidationState::NotFound {
+                let cc = self.delegations.find_cc(ann.as_ref());
+                space.add(ann.prefix(), cc);
+            }
+        }
+
+        space.normalise();
+        space
+    }
 
-            country_stats.add_impact(&impact, cc);
+    /// Finds the VRPs in the given scope that cover space delegated by more
+    /// than one RIR. ROAs are issued under a single RIR, so this is a sign
+    /// of a misaligned ROA, or of stale delegation data.
+    pub fn cross_registry_vrps(&self, scope: &ScopeLimits) -> Vec<CrossRegistryVrp> {
+        let mut res = vec![];
+
+        for vrp in self.vrps.in_scope(scope) {
+            let registries = self.delegations.registries(vrp.as_ref());
+            if registries.len() > 1 {
+                res.push(CrossRegistryVrp {
+                    vrp: vrp.clone(),
+                    registries,
+                });
+            }
         }
 
-        country_stats
+        res
     }
 
     pub fn execute(options: &WorldStatsOpts) -> Result<(), Error> {
-        let announcements = Announcements::from_ris(&options.announcements).unwrap();
+        Self::execute_with_summary(options, &mut io::stderr())
+    }
 
-        let vrps = Vrps::from_file(&options.vrps).unwrap();
+    /// Like 'execute', but writes the summary asked for with --stats to the
+    /// given writer, rather than to stderr.
+    pub fn execute_with_summary<W: io::Write>(
+        options: &WorldStatsOpts,
+        summary: &mut W,
+    ) -> Result<(), Error> {
+        let start = Instant::now();
+
+        let announcements = Announcements::from_ris_with_progress(
+            &options.announcements,
+            options.min_peers,
+            options.progress,
+        )?;
+
+        let vrps = Vrps::load(&options.vrps, options.slurm.as_deref(), options.progress)?;
+
+        let delegations = IpDelegations::from_file_with_progress(&options.dels, options.progress)?;
+
+        let reporter = WorldStatsReporter::new(&announcements, &vrps, &delegations)
+            .with_policy(options.policy)
+            .with_denominator(options.denominator)
+            .with_exclude_dark_vrps(options.exclude_dark_vrps);
+
+        let pool = Self::thread_pool(options.threads)?;
+
+        // The formats based on the country or registry stats return their
+        // summary, so that it can be shown with --stats.
+        let run_summary = if let Some(dir) = &options.html_dir {
+            let stats = pool.install(|| reporter.analyse(options.scope()));
+            Self::html_dir(&stats, dir)?;
+            Some(stats.run_summary(start.elapsed()))
+        } else if options.by == WorldStatsBy::Registry {
+            let stats = pool.install(|| reporter.analyse_by_registry(options.scope()));
+            match options.format {
+                WorldStatsFormat::Text => println!("{}", stats),
+                _ => Self::json(&stats, options.deterministic)?,
+            }
+            Some(stats.run_summary(start.elapsed()))
+        } else {
+            pool.install(|| match options.format {
+                WorldStatsFormat::Json => {
+                    let stats = reporter.analyse(options.scope());
+                    Self::json(&stats, options.deterministic)?;
+                    Ok(Some(stats))
+                }
+                WorldStatsFormat::Text => {
+                    let stats = reporter.analyse(options.scope());
+                    Self::text(&stats);
+                    Ok(Some(stats))
+                }
+                WorldStatsFormat::GeoJson => {
+                    let stats = reporter.analyse(options.scope());
+                    Self::json(&stats.to_geojson(), options.deterministic)?;
+                    Ok(Some(stats))
+                }
+                WorldStatsFormat::Tsv => {
+                    let stats = reporter.analyse(options.scope());
+                    print!("{}", stats.to_tsv());
+                    Ok(Some(stats))
+                }
+                WorldStatsFormat::CrossRir => {
+                    let vrps = reporter.cross_registry_vrps(options.scope());
+                    Self::json(&vrps, options.deterministic).map(|_| None)
+                }
+                WorldStatsFormat::NotFound => {
+                    let space = reporter.not_found_space(options.scope());
+                    Self::json(&space, options.deterministic).map(|_| None)
+                }
+            })?
+            .map(|stats: CountryStats| stats.run_summary(start.elapsed()))
+        };
 
-        let delegations = IpDelegations::from_file(&options.dels).unwrap();
+        match run_summary {
+            Some(run_summary) if options.stats => writeln!(summary, "{}", run_summary)
+                .map_err(|e| Error::WithMessage(format!("Cannot write summary: {}", e))),
+            _ => Ok(()),
+        }
+    }
 
-        let reporter = WorldStatsReporter::new(&announcements, &vrps, &delegations);
+    /// Creates a thread pool to run the analysis in. Uses as many threads as
+    /// there are CPUs, unless a number of threads is given.
+    pub fn thread_pool(threads: Option<usize>) -> Result<ThreadPool, Error> {
+        let mut builder = ThreadPoolBuilder::new();
+        if let Some(threads) = threads {
+            builder = builder.num_threads(threads);
+        }
+        Ok(builder.build()?)
+    }
 
-        let stats = reporter.analyse();
+    /// Writes the world map as 'index.html' to the directory, with the stats
+    /// as 'world.csv' and the ui assets it uses, so that it can be opened
+    /// without running the daemon. Note that some browsers do not allow
+    /// pages opened from disk to load 'world.csv'. In that case, serve the
+    /// directory with any static web server. Looking up details for a scope
+    /// does need the daemon.
+    pub fn html_dir(stats: &CountryStats, dir: &Path) -> Result<(), Error> {
+        let write = |path: &Path, content: &[u8]| -> Result<(), Error> {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| Error::cannot_write(parent, e))?;
+            }
+            fs::write(path, content).map_err(|e| Error::cannot_write(path, e))
+        };
 
-        match options.format {
-            WorldStatsFormat::Json => Self::json(&stats)?,
-            WorldStatsFormat::Text => Self::text(&stats),
+        for content in statics::UI {
+            if content.path == "world.html" {
+                let html = String::from_utf8_lossy(content.content)
+                    .replace("/ui/", "ui/")
+                    .replace("/rpki-stats-api/world.csv", "world.csv");
+                write(&dir.join("index.html"), html.as_bytes())?;
+            } else {
+                write(&dir.join("ui").join(content.path), content.content)?;
+            }
         }
 
-        Ok(())
+        write(&dir.join("world.csv"), stats.to_csv().as_bytes())
     }
 
-    fn json(stats: &CountryStats) -> Result<(), Error> {
-        println!("{}", serde_json::to_string(stats)?);
+    fn json<O: Serialize>(obj: &O, deterministic: bool) -> Result<(), Error> {
+        println!("{}", Self::to_json(obj, deterministic)?);
         Ok(())
     }
 
+    /// Serializes the object to JSON. The stats are kept in HashMaps, so the
+    /// order of keys differs between runs. If 'deterministic' is set, the
+    /// object is converted to a serde_json::Value first, which keeps object
+    /// keys sorted, so that the same input always gives the same output.
+    pub fn to_json<O: Serialize>(obj: &O, deterministic: bool) -> Result<String, Error> {
+        if deterministic {
+            Ok(serde_json::to_string(&serde_json::to_value(obj)?)?)
+        } else {
+            Ok(serde_json::to_string(obj)?)
+        }
+    }
+
     fn text(stats: &CountryStats) {
         println!("{}", stats);
     }
@@ -415,14 +1358,39 @@ pub enum Error {
     #[display(fmt = "{}", _0)]
     IpResourceSet(IpRespourceSetError),
 
+    #[display(fmt = "{}", _0)]
+    AsnError(AsnError),
+
     #[display(fmt = "{}", _0)]
     JsonError(serde_json::Error),
+
+    #[display(fmt = "Cannot create thread pool: {}", _0)]
+    ThreadPoolError(ThreadPoolBuildError),
+
+    #[display(fmt = "{}", _0)]
+    ConfigError(config::Error),
+
+    #[display(fmt = "{}", _0)]
+    AnnouncementsError(announcements::Error),
+
+    #[display(fmt = "{}", _0)]
+    VrpsError(vrps::Error),
+
+    #[display(fmt = "{}", _0)]
+    DelegationsError(delegations::Error),
+
+    #[display(fmt = "Cannot write: {}", _0)]
+    CannotWrite(String),
 }
 
 impl Error {
     pub fn msg(s: &str) -> Self {
         Error::WithMessage(s.to_string())
     }
+
+    fn cannot_write(path: &Path, e: impl Display) -> Self {
+        Error::CannotWrite(format!("{}: {}", path.to_string_lossy(), e))
+    }
 }
 
 impl From<IpRespourceSetError> for Error {
@@ -431,8 +1399,613 @@ impl From<IpRespourceSetError> for Error {
     }
 }
 
+impl From<AsnError> for Error {
+    fn from(e: AsnError) -> Self {
+        Error::AsnError(e)
+    }
+}
+
 impl From<serde_json::Error> for Error {
     fn from(e: serde_json::Error) -> Self {
         Error::JsonError(e)
     }
 }
+
+impl From<config::Error> for Error {
+    fn from(e: config::Error) -> Self {
+        Error::ConfigError(e)
+    }
+}
+
+impl From<announcements::Error> for Error {
+    fn from(e: announcements::Error) -> Self {
+        Error::AnnouncementsError(e)
+    }
+}
+
+impl From<vrps::Error> for Error {
+    fn from(e: vrps::Error) -> Self {
+        Error::VrpsError(e)
+    }
+}
+
+impl From<delegations::Error> for Error {
+    fn from(e: delegations::Error) -> Self {
+        Error::DelegationsError(e)
+    }
+}
+
+impl From<ThreadPoolBuildError> for Error {
+    fn from(e: ThreadPoolBuildError) -> Self {
+        Error::ThreadPoolError(e)
+    }
+}
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+
+    fn small_stats() -> CountryStats {
+        small_stats_in_scope(&ScopeLimits::empty())
+    }
+
+    fn small_stats_in_scope(scope: &ScopeLimits) -> CountryStats {
+        with_small_reporter(|reporter| reporter.analyse(scope))
+    }
+
+    #[test]
+    fn deterministic_json_should_be_identical_across_runs() {
+        let run = || {
+            with_small_reporter(|reporter| {
+                let stats = reporter.analyse(&ScopeLimits::empty());
+                let space = reporter.not_found_space(&ScopeLimits::empty());
+                (
+                    WorldStatsReporter::to_json(&stats, true).unwrap(),
+                    WorldStatsReporter::to_json(&space, true).unwrap(),
+                )
+            })
+        };
+
+        let (stats, space) = run();
+        assert!(stats.starts_with("{\"denominator\":\"all\",\"stats\":{\"NL\":"));
+        assert_eq!((stats, space), run());
+    }
+
+    fn with_small_reporter<F, R>(f: F) -> R
+    where
+        F: FnOnce(&WorldStatsReporter) -> R,
+    {
+        let announcements = Announcements::from_ris(&[
+            PathBuf::from("test/small/riswhoisdump.IPv4"),
+            PathBuf::from("test/small/riswhoisdump.IPv6"),
+        ])
+        .unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/small/vrps.csv")).unwrap();
+        let delegations =
+            IpDelegations::from_file(&PathBuf::from("test/small/delegated-extended.txt")).unwrap();
+
+        f(&WorldStatsReporter::new(
+            &announcements,
+            &vrps,
+            &delegations,
+        ))
+    }
+
+    #[test]
+    fn should_exclude_dark_vrps_from_seen() {
+        let announcements = Announcements::from_ris(&[
+            PathBuf::from("test/small/riswhoisdump.IPv4"),
+            PathBuf::from("test/small/riswhoisdump.IPv6"),
+        ])
+        .unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/dark/vrps.csv")).unwrap();
+        let delegations =
+            IpDelegations::from_file(&PathBuf::from("test/small/delegated-extended.txt")).unwrap();
+        let reporter = WorldStatsReporter::new(&announcements, &vrps, &delegations);
+
+        // The VRP for 10.9.0.0/16 is unseen because nothing is announced
+        let all = reporter.analyse(&ScopeLimits::empty()).stats["all"].clone();
+        assert_eq!((2, 2), (all.vrps_seen, all.vrps_unseen));
+        assert_eq!(Some(50.), all.f_seen());
+
+        let excluded = reporter
+            .with_exclude_dark_vrps(true)
+            .analyse(&ScopeLimits::empty())
+            .stats["all"]
+            .clone();
+        assert_eq!((2, 1), (excluded.vrps_seen, excluded.vrps_unseen));
+        assert_eq!(Some(66.66), excluded.f_seen());
+
+        // Announcements are not affected
+        assert_eq!(all.f_quality(), excluded.f_quality());
+        assert_eq!(all.total(), excluded.total());
+    }
+
+    #[test]
+    fn family_counts_should_add_up_to_combined() {
+        let stats = small_stats();
+
+        for stat in stats.stats.values() {
+            assert_eq!(stat.total(), stat.v4().total() + stat.v6().total());
+            assert_eq!(stat.covered(), stat.v4().covered() + stat.v6().covered());
+        }
+
+        let all = &stats.stats["all"];
+        assert_eq!(5, all.v4().total());
+        assert_eq!(2, all.v6().total());
+    }
+
+    #[test]
+    fn adoption_should_exclude_unassigned_not_found_if_asked() {
+        let stats = small_stats();
+
+        // The fixture has one not found announcement in reserved space, and
+        // one in assigned space, out of seven announcements in total.
+        let all = &stats.stats["all"];
+        assert_eq!(71.42, all.f_adoption_with(AdoptionDenominator::All));
+        assert_eq!(
+            83.33,
+            all.f_adoption_with(AdoptionDenominator::AssignedOnly)
+        );
+
+        let us = &stats.stats["US"];
+        assert_eq!(
+            us.f_adoption_with(AdoptionDenominator::All),
+            us.f_adoption_with(AdoptionDenominator::AssignedOnly)
+        );
+    }
+
+    #[test]
+    fn should_count_per_registry() {
+        let stats =
+            with_small_reporter(|reporter| reporter.analyse_by_registry(&ScopeLimits::empty()));
+
+        // NL and the reserved and available ZZ space are all ripencc
+        let ripencc = stats.get("ripencc").unwrap();
+        assert_eq!(
+            (6, 2, 1, 2, 1),
+            (
+                ripencc.total(),
+                ripencc.routes_valid,
+                ripencc.routes_inv_l,
+                ripencc.routes_inv_a,
+                ripencc.routes_not_f
+            )
+        );
+        assert_eq!((2, 0), (ripencc.vrps_seen, ripencc.vrps_unseen));
+
+        let arin = stats.get("arin").unwrap();
+        assert_eq!((1, 1), (arin.total(), arin.routes_not_f));
+        assert_eq!((0, 1), (arin.vrps_seen, arin.vrps_unseen));
+
+        assert_eq!(7, stats.get("all").unwrap().total());
+        assert_eq!(None, stats.get(UNKNOWN_REGISTRY));
+        assert_eq!(None, stats.get("NL"));
+
+        assert_eq!(
+            "Overall\n  \
+             Valid: 2, Invalid Length: 1, Invalid ASN: 2, Not Found: 2, VRPS seen: 2, VRPS unseen: 1\n\
+             \n\
+             Per RIR:\n\
+             arin: Valid: 0, Invalid Length: 0, Invalid ASN: 0, Not Found: 1, VRPS seen: 0, VRPS unseen: 1\n\
+             ripencc: Valid: 2, Invalid Length: 1, Invalid ASN: 2, Not Found: 1, VRPS seen: 2, VRPS unseen: 0\n",
+            stats.to_string()
+        );
+    }
+
+    #[test]
+    fn should_only_group_by_registry_for_json_and_text() {
+        let parse = |args: &[&str]| {
+            let mut all = vec![
+                "world",
+                "--announcements",
+                "a",
+                "--vrps",
+                "v",
+                "--delegations",
+                "d",
+            ];
+            all.extend(args);
+            let matches = cli::subcommand_matches(&all);
+            WorldStatsOpts::parse(&matches).map(|opts| opts.by)
+        };
+
+        assert_eq!(WorldStatsBy::Country, parse(&[]).unwrap());
+        assert_eq!(WorldStatsBy::Registry, parse(&["--by", "rir"]).unwrap());
+        assert_eq!(
+            WorldStatsBy::Registry,
+            parse(&["--by", "rir", "--format", "text"]).unwrap()
+        );
+        assert!(parse(&["--by", "rir", "--format", "tsv"]).is_err());
+        assert!(parse(&["--by", "continent"]).is_err());
+    }
+
+    #[test]
+    fn should_write_summary_for_country_stats_formats() {
+        let summary = |format: &str| {
+            let matches = cli::subcommand_matches(&[
+                "world",
+                "--announcements",
+                "test/small/riswhoisdump.IPv4",
+                "test/small/riswhoisdump.IPv6",
+                "--vrps",
+                "test/small/vrps.csv",
+                "--delegations",
+                "test/small/delegated-extended.txt",
+                "--format",
+                format,
+                "--stats",
+            ]);
+            let options = WorldStatsOpts::parse(&matches).unwrap();
+
+            let mut out = vec![];
+            WorldStatsReporter::execute_with_summary(&options, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert!(
+            summary("tsv").starts_with("Announcements: 7, invalid: 3, VRPs: 3, unseen: 1, took: ")
+        );
+        assert_eq!("", summary("cross-rir"));
+    }
+
+    #[test]
+    fn rendered_adoption_should_use_denominator() {
+        use crate::announcements::Announcement;
+
+        let vrp = ValidatedRoaPayload::from_str("AS65000,10.0.0.0/16,16").unwrap();
+        let covered = Announcement::from_str("AS65000, 10.0.0.0/16").unwrap();
+        let unassigned = Announcement::from_str("AS65000, 2001:db8::/32").unwrap();
+
+        let mut stats = CountryStats::default();
+        stats.add_ann(
+            &ValidatedAnnouncement::create(&covered, &[&vrp]),
+            "NL",
+            true,
+        );
+        stats.add_ann(
+            &ValidatedAnnouncement::create(&unassigned, &[]),
+            "NL",
+            false,
+        );
+
+        assert_eq!(AdoptionDenominator::All, stats.denominator());
+        assert!(stats.to_csv().contains("\nNL,50,100,0,100,100,0,0\n"));
+        assert!(stats.adoption_array().contains("['NL', 50]"));
+
+        let stats = stats.with_denominator(AdoptionDenominator::AssignedOnly);
+        assert!(stats.to_csv().contains("\nNL,100,100,0,100,100,0,0\n"));
+        assert!(stats.adoption_array().contains("['NL', 100]"));
+        assert_eq!(100., stats.to_geojson().features[0].properties.coverage);
+    }
+
+    #[test]
+    fn denominator_should_parse() {
+        assert_eq!(
+            AdoptionDenominator::All,
+            AdoptionDenominator::from_str("all").unwrap()
+        );
+        assert_eq!(
+            AdoptionDenominator::AssignedOnly,
+            AdoptionDenominator::from_str("assigned").unwrap()
+        );
+        assert!(AdoptionDenominator::from_str("some").is_err());
+    }
+
+    #[test]
+    fn scoped_analysis_should_only_include_countries_in_scope() {
+        let stats = small_stats();
+        assert!(stats.stats.contains_key("US"));
+
+        let scope = ScopeLimits::from_str("10.0.0.0/8").unwrap();
+        let stats = small_stats_in_scope(&scope);
+
+        let mut countries: Vec<&String> = stats.stats.keys().collect();
+        countries.sort();
+        assert_eq!(vec!["NL", "all"], countries);
+        assert_eq!(3, stats.stats["all"].total());
+
+        let scope = ScopeLimits::from_str("AS65001").unwrap();
+        let stats = small_stats_in_scope(&scope);
+        assert_eq!(3, stats.stats["all"].total());
+        assert!(stats.stats.contains_key("US"));
+    }
+
+    #[test]
+    fn should_list_not_found_prefixes_per_country() {
+        let space = with_small_reporter(|r| r.not_found_space(&ScopeLimits::empty()));
+
+        let prefixes = |cc| -> Vec<String> {
+            match space.prefixes(cc) {
+                Some(pfxs) => pfxs.iter().map(|p| p.to_string()).collect(),
+                None => vec![],
+            }
+        };
+
+        assert_eq!(vec!["192.168.0.0/16"], prefixes("US"));
+        assert_eq!(vec!["172.16.0.0/24"], prefixes("ZZ"));
+        assert!(prefixes("NL").is_empty());
+    }
+
+    #[test]
+    fn not_found_space_should_leave_out_covered_more_specifics() {
+        let mut space = NotFoundSpace::default();
+        for pfx in &["10.0.1.0/24", "10.0.0.0/16", "10.0.0.0/24", "10.1.0.0/24"] {
+            space.add(&IpPrefix::from_str(pfx).unwrap(), "NL");
+        }
+        space.normalise();
+
+        let prefixes: Vec<String> = space
+            .prefixes("NL")
+            .unwrap()
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        assert_eq!(vec!["10.0.0.0/16", "10.1.0.0/24"], prefixes);
+    }
+
+    #[test]
+    fn should_handle_country_with_only_vrps() {
+        let mut stats = CountryStats::default().with_denominator(AdoptionDenominator::AssignedOnly);
+        stats.get_cc("DE").vrps_unseen = 2;
+
+        let de = &stats.stats["DE"];
+        assert_eq!(0_f32, de.f_adoption());
+        assert_eq!(0_f32, de.f_adoption_with(AdoptionDenominator::AssignedOnly));
+        assert_eq!(0_f32, de.f_valid());
+        assert_eq!(None, de.f_quality());
+        assert_eq!(Some(0_f32), de.f_seen());
+
+        assert_eq!("          ['DE', 0],\n", stats.adoption_array());
+        assert_eq!("          ['DE', 0],\n", stats.valid_array());
+        assert_eq!("", stats.quality_array());
+
+        // there is no adoption to report for the country in the CSV
+        assert_eq!(1, stats.to_csv().lines().count());
+    }
+
+    #[test]
+    fn should_merge_country_stats() {
+        let stat = |valid, inv_a, seen| CountryStat {
+            routes_valid: valid,
+            routes_inv_a: inv_a,
+            vrps_seen: seen,
+            v4: FamilyStat {
+                routes_valid: valid,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let stats = |ccs: &[(&str, CountryStat)]| {
+            let mut stats = CountryStats::default();
+            for (cc, stat) in ccs {
+                stats.get_cc(cc).merge(stat);
+                stats.get_cc("all").merge(stat);
+            }
+            stats
+        };
+
+        // disjoint countries are kept apart, 'all' is summed
+        let mut merged = stats(&[("NL", stat(1, 2, 3))]);
+        merged.merge(&stats(&[("DE", stat(4, 5, 6))]));
+        assert_eq!(stat(1, 2, 3), merged.stats["NL"]);
+        assert_eq!(stat(4, 5, 6), merged.stats["DE"]);
+        assert_eq!(stat(5, 7, 9), merged.stats["all"]);
+        assert_eq!(3, merged.stats.len());
+
+        // the same country is summed
+        let mut merged = stats(&[("NL", stat(1, 2, 3))]);
+        merged.merge(&stats(&[("NL", stat(4, 5, 6))]));
+        assert_eq!(stat(5, 7, 9), merged.stats["NL"]);
+        assert_eq!(stat(5, 7, 9), merged.stats["all"]);
+        assert_eq!(2, merged.stats.len());
+
+        // merging with empty stats changes nothing
+        let mut merged = small_stats();
+        merged.merge(&CountryStats::default());
+        assert_eq!(small_stats(), merged);
+    }
+
+    #[test]
+    fn analysis_should_not_depend_on_number_of_threads() {
+        let single = WorldStatsReporter::thread_pool(Some(1))
+            .unwrap()
+            .install(small_stats);
+        let multi = WorldStatsReporter::thread_pool(Some(4))
+            .unwrap()
+            .install(small_stats);
+
+        assert_eq!(single, multi);
+        assert_eq!(7, single.stats["all"].total());
+    }
+
+    #[test]
+    fn csv_should_include_family_columns() {
+        let stats = small_stats();
+        let csv = stats.to_csv();
+        let mut lines = csv.lines();
+
+        assert_eq!(
+            Some("iso2,coverage,accuracy,seen,coverage_v4,accuracy_v4,coverage_v6,accuracy_v6"),
+            lines.next()
+        );
+
+        // NL has IPv4 and IPv6 announcements in the fixture. The combined
+        // accuracy counts both: 1 of 3 IPv4, and 1 of 2 IPv6 are valid.
+        let nl = lines.find(|l| l.starts_with("NL,")).unwrap();
+        let values: Vec<&str> = nl.split(',').collect();
+        assert_eq!(
+            vec!["NL", "100", "40", "100", "100", "33.33", "100", "50"],
+            values
+        );
+    }
+
+    #[test]
+    fn geojson_should_be_a_feature_collection() {
+        let stats = small_stats();
+        let geojson = serde_json::to_value(stats.to_geojson()).unwrap();
+
+        assert_eq!("FeatureCollection", geojson["type"]);
+
+        let features = geojson["features"].as_array().unwrap();
+        let codes: Vec<&str> = features
+            .iter()
+            .map(|f| f["properties"]["iso2"].as_str().unwrap())
+            .collect();
+        assert_eq!(vec!["NL", "US", "ZZ"], codes);
+
+        for feature in features {
+            assert_eq!("Feature", feature["type"]);
+            assert!(feature["geometry"].is_null());
+            assert!(feature["properties"]["coverage"].is_number());
+        }
+
+        let nl = &features[0]["properties"];
+        assert_eq!(
+            stats.stats["NL"].f_adoption() as f64,
+            nl["coverage"].as_f64().unwrap()
+        );
+    }
+
+    #[test]
+    fn should_break_ties_between_equal_codes() {
+        let stat = |routes_valid, routes_not_f| CountryStat {
+            routes_valid,
+            routes_not_f,
+            ..Default::default()
+        };
+        let small = stat(1, 0);
+        let large = stat(1, 5);
+        let covered = stat(3, 3);
+        let other = stat(0, 1);
+
+        let sorted = |stats: Vec<(&'static str, &CountryStat)>| -> Vec<(String, usize, usize)> {
+            let mut countries: Vec<CountryStatWithCode> = stats
+                .into_iter()
+                .map(|(cc, stat)| CountryStatWithCode { cc, stat })
+                .collect();
+            countries.sort();
+            countries
+                .iter()
+                .map(|c| (c.cc.to_string(), c.stat.total(), c.stat.covered()))
+                .collect()
+        };
+
+        // Larger totals first, then more covered routes
+        let expected = vec![
+            ("DE".to_string(), 1, 0),
+            ("NL".to_string(), 6, 3),
+            ("NL".to_string(), 6, 1),
+            ("NL".to_string(), 1, 1),
+        ];
+        assert_eq!(
+            expected,
+            sorted(vec![
+                ("NL", &small),
+                ("NL", &large),
+                ("DE", &other),
+                ("NL", &covered)
+            ])
+        );
+        assert_eq!(
+            expected,
+            sorted(vec![
+                ("NL", &covered),
+                ("DE", &other),
+                ("NL", &large),
+                ("NL", &small)
+            ])
+        );
+
+        let nl = sorted(vec![("NL", &small), ("NL", &large)]);
+        assert_eq!(nl, sorted(vec![("NL", &large), ("NL", &small)]));
+
+        // Equality agrees with the order, even for stats that differ in
+        // fields outside of the tie breaker
+        let with_code = |cc, stat| CountryStatWithCode { cc, stat };
+        let other_f = CountryStat {
+            v4: FamilyStat {
+                routes_valid: 1,
+                ..Default::default()
+            },
+            ..large.clone()
+        };
+        assert!(with_code("NL", &large) == with_code("NL", &other_f));
+        assert_eq!(
+            Ordering::Equal,
+            with_code("NL", &large).cmp(&with_code("NL", &other_f))
+        );
+        assert!(with_code("NL", &large) != with_code("DE", &large));
+        assert!(with_code("NL", &large) != with_code("NL", &small));
+    }
+
+    #[test]
+    fn tsv_should_have_one_metric_per_row() {
+        let stats = small_stats();
+        let tsv = stats.to_tsv();
+
+        let rows: Vec<Vec<&str>> = tsv.lines().map(|l| l.split('\t').collect()).collect();
+        for row in &rows {
+            assert_eq!(4, row.len());
+            usize::from_str(row[3]).unwrap();
+        }
+
+        // 14 metrics for the overall totals, and for each country
+        assert_eq!(14 * (stats.get_sorted_countries().len() + 1), rows.len());
+        assert_eq!(vec!["all", "all", "valid", "2"], rows[0]);
+
+        let value = |cc, family, metric| {
+            rows.iter()
+                .find(|r| r[0] == cc && r[1] == family && r[2] == metric)
+                .map(|r| r[3])
+        };
+        assert_eq!(Some("1"), value("NL", "ipv6", "valid"));
+        assert_eq!(Some("1"), value("NL", "ipv6", "invalid_asn"));
+        assert_eq!(Some("1"), value("US", "all", "vrps_unseen"));
+        assert_eq!(None, value("NL", "ipv4", "vrps_seen"));
+    }
+
+    #[test]
+    fn prometheus_should_have_gauges_per_country() {
+        let stats = small_stats();
+        let metrics = stats.to_prometheus();
+
+        assert!(metrics.starts_with(
+            "# HELP rpki_stats_routes_valid Valid announcements\n\
+             # TYPE rpki_stats_routes_valid gauge\n\
+             rpki_stats_routes_valid{cc=\"all\"} 2\n"
+        ));
+        assert!(metrics.contains("rpki_stats_routes_invalid_asn{cc=\"all\"} 2\n"));
+        assert!(metrics.contains("rpki_stats_vrps_unseen{cc=\"us\"} 1\n"));
+        assert!(metrics.contains("# TYPE rpki_stats_vrps_seen gauge\n"));
+
+        let samples = metrics.lines().filter(|l| !l.starts_with('#')).count();
+        assert_eq!(6 * (stats.get_sorted_countries().len() + 1), samples);
+    }
+
+    #[test]
+    fn html_dir_should_include_assets() {
+        let dir = std::env::temp_dir().join(format!("world-html-{}", std::process::id()));
+        let stats = small_stats();
+
+        WorldStatsReporter::html_dir(&stats, &dir).unwrap();
+
+        let html = fs::read_to_string(dir.join("index.html")).unwrap();
+        assert!(html.contains("\"ui/css/bootstrap.min.css\""));
+        assert!(html.contains("'world.csv'"));
+        assert!(!html.contains("/ui/"));
+
+        for content in statics::UI.iter().filter(|c| c.path != "world.html") {
+            let written = fs::read(dir.join("ui").join(content.path)).unwrap();
+            assert_eq!(content.content, written.as_slice());
+        }
+
+        let csv = fs::read_to_string(dir.join("world.csv")).unwrap();
+        assert_eq!(stats.to_csv(), csv);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
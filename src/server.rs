@@ -1,82 +1,431 @@
 //! Run the stats as an HTTP daemon
 
+use crate::announcements::Announcement;
 use crate::announcements::Announcements;
+use crate::config;
+use crate::config::Config;
+use crate::ip::Asn;
+use crate::ip::IpPrefix;
+use crate::validation::ValidatedAnnouncement;
+use crate::validation::ValidationState;
+use crate::vrps::ValidatedRoaPayload;
 use crate::vrps::Vrps;
+use actix_web::actix;
+#[cfg(unix)]
+use actix_web::actix::signal;
+#[cfg(unix)]
+use actix_web::actix::Actor;
+#[cfg(unix)]
+use actix_web::actix::Context;
+#[cfg(unix)]
+use actix_web::actix::Handler;
+use actix_web::http::header;
 use actix_web::http::Method;
 use actix_web::http::StatusCode;
+use actix_web::middleware;
 use actix_web::pred;
 use actix_web::server;
 use actix_web::App;
+use actix_web::FutureResponse;
 use actix_web::HttpResponse;
 use announcements;
 use clap::ArgMatches;
 use delegations;
 use delegations::IpDelegations;
+use futures::future;
+use futures::sync::oneshot;
+use futures::Future;
+use input;
+use input::Progress;
+use log::Level;
+use logging;
+use logging::LogFormat;
+use logging::Logger;
+use report::asn::AsnReporter;
+use report::coverage::CoverageReporter;
+use report::coverage::SpaceCoverage;
+use report::lengths::PrefixLengthReporter;
+use report::reject::RejectImpactReporter;
 use report::resources::ResourceReporter;
+use report::world::AdoptionDenominator;
+use report::world::CountryStats;
 use report::world::WorldStatsReporter;
 use report::ScopeLimits;
 use serde::Serialize;
-use std::net::IpAddr;
+use statics;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+#[cfg(unix)]
+use std::fs;
+use std::io;
 use std::net::SocketAddr;
+use std::path::Path;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
+#[cfg(unix)]
+use std::thread;
+#[cfg(unix)]
+use tokio_uds::UnixListener;
 use vrps;
 
 const NOT_FOUND: &[u8] = include_bytes!("../ui/not_found.html");
 
+/// The API endpoints for which requests are counted.
+const ENDPOINTS: &[&str] = &[
+    "asn",
+    "details",
+    "metrics",
+    "prefix-lengths",
+    "reject-impact",
+    "reload",
+    "unseen",
+    "validate",
+    "vrps.csv",
+    "world",
+    "world.csv",
+    "world.json",
+];
+
+#[derive(Clone)]
 pub struct ServerOpts {
     announcements: Vec<PathBuf>,
+    min_peers: u32,
     vrps: PathBuf,
+    slurm: Option<PathBuf>,
     dels: PathBuf,
+    bind: SocketAddr,
+    unix_socket: Option<PathBuf>,
+    log_format: LogFormat,
+    denominator: AdoptionDenominator,
+    reload_token: Option<String>,
+    progress: Progress,
 }
 
 impl ServerOpts {
     pub fn parse(matches: &ArgMatches) -> Result<Self, Error> {
-        let mut announcements = vec![];
-        for name in matches.values_of("announcements").unwrap().into_iter() {
-            announcements.push(PathBuf::from(name))
-        }
-
-        let vrps_file = matches.value_of("vrps").unwrap();
-        let vrps = PathBuf::from(vrps_file);
+        let config = Config::from_matches(matches)?;
+        let announcements = config.announcements()?;
+        let min_peers = config.min_peers();
+        let vrps = config.vrps()?;
+        let slurm = config.optional_slurm();
+        let dels = config.delegations()?;
+        let bind = config.bind();
+        let denominator = config.denominator();
+        let unix_socket = config.optional_unix_socket();
+        let log_format = config.log_format();
+        let reload_token = config.optional_reload_token();
+        let progress = Progress::from_matches(matches);
 
-        let dels_file = matches.value_of("delegations").unwrap();
-        let dels = PathBuf::from(dels_file);
+        // The daemon reads its files again on every reload, but stdin can
+        // only be read once.
+        let inputs = announcements
+            .iter()
+            .chain(Some(&vrps))
+            .chain(slurm.as_ref())
+            .chain(Some(&dels));
+        for path in inputs {
+            if input::is_stdin(path) {
+                return Err(Error::StdinInput);
+            }
+        }
 
         Ok(ServerOpts {
             announcements,
+            min_peers,
             vrps,
+            slurm,
             dels,
+            bind,
+            unix_socket,
+            log_format,
+            denominator,
+            reload_token,
+            progress,
         })
     }
 }
 
+/// The data loaded from the input files. A reload replaces all of it at
+/// once, so that a request never sees a mix of old and new data.
 #[derive(Debug)]
 pub struct Sources {
     announcements: Announcements,
     vrps: Vrps,
-    delegations: IpDelegations,
+    coverage: SpaceCoverage,
+    world: CountryStats,
+}
+
+impl Sources {
+    fn load(opts: &ServerOpts) -> Result<Self, Error> {
+        let announcements = Announcements::from_ris_with_progress(
+            &opts.announcements,
+            opts.min_peers,
+            opts.progress,
+        )?;
+        let vrps = Vrps::load(&opts.vrps, opts.slurm.as_deref(), opts.progress)?;
+        let delegations = IpDelegations::from_file_with_progress(&opts.dels, opts.progress)?;
+
+        // The sources do not change until they are reloaded, so the coverage
+        // gauges and world stats only need to be computed once.
+        let coverage = CoverageReporter::new(&announcements, &vrps).analyse(&ScopeLimits::empty());
+        let world = WorldStatsReporter::new(&announcements, &vrps, &delegations)
+            .with_denominator(opts.denominator)
+            .analyse(&ScopeLimits::empty());
+
+        Ok(Sources {
+            announcements,
+            vrps,
+            coverage,
+            world,
+        })
+    }
+}
+
+/// The number of announcements and VRPs after a reload.
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct ReloadSummary {
+    announcements: usize,
+    vrps: usize,
 }
 
-#[derive(Debug)]
 pub struct StatsServer {
-    sources: Sources,
+    opts: ServerOpts,
+    sources: RwLock<Arc<Sources>>,
+    reloading: Mutex<()>,
+    hits: EndpointHits,
 }
 
 impl StatsServer {
     fn create(opts: &ServerOpts) -> Result<Self, Error> {
-        let announcements = Announcements::from_ris(&opts.announcements)?;
-        let vrps = Vrps::from_file(&opts.vrps)?;
-        let delegations = IpDelegations::from_file(&opts.dels)?;
+        Ok(StatsServer {
+            opts: opts.clone(),
+            sources: RwLock::new(Arc::new(Sources::load(opts)?)),
+            reloading: Mutex::new(()),
+            hits: EndpointHits::default(),
+        })
+    }
 
-        let sources = Sources {
-            announcements,
-            vrps,
-            delegations,
+    /// Returns the current sources. Requests keep using these until they
+    /// are done, even if the sources are reloaded in the meantime.
+    fn sources(&self) -> Arc<Sources> {
+        self.sources.read().unwrap().clone()
+    }
+
+    /// Loads the sources again from the same files, and replaces the current
+    /// sources if all files could be read. Reloads run one at a time.
+    fn reload(&self) -> Result<ReloadSummary, Error> {
+        let _reloading = self.reloading.lock().unwrap();
+
+        let sources = Sources::load(&self.opts)?;
+        let summary = ReloadSummary {
+            announcements: sources.announcements.iter().count(),
+            vrps: sources.vrps.iter().count(),
         };
 
-        Ok(StatsServer { sources })
+        *self.sources.write().unwrap() = Arc::new(sources);
+        info!(
+            "Reloaded {} announcements and {} VRPs",
+            summary.announcements, summary.vrps
+        );
+        Ok(summary)
+    }
+}
+
+//------------ Page ----------------------------------------------------------
+
+/// The part of a list to return, as requested with the 'offset' and 'limit'
+/// query parameters. Without a limit the rest of the list is returned.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Page {
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl Page {
+    fn apply<'a, T>(&self, items: &'a [T]) -> &'a [T] {
+        let start = self.offset.min(items.len());
+        let end = match self.limit {
+            Some(limit) => start.saturating_add(limit).min(items.len()),
+            None => items.len(),
+        };
+        &items[start..end]
+    }
+}
+
+/// A page of unseen VRPs, with the total number of unseen VRPs in scope.
+#[derive(Serialize)]
+struct UnseenPage<'a> {
+    total: usize,
+    offset: usize,
+    unseen: &'a [ValidatedRoaPayload],
+}
+
+/// The validation state of a single announcement, with all VRPs that cover
+/// its prefix.
+#[derive(Serialize)]
+struct Validation<'a> {
+    announcement: &'a Announcement,
+    state: &'a ValidationState,
+    vrps: Vec<&'a ValidatedRoaPayload>,
+}
+
+//------------ WorldFormat ---------------------------------------------------
+
+/// The formats that the world endpoint can return.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum WorldFormat {
+    Json,
+    Csv,
+}
+
+impl WorldFormat {
+    /// Picks the format with the highest quality value in the Accept header.
+    /// If neither format is acceptable, or the header is missing, JSON is
+    /// used.
+    fn negotiate(accept: &str) -> Self {
+        let mut best = (WorldFormat::Json, 0.0);
+
+        for media_range in accept.split(',') {
+            let mut parts = media_range.split(';').map(str::trim);
+            let format = match parts.next() {
+                Some("application/json") | Some("*/*") | Some("application/*") => WorldFormat::Json,
+                Some("text/csv") | Some("text/*") => WorldFormat::Csv,
+                _ => continue,
+            };
+
+            let quality = parts
+                .filter_map(|param| param.strip_prefix("q="))
+                .filter_map(|q| f32::from_str(q).ok())
+                .next()
+                .unwrap_or(1.0);
+
+            if quality > best.1 {
+                best = (format, quality);
+            }
+        }
+
+        best.0
+    }
+}
+
+//------------ UnixSocket ----------------------------------------------------
+
+/// Removes the socket file when the server stops.
+#[cfg(unix)]
+struct UnixSocket(PathBuf);
+
+#[cfg(unix)]
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+//------------ ShutdownOnSignal ----------------------------------------------
+
+/// Stops the actix system on SIGINT, SIGTERM and SIGQUIT. The TCP server
+/// does this itself, but serving a Unix socket does not.
+#[cfg(unix)]
+struct ShutdownOnSignal;
+
+#[cfg(unix)]
+impl Actor for ShutdownOnSignal {
+    type Context = Context<Self>;
+}
+
+#[cfg(unix)]
+impl Handler<signal::Signal> for ShutdownOnSignal {
+    type Result = ();
+
+    fn handle(&mut self, msg: signal::Signal, _: &mut Context<Self>) {
+        match msg.0 {
+            signal::SignalType::Int | signal::SignalType::Term | signal::SignalType::Quit => {
+                actix::System::current().stop()
+            }
+            _ => {}
+        }
+    }
+}
+
+//------------ ReloadOnSignal ------------------------------------------------
+
+/// Reloads the sources on SIGHUP. The reload runs on its own thread, so that
+/// it does not hold up the actix system.
+#[cfg(unix)]
+struct ReloadOnSignal(Arc<StatsServer>);
+
+#[cfg(unix)]
+impl Actor for ReloadOnSignal {
+    type Context = Context<Self>;
+}
+
+#[cfg(unix)]
+impl Handler<signal::Signal> for ReloadOnSignal {
+    type Result = ();
+
+    fn handle(&mut self, msg: signal::Signal, _: &mut Context<Self>) {
+        if let signal::SignalType::Hup = msg.0 {
+            let server = self.0.clone();
+            thread::spawn(move || {
+                if let Err(e) = server.reload() {
+                    error!("Reload failed, keeping the current sources: {}", e);
+                }
+            });
+        }
+    }
+}
+
+//------------ EndpointHits --------------------------------------------------
+
+/// Counts the number of requests per API endpoint, since the server was
+/// started.
+#[derive(Debug)]
+pub struct EndpointHits {
+    counters: BTreeMap<&'static str, AtomicUsize>,
+}
+
+impl Default for EndpointHits {
+    fn default() -> Self {
+        let counters = ENDPOINTS
+            .iter()
+            .map(|endpoint| (*endpoint, AtomicUsize::new(0)))
+            .collect();
+        EndpointHits { counters }
+    }
+}
+
+impl EndpointHits {
+    fn hit(&self, endpoint: &str) {
+        if let Some(counter) = self.counters.get(endpoint) {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn get(&self, endpoint: &str) -> Option<usize> {
+        self.counters
+            .get(endpoint)
+            .map(|counter| counter.load(Ordering::Relaxed))
+    }
+
+    /// Returns the counts in the Prometheus text format.
+    pub fn to_prometheus(&self) -> String {
+        let mut s = String::new();
+        writeln!(s, "# TYPE rpki_stats_requests_total counter").unwrap();
+        for (endpoint, counter) in &self.counters {
+            writeln!(
+                s,
+                "rpki_stats_requests_total{{endpoint=\"{}\"}} {}",
+                endpoint,
+                counter.load(Ordering::Relaxed)
+            )
+            .unwrap();
+        }
+        s
     }
 }
 
@@ -85,6 +434,7 @@ pub struct StatsApp(App<Arc<StatsServer>>);
 impl StatsApp {
     pub fn new(server: Arc<StatsServer>) -> Self {
         let app = App::with_state(server)
+            .middleware(middleware::Logger::default())
             .resource("/", |r| {
                 r.method(Method::GET).f(|_r| {
                     HttpResponse::Found()
@@ -95,12 +445,39 @@ impl StatsApp {
             .resource("/rpki-stats-api/details", |r| {
                 r.method(Method::GET).f(Self::details);
             })
+            .resource("/rpki-stats-api/asn/{asn}", |r| {
+                r.method(Method::GET).f(Self::asn);
+            })
+            .resource("/rpki-stats-api/prefix-lengths", |r| {
+                r.method(Method::GET).f(Self::prefix_lengths);
+            })
+            .resource("/rpki-stats-api/unseen", |r| {
+                r.method(Method::GET).f(Self::unseen);
+            })
+            .resource("/rpki-stats-api/reject-impact", |r| {
+                r.method(Method::GET).f(Self::reject_impact);
+            })
+            .resource("/rpki-stats-api/reload", |r| {
+                r.method(Method::POST).f(Self::reload);
+            })
+            .resource("/rpki-stats-api/validate", |r| {
+                r.method(Method::GET).f(Self::validate);
+            })
+            .resource("/rpki-stats-api/world", |r| {
+                r.method(Method::GET).f(Self::world);
+            })
             .resource("/rpki-stats-api/world.json", |r| {
                 r.method(Method::GET).f(Self::world_json);
             })
             .resource("/rpki-stats-api/world.csv", |r| {
                 r.method(Method::GET).f(Self::world_csv);
             })
+            .resource("/rpki-stats-api/vrps.csv", |r| {
+                r.method(Method::GET).f(Self::vrps_csv);
+            })
+            .resource("/metrics", |r| {
+                r.method(Method::GET).f(Self::metrics);
+            })
             .default_resource(|r| {
                 // 404 for GET request
                 r.method(Method::GET).f(Self::p404);
@@ -117,70 +494,380 @@ impl StatsApp {
     }
 
     pub fn run(opts: &ServerOpts) -> Result<(), Error> {
+        Logger::new(opts.log_format, Level::Info).init()?;
+
         let stats_server = Arc::new(StatsServer::create(opts)?);
+        info!("Loaded announcements, VRPs and delegations");
+
+        if let Some(path) = &opts.unix_socket {
+            info!("Listening on unix socket: {}", path.display());
+            return Self::run_unix(stats_server, path);
+        }
+
+        let sys = actix::System::new("secure-routing-stats");
 
-        let server = server::new(move || Self::new(stats_server.clone()));
+        let app_server = stats_server.clone();
+        let server = server::new(move || Self::new(app_server.clone()));
 
-        let address = SocketAddr::new(IpAddr::from_str("127.0.0.1").unwrap(), 8080);
+        let address = opts.bind;
+        info!("Listening on: {}", address);
 
         server
             .bind(address)
             .unwrap_or_else(|_| panic!("Cannot bind to: {}", address))
             .shutdown_timeout(0)
-            .run();
+            .start();
 
+        Self::reload_on_hangup(stats_server);
+
+        sys.run();
         Ok(())
     }
 
+    /// Reloads the sources when the process gets SIGHUP.
+    #[cfg(unix)]
+    fn reload_on_hangup(stats_server: Arc<StatsServer>) {
+        let reload = ReloadOnSignal(stats_server).start();
+        let signals = actix::System::current()
+            .registry()
+            .get::<signal::ProcessSignals>();
+        signals.do_send(signal::Subscribe(reload.recipient()));
+    }
+
+    #[cfg(not(unix))]
+    fn reload_on_hangup(_stats_server: Arc<StatsServer>) {}
+
+    /// Serves on a Unix domain socket instead of a TCP port, until the
+    /// process gets SIGINT, SIGTERM or SIGQUIT. The socket file is removed
+    /// on shutdown.
+    #[cfg(unix)]
+    fn run_unix(stats_server: Arc<StatsServer>, path: &Path) -> Result<(), Error> {
+        let sys = actix::System::new("secure-routing-stats");
+
+        let _socket = Self::start_unix(stats_server.clone(), path)?;
+        Self::reload_on_hangup(stats_server);
+
+        let shutdown = ShutdownOnSignal.start();
+        let signals = actix::System::current()
+            .registry()
+            .get::<signal::ProcessSignals>();
+        signals.do_send(signal::Subscribe(shutdown.recipient()));
+
+        sys.run();
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn run_unix(_stats_server: Arc<StatsServer>, _path: &Path) -> Result<(), Error> {
+        Err(Error::msg(
+            "Unix domain sockets are not supported on this platform",
+        ))
+    }
+
+    /// Starts serving on the Unix domain socket, in the current actix
+    /// system. A stale socket file left by an earlier run is replaced, but
+    /// a socket that another server still accepts connections on is not.
+    #[cfg(unix)]
+    #[allow(deprecated)]
+    fn start_unix(stats_server: Arc<StatsServer>, path: &Path) -> Result<UnixSocket, Error> {
+        use std::os::unix::fs::FileTypeExt;
+        use std::os::unix::net::UnixStream;
+
+        if let Ok(meta) = fs::symlink_metadata(path) {
+            if meta.file_type().is_socket() {
+                if UnixStream::connect(path).is_ok() {
+                    let in_use = io::Error::new(
+                        io::ErrorKind::AddrInUse,
+                        "another server is listening on it",
+                    );
+                    return Err(Error::unix_socket(path, in_use));
+                }
+                fs::remove_file(path).map_err(|e| Error::unix_socket(path, e))?;
+            }
+        }
+
+        let listener = UnixListener::bind(path).map_err(|e| Error::unix_socket(path, e))?;
+        let socket = UnixSocket(path.to_path_buf());
+
+        server::new(move || Self::new(stats_server.clone()))
+            .start_incoming(listener.incoming(), false);
+
+        Ok(socket)
+    }
+
     fn p404(_req: &HttpRequest) -> HttpResponse {
         HttpResponse::build(StatusCode::NOT_FOUND).body(NOT_FOUND)
     }
 
+    /// Parses the optional 'scope' query parameter. Returns a response for
+    /// the user in case it can't be parsed.
+    fn scope(req: &HttpRequest) -> Result<ScopeLimits, HttpResponse> {
+        match req.query().get("scope") {
+            None => Ok(ScopeLimits::empty()),
+            Some(scope_str) => match ScopeLimits::from_str(scope_str) {
+                Ok(scope) => Ok(scope),
+                Err(_) => Err(Self::user_error("Can't parse scope")),
+            },
+        }
+    }
+
+    /// Parses the optional 'offset' and 'limit' query parameters. Returns a
+    /// response for the user in case they can't be parsed.
+    fn page(req: &HttpRequest) -> Result<Page, HttpResponse> {
+        let param = |name| match req.query().get(name) {
+            None => Ok(None),
+            Some(value) => match usize::from_str(value) {
+                Ok(value) => Ok(Some(value)),
+                Err(_) => Err(Self::user_error(&format!("Can't parse {}", name))),
+            },
+        };
+
+        Ok(Page {
+            offset: param("offset")?.unwrap_or(0),
+            limit: param("limit")?,
+        })
+    }
+
     fn details(req: &HttpRequest) -> HttpResponse {
         let server: &Arc<StatsServer> = req.state();
+        server.hits.hit("details");
+        let sources = server.sources();
 
-        let limits = match req.query().get("scope") {
-            None => ScopeLimits::empty(),
-            Some(scope_str) => match ScopeLimits::from_str(scope_str) {
-                Ok(scope) => scope,
-                Err(_) => return Self::user_error("Can't parse scope"),
-            },
+        let limits = match Self::scope(req) {
+            Ok(limits) => limits,
+            Err(res) => return res,
         };
 
-        let reporter = ResourceReporter::new(&server.sources.announcements, &server.sources.vrps);
+        let reporter = ResourceReporter::new(&sources.announcements, &sources.vrps);
 
         let stats = reporter.analyse(&limits);
 
         Self::render_json(&stats)
     }
 
-    fn world_json(req: &HttpRequest) -> HttpResponse {
+    fn asn(req: &HttpRequest) -> HttpResponse {
         let server: &Arc<StatsServer> = req.state();
-        let reporter = WorldStatsReporter::new(
-            &server.sources.announcements,
-            &server.sources.vrps,
-            &server.sources.delegations,
-        );
+        server.hits.hit("asn");
+        let sources = server.sources();
 
-        let stats = reporter.analyse();
+        let asn = match req.match_info().get("asn").map(Asn::from_str) {
+            Some(Ok(asn)) => asn,
+            _ => return Self::user_error("Can't parse ASN"),
+        };
 
-        Self::render_json(&stats)
+        let reporter = AsnReporter::new(&sources.announcements, &sources.vrps);
+
+        Self::render_json(&reporter.summary(asn))
+    }
+
+    fn prefix_lengths(req: &HttpRequest) -> HttpResponse {
+        let server: &Arc<StatsServer> = req.state();
+        server.hits.hit("prefix-lengths");
+        let sources = server.sources();
+
+        let limits = match Self::scope(req) {
+            Ok(limits) => limits,
+            Err(res) => return res,
+        };
+
+        let reporter = PrefixLengthReporter::new(&sources.announcements, &sources.vrps);
+
+        Self::render_json(&reporter.analyse(&limits))
+    }
+
+    /// Returns the VRPs in scope that are not seen in BGP, a page at a time.
+    fn unseen(req: &HttpRequest) -> HttpResponse {
+        let server: &Arc<StatsServer> = req.state();
+        server.hits.hit("unseen");
+        let sources = server.sources();
+
+        let limits = match Self::scope(req) {
+            Ok(limits) => limits,
+            Err(res) => return res,
+        };
+
+        let page = match Self::page(req) {
+            Ok(page) => page,
+            Err(res) => return res,
+        };
+
+        let reporter = ResourceReporter::new(&sources.announcements, &sources.vrps);
+        let visibility = reporter.visibility(&limits);
+
+        Self::render_json(&UnseenPage {
+            total: visibility.unseen().len(),
+            offset: page.offset,
+            unseen: page.apply(visibility.unseen()),
+        })
+    }
+
+    /// Returns the announced space in scope that would no longer be routed
+    /// if RPKI invalid announcements were rejected.
+    fn reject_impact(req: &HttpRequest) -> HttpResponse {
+        let server: &Arc<StatsServer> = req.state();
+        server.hits.hit("reject-impact");
+        let sources = server.sources();
+
+        let limits = match Self::scope(req) {
+            Ok(limits) => limits,
+            Err(res) => return res,
+        };
+
+        let mut reporter = RejectImpactReporter::new(&sources.announcements, &sources.vrps);
+
+        match req.query().get("withdrawn").map(String::as_str) {
+            None | Some("false") => {}
+            Some("true") => reporter = reporter.with_withdrawn(),
+            Some(_) => return Self::user_error("Can't parse withdrawn"),
+        }
+
+        Self::render_json(&reporter.analyse(&limits))
+    }
+
+    /// Reloads the sources from the same files as at startup, and returns
+    /// the number of announcements and VRPs loaded. Requests need the
+    /// reload token as a bearer token. Without a token, reloads over HTTP
+    /// are disabled.
+    ///
+    /// Loading full dumps takes a while, so it runs on its own thread, as
+    /// for SIGHUP. The worker keeps serving other requests in the meantime,
+    /// and answers this one when the reload is done.
+    fn reload(req: &HttpRequest) -> FutureResponse<HttpResponse> {
+        let server: &Arc<StatsServer> = req.state();
+        server.hits.hit("reload");
+
+        let token = match &server.opts.reload_token {
+            Some(token) => token,
+            None => {
+                return Box::new(future::ok(
+                    HttpResponse::build(StatusCode::FORBIDDEN).body("Reloading is not enabled"),
+                ))
+            }
+        };
+
+        let authorization = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or("");
+
+        if !is_same_secret(authorization, token) {
+            return Box::new(future::ok(
+                HttpResponse::build(StatusCode::UNAUTHORIZED)
+                    .header(header::WWW_AUTHENTICATE, "Bearer")
+                    .body("Missing or wrong reload token"),
+            ));
+        }
+
+        let (done, reloaded) = oneshot::channel();
+        let server = server.clone();
+        thread::spawn(move || {
+            let _ = done.send(server.reload());
+        });
+
+        Box::new(
+            reloaded
+                .map_err(actix_web::error::ErrorInternalServerError)
+                .map(|res| match res {
+                    Ok(summary) => Self::render_json(&summary),
+                    Err(e) => {
+                        error!("Reload failed, keeping the current sources: {}", e);
+                        HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
+                            .body(format!("Reload failed: {}", e))
+                    }
+                }),
+        )
+    }
+
+    /// Returns the validation state of the announcement given with the
+    /// 'prefix' and 'asn' query parameters, and the VRPs covering it.
+    fn validate(req: &HttpRequest) -> HttpResponse {
+        let server: &Arc<StatsServer> = req.state();
+        server.hits.hit("validate");
+        let sources = server.sources();
+
+        let prefix = match req.query().get("prefix").map(|s| IpPrefix::from_str(s)) {
+            Some(Ok(prefix)) => prefix,
+            _ => return Self::user_error("Can't parse prefix"),
+        };
+
+        let asn = match req.query().get("asn").map(|s| Asn::from_str(s)) {
+            Some(Ok(asn)) => asn,
+            _ => return Self::user_error("Can't parse ASN"),
+        };
+
+        let announcement = Announcement::new(prefix, asn);
+        let vrps = sources.vrps.containing(announcement.as_ref());
+        let validated = ValidatedAnnouncement::create(&announcement, &vrps);
+
+        Self::render_json(&Validation {
+            announcement: &announcement,
+            state: validated.state(),
+            vrps,
+        })
+    }
+
+    /// Returns the world stats as JSON or CSV, depending on the Accept
+    /// header. Defaults to JSON.
+    fn world(req: &HttpRequest) -> HttpResponse {
+        let server: &Arc<StatsServer> = req.state();
+        server.hits.hit("world");
+
+        let accept = req
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("");
+
+        match WorldFormat::negotiate(accept) {
+            WorldFormat::Json => Self::render_json(&server.sources().world),
+            WorldFormat::Csv => Self::render_csv(&server.sources().world),
+        }
+    }
+
+    fn world_json(req: &HttpRequest) -> HttpResponse {
+        let server: &Arc<StatsServer> = req.state();
+        server.hits.hit("world.json");
+        Self::render_json(&server.sources().world)
     }
 
     fn world_csv(req: &HttpRequest) -> HttpResponse {
         let server: &Arc<StatsServer> = req.state();
-        let reporter = WorldStatsReporter::new(
-            &server.sources.announcements,
-            &server.sources.vrps,
-            &server.sources.delegations,
-        );
+        server.hits.hit("world.csv");
+        Self::render_csv(&server.sources().world)
+    }
+
+    fn render_csv(stats: &CountryStats) -> HttpResponse {
+        HttpResponse::Ok()
+            .content_type("text/csv")
+            .body(stats.to_csv())
+    }
 
-        let stats = reporter.analyse();
-        let csv = stats.to_csv();
+    fn vrps_csv(req: &HttpRequest) -> HttpResponse {
+        let server: &Arc<StatsServer> = req.state();
+        server.hits.hit("vrps.csv");
+        let sources = server.sources();
+        let csv = sources.vrps.to_csv();
 
         HttpResponse::Ok().content_type("text/csv").body(csv)
     }
 
+    fn metrics(req: &HttpRequest) -> HttpResponse {
+        let server: &Arc<StatsServer> = req.state();
+        server.hits.hit("metrics");
+        let sources = server.sources();
+
+        HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4")
+            .body(format!(
+                "{}{}{}",
+                server.hits.to_prometheus(),
+                sources.coverage.to_prometheus(),
+                sources.world.to_prometheus()
+            ))
+    }
+
     fn render_json<O: Serialize>(obj: &O) -> HttpResponse {
         match serde_json::to_string(obj) {
             Ok(json) => HttpResponse::Ok()
@@ -200,6 +887,17 @@ impl StatsApp {
     }
 }
 
+/// Compares the given and expected secrets in time that does not depend on
+/// where they differ, so that the token cannot be guessed byte by byte.
+fn is_same_secret(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
 //------------ IntoHttpHandler -----------------------------------------------
 
 impl server::IntoHttpHandler for StatsApp {
@@ -216,24 +914,12 @@ pub type HttpRequest = actix_web::HttpRequest<Arc<StatsServer>>;
 
 //------------ Definition of Statics for UI content --------------------------
 
-static HTML: &[u8] = b"text/html";
-static CSS: &[u8] = b"text/css";
-static JS: &[u8] = b"application/javascript";
-static JSON: &[u8] = b"application/json";
-
 fn with_statics<S: 'static>(app: App<S>) -> App<S> {
-    statics!(app,
-        "world.html" => HTML,
-        "css/bootstrap.min.css" => CSS,
-        "css/d3.geomap.css" => CSS,
-        "js/axios.min.js" => JS,
-        "js/bootstrap.min.js" => JS,
-        "js/d3.geomap.dependencies.min.js" => JS,
-        "js/d3.geomap.min.js" => JS,
-        "js/jquery-3.3.1.min.js" => JS,
-        "js/vue.min.js" => JS,
-        "json/countries_with_iso2.json" => JSON,
-    )
+    statics::UI.iter().fold(app, |app, content| {
+        app.resource(&format!("/ui/{}", content.path), move |r| {
+            r.get().f(move |_| content);
+        })
+    })
 }
 
 //------------ Error --------------------------------------------------------
@@ -249,6 +935,20 @@ pub enum Error {
     #[display(fmt = "{}", _0)]
     DelegationsError(delegations::Error),
 
+    #[display(fmt = "{}", _0)]
+    ConfigError(config::Error),
+
+    #[display(fmt = "Cannot bind to unix socket {}: {}", _0, _1)]
+    UnixSocketError(String, io::Error),
+
+    #[display(fmt = "{}", _0)]
+    LoggingError(logging::Error),
+
+    #[display(
+        fmt = "The daemon cannot read its inputs from stdin ('-'), because it reads them again on reload"
+    )]
+    StdinInput,
+
     #[display(fmt = "{}", _0)]
     Other(String),
 }
@@ -257,6 +957,16 @@ impl Error {
     pub fn msg(msg: &str) -> Self {
         Error::Other(msg.to_string())
     }
+
+    fn unix_socket(path: &Path, e: io::Error) -> Self {
+        Error::UnixSocketError(path.to_string_lossy().to_string(), e)
+    }
+}
+
+impl From<logging::Error> for Error {
+    fn from(e: logging::Error) -> Self {
+        Error::LoggingError(e)
+    }
 }
 
 impl From<announcements::Error> for Error {
@@ -277,6 +987,12 @@ impl From<delegations::Error> for Error {
     }
 }
 
+impl From<config::Error> for Error {
+    fn from(e: config::Error) -> Self {
+        Error::ConfigError(e)
+    }
+}
+
 impl std::error::Error for Error {}
 
 impl actix_web::ResponseError for Error {
@@ -284,3 +1000,605 @@ impl actix_web::ResponseError for Error {
         HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR).body(format!("{}", self))
     }
 }
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use actix_web::Body;
+    use announcements::DEFAULT_MIN_PEERS;
+    use cli;
+
+    /// Returns the options for the given announcements and VRPs, with the
+    /// delegations from the small test set.
+    fn opts_with(announcements: &[&str], vrps: &str) -> ServerOpts {
+        ServerOpts {
+            announcements: announcements.iter().map(PathBuf::from).collect(),
+            min_peers: DEFAULT_MIN_PEERS,
+            vrps: PathBuf::from(vrps),
+            slurm: None,
+            dels: PathBuf::from("test/small/delegated-extended.txt"),
+            bind: SocketAddr::from_str("127.0.0.1:8080").unwrap(),
+            unix_socket: None,
+            log_format: LogFormat::Text,
+            denominator: AdoptionDenominator::default(),
+            reload_token: None,
+            progress: Progress::Silent,
+        }
+    }
+
+    fn server_with(announcements: &[&str], vrps: &str) -> Arc<StatsServer> {
+        Arc::new(StatsServer::create(&opts_with(announcements, vrps)).unwrap())
+    }
+
+    fn test_server() -> Arc<StatsServer> {
+        server_with(
+            &[
+                "test/small/riswhoisdump.IPv4",
+                "test/small/riswhoisdump.IPv6",
+            ],
+            "test/small/vrps.csv",
+        )
+    }
+
+    fn body_string(res: &HttpResponse) -> String {
+        match res.body() {
+            Body::Binary(bin) => String::from_utf8(bin.as_ref().to_vec()).unwrap(),
+            _ => panic!("Expected binary body"),
+        }
+    }
+
+    #[test]
+    fn should_export_vrps_csv() {
+        let req = TestRequest::with_state(test_server()).finish();
+        let res = StatsApp::vrps_csv(&req);
+
+        assert_eq!(StatusCode::OK, res.status());
+        let body = body_string(&res);
+        assert!(body.starts_with("ASN,IP Prefix,Max Length\n"));
+        assert!(body.contains("AS65003,192.168.1.0/24,24\n"));
+        assert_eq!(4, body.lines().count());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_serve_on_unix_socket() {
+        use std::io::Read;
+        use std::io::Write as IoWrite;
+        use std::os::unix::net::UnixStream;
+        use std::sync::mpsc;
+        use std::thread;
+
+        let path = ::std::env::temp_dir().join("secure_routing_stats_test.sock");
+        let server = test_server();
+
+        let (tx, rx) = mpsc::channel();
+        let thread_path = path.clone();
+        let handle = thread::spawn(move || {
+            let sys = actix::System::new("test");
+            let socket = StatsApp::start_unix(server, &thread_path).unwrap();
+            tx.send(actix::System::current()).unwrap();
+            sys.run();
+            drop(socket);
+        });
+        let system = rx.recv().unwrap();
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("rpki_stats_requests_total{endpoint=\"metrics\"} 1"));
+
+        system.stop();
+        handle.join().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_only_replace_stale_unix_socket() {
+        use std::os::unix::net::UnixListener as StdUnixListener;
+
+        let path = ::std::env::temp_dir().join("secure_routing_stats_stale_test.sock");
+        let _ = fs::remove_file(&path);
+
+        let listener = StdUnixListener::bind(&path).unwrap();
+        match StatsApp::start_unix(test_server(), &path) {
+            Err(Error::UnixSocketError(_, e)) => assert_eq!(io::ErrorKind::AddrInUse, e.kind()),
+            _ => panic!("Should not replace a socket in use"),
+        }
+        assert!(path.exists());
+
+        // The file stays behind when the listener is closed, as when a
+        // server is killed.
+        drop(listener);
+        assert!(path.exists());
+
+        let _sys = actix::System::new("test");
+        let socket = StatsApp::start_unix(test_server(), &path).unwrap();
+        drop(socket);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn should_negotiate_world_format() {
+        let get = |accept: Option<&str>| {
+            let mut req = TestRequest::with_state(test_server());
+            if let Some(accept) = accept {
+                req = req.header(header::ACCEPT, accept);
+            }
+            let res = StatsApp::world(&req.finish());
+            assert_eq!(StatusCode::OK, res.status());
+            let content_type = res.headers()[header::CONTENT_TYPE]
+                .to_str()
+                .unwrap()
+                .to_string();
+            (content_type, body_string(&res))
+        };
+
+        let (json_type, json) = get(Some("application/json"));
+        assert_eq!("application/json", json_type);
+        assert!(json.starts_with('{'));
+
+        let (csv_type, csv) = get(Some("text/csv"));
+        assert_eq!("text/csv", csv_type);
+        assert_eq!(test_server().sources().world.to_csv(), csv);
+
+        assert_eq!("application/json", get(None).0);
+        assert_eq!("application/json", get(Some("*/*")).0);
+        assert_eq!("application/json", get(Some("text/html")).0);
+        assert_eq!("text/csv", get(Some("application/json;q=0.5, text/csv")).0);
+        assert_eq!("application/json", get(Some("text/csv;q=0.2, */*;q=0.8")).0);
+    }
+
+    #[test]
+    fn should_compress_responses() {
+        use actix_web::test::TestServer;
+        use actix_web::HttpMessage;
+
+        let server = test_server();
+        let mut srv = TestServer::with_factory(move || StatsApp::new(server.clone()));
+
+        let mut encoding = |path: &str, accept: Option<&str>| {
+            let mut req = srv.get();
+            req.uri(srv.url(path)).no_default_headers();
+            if let Some(accept) = accept {
+                req.header(header::ACCEPT_ENCODING, accept);
+            }
+            let res = srv.execute(req.finish().unwrap().send()).unwrap();
+            assert!(res.status().is_success());
+            res.headers()
+                .get(header::CONTENT_ENCODING)
+                .map(|value| value.to_str().unwrap().to_string())
+        };
+
+        let gzip = Some("gzip".to_string());
+        assert_eq!(gzip, encoding("/rpki-stats-api/world.json", Some("gzip")));
+        assert_eq!(gzip, encoding("/rpki-stats-api/details", Some("gzip")));
+        assert_eq!(gzip, encoding("/ui/world.html", Some("gzip")));
+        assert_eq!(
+            Some("deflate".to_string()),
+            encoding("/rpki-stats-api/world.csv", Some("deflate"))
+        );
+        assert_eq!(None, encoding("/rpki-stats-api/world.json", None));
+    }
+
+    #[test]
+    fn should_return_cached_world_stats() {
+        let server = test_server();
+        let get = || {
+            let req = TestRequest::with_state(server.clone()).finish();
+            body_string(&StatsApp::world_json(&req))
+        };
+
+        let first = get();
+        assert_eq!(first, get());
+        assert_eq!(
+            serde_json::to_string(&server.sources().world).unwrap(),
+            first
+        );
+        assert_eq!(Some(2), server.hits.get("world.json"));
+    }
+
+    #[test]
+    fn should_count_endpoint_hits() {
+        let server = test_server();
+        assert_eq!(Some(0), server.hits.get("vrps.csv"));
+
+        let req = TestRequest::with_state(server.clone()).finish();
+        StatsApp::vrps_csv(&req);
+        StatsApp::vrps_csv(&req);
+        assert_eq!(Some(2), server.hits.get("vrps.csv"));
+        assert_eq!(Some(0), server.hits.get("details"));
+
+        let res = StatsApp::metrics(&req);
+        let body = body_string(&res);
+        assert!(body.contains("rpki_stats_requests_total{endpoint=\"vrps.csv\"} 2\n"));
+        assert!(body.contains("rpki_stats_requests_total{endpoint=\"metrics\"} 1\n"));
+        assert!(body.contains("# TYPE rpki_stats_coverage_ratio gauge\n"));
+        assert!(body.contains("# TYPE rpki_stats_routes_valid gauge\n"));
+        assert!(body.contains("rpki_stats_routes_valid{cc=\"all\"} 2\n"));
+        assert!(body.contains("rpki_stats_routes_not_found{cc=\"all\"} 2\n"));
+    }
+
+    #[test]
+    fn should_reject_stdin_inputs() {
+        let parse = |announcements: &str, vrps: &str, dels: &str, slurm: &str| {
+            let args = [
+                "daemon",
+                "--announcements",
+                "test/small/riswhoisdump.IPv4",
+                announcements,
+                "--vrps",
+                vrps,
+                "--delegations",
+                dels,
+                "--slurm",
+                slurm,
+            ];
+            ServerOpts::parse(&cli::subcommand_matches(&args))
+        };
+
+        let (ris, vrps, dels, slurm) = (
+            "test/small/riswhoisdump.IPv6",
+            "test/small/vrps.csv",
+            "test/small/delegated-extended.txt",
+            "test/small/slurm.json",
+        );
+        assert!(parse(ris, vrps, dels, slurm).is_ok());
+        for args in &[
+            ("-", vrps, dels, slurm),
+            (ris, "-", dels, slurm),
+            (ris, vrps, "-", slurm),
+            (ris, vrps, dels, "-"),
+        ] {
+            match parse(args.0, args.1, args.2, args.3) {
+                Err(Error::StdinInput) => {}
+                Err(e) => panic!("Expected stdin error for {:?}, got: {}", args, e),
+                Ok(_) => panic!("Expected stdin error for {:?}", args),
+            }
+        }
+    }
+
+    #[test]
+    fn should_reload_sources() {
+        let dir = ::std::env::temp_dir().join(format!("reload-{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let vrps = dir.join("vrps.csv");
+        fs::copy("test/small/vrps.csv", &vrps).unwrap();
+
+        let mut opts = opts_with(
+            &[
+                "test/small/riswhoisdump.IPv4",
+                "test/small/riswhoisdump.IPv6",
+            ],
+            vrps.to_str().unwrap(),
+        );
+        opts.reload_token = Some("secret".to_string());
+        let server = Arc::new(StatsServer::create(&opts).unwrap());
+
+        let reload = |authorization: Option<&str>| {
+            let mut req = TestRequest::with_state(server.clone());
+            if let Some(authorization) = authorization {
+                req = req.header(header::AUTHORIZATION, authorization);
+            }
+            let res = StatsApp::reload(&req.finish()).wait().unwrap();
+            (res.status(), body_string(&res))
+        };
+
+        assert_eq!(StatusCode::UNAUTHORIZED, reload(None).0);
+        assert_eq!(StatusCode::UNAUTHORIZED, reload(Some("Bearer wrong")).0);
+        assert_eq!(StatusCode::UNAUTHORIZED, reload(Some("secret")).0);
+
+        // Requests in flight keep the sources they started with
+        let before = server.sources();
+        fs::write(&vrps, "ASN,IP Prefix,Max Length\nAS65000,10.0.0.0/16,24\n").unwrap();
+        assert_eq!(
+            (
+                StatusCode::OK,
+                "{\"announcements\":7,\"vrps\":1}".to_string()
+            ),
+            reload(Some("Bearer secret"))
+        );
+        assert_eq!(3, before.vrps.iter().count());
+        assert_eq!(1, server.sources().vrps.iter().count());
+        assert_ne!(before.world, server.sources().world);
+
+        // A failed reload keeps the current sources
+        fs::write(&vrps, "").unwrap();
+        assert_eq!(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            reload(Some("Bearer secret")).0
+        );
+        assert_eq!(1, server.sources().vrps.iter().count());
+        assert_eq!(Some(5), server.hits.get("reload"));
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        let req = TestRequest::with_state(test_server()).finish();
+        let res = StatsApp::reload(&req).wait().unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, res.status());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn should_serve_requests_while_reloading() {
+        let dir = ::std::env::temp_dir().join(format!("reload-busy-{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let vrps = dir.join("vrps.csv");
+        fs::copy("test/small/vrps.csv", &vrps).unwrap();
+
+        let mut opts = opts_with(&["test/small/riswhoisdump.IPv4"], vrps.to_str().unwrap());
+        opts.reload_token = Some("secret".to_string());
+        let server = Arc::new(StatsServer::create(&opts).unwrap());
+
+        // Reading the VRPs from a FIFO blocks the reload until they are
+        // written below.
+        fs::remove_file(&vrps).unwrap();
+        let mkfifo = ::std::process::Command::new("mkfifo")
+            .arg(&vrps)
+            .status()
+            .unwrap();
+        assert!(mkfifo.success());
+
+        let req = TestRequest::with_state(server.clone())
+            .header(header::AUTHORIZATION, "Bearer secret")
+            .finish();
+        let reloading = StatsApp::reload(&req);
+
+        let state = || {
+            let uri = "/rpki-stats-api/validate?prefix=10.0.0.0/16&asn=AS65000";
+            let req = TestRequest::with_state(server.clone()).uri(uri).finish();
+            let res = StatsApp::validate(&req);
+            assert_eq!(StatusCode::OK, res.status());
+            let json: serde_json::Value = serde_json::from_str(&body_string(&res)).unwrap();
+            json["state"].as_str().unwrap().to_string()
+        };
+        assert_eq!("Valid", state());
+
+        fs::write(&vrps, "ASN,IP Prefix,Max Length\nAS65001,10.0.0.0/16,16\n").unwrap();
+        let res = reloading.wait().unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("InvalidAsn", state());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn should_validate_announcement() {
+        let get = |query: &str| {
+            let uri = format!("/rpki-stats-api/validate?{}", query);
+            let req = TestRequest::with_state(test_server()).uri(&uri).finish();
+            let res = StatsApp::validate(&req);
+            (res.status(), body_string(&res))
+        };
+
+        let vrp =
+            "{\"asn\":\"AS65000\",\"prefix\":\"10.0.0.0/16\",\"max_length\":16,\"ta\":\"ripe\"}";
+
+        assert_eq!(
+            (
+                StatusCode::OK,
+                format!(
+                    "{{\"announcement\":{{\"asn\":\"AS65000\",\"prefix\":\"10.0.0.0/16\"}},\
+                     \"state\":\"Valid\",\"vrps\":[{}]}}",
+                    vrp
+                )
+            ),
+            get("prefix=10.0.0.0/16&asn=AS65000")
+        );
+        assert_eq!(
+            (
+                StatusCode::OK,
+                format!(
+                    "{{\"announcement\":{{\"asn\":\"AS65000\",\"prefix\":\"10.0.1.0/24\"}},\
+                     \"state\":\"InvalidLength\",\"vrps\":[{}]}}",
+                    vrp
+                )
+            ),
+            get("prefix=10.0.1.0/24&asn=AS65000")
+        );
+        assert_eq!(
+            (
+                StatusCode::OK,
+                format!(
+                    "{{\"announcement\":{{\"asn\":\"AS65001\",\"prefix\":\"10.0.0.0/16\"}},\
+                     \"state\":\"InvalidAsn\",\"vrps\":[{}]}}",
+                    vrp
+                )
+            ),
+            get("prefix=10.0.0.0/16&asn=AS65001")
+        );
+        assert_eq!(
+            (
+                StatusCode::OK,
+                "{\"announcement\":{\"asn\":\"AS65000\",\"prefix\":\"172.16.0.0/24\"},\
+                 \"state\":\"NotFound\",\"vrps\":[]}"
+                    .to_string()
+            ),
+            get("prefix=172.16.0.0/24&asn=AS65000")
+        );
+
+        let bad_request = |query| get(query).0 == StatusCode::BAD_REQUEST;
+        assert!(bad_request("prefix=10.0.0.0/33&asn=AS65000"));
+        assert!(bad_request("prefix=10.0.0.0/16&asn=65000x"));
+        assert!(bad_request("asn=AS65000"));
+        assert!(bad_request("prefix=10.0.0.0/16"));
+    }
+
+    #[test]
+    fn should_summarise_asn() {
+        let get = |asn| {
+            let req = TestRequest::with_state(test_server())
+                .param("asn", asn)
+                .finish();
+            let res = StatsApp::asn(&req);
+            (res.status(), body_string(&res))
+        };
+
+        assert_eq!(
+            (
+                StatusCode::OK,
+                "{\"asn\":\"AS65000\",\"valid\":2,\"invalid_asn\":0,\"invalid_length\":1,\
+                 \"not_found\":0,\"invalids\":[{\"announcement\":\
+                 {\"asn\":\"AS65000\",\"prefix\":\"10.0.1.0/24\"},\"state\":\"InvalidLength\",\
+                 \"covering_vrp\":{\"asn\":\"AS65000\",\"prefix\":\"10.0.0.0/16\",\
+                 \"max_length\":16,\"ta\":\"ripe\"}}],\
+                 \"not_found_prefixes\":[]}"
+                    .to_string()
+            ),
+            get("AS65000")
+        );
+
+        assert_eq!(
+            (
+                StatusCode::OK,
+                "{\"asn\":\"AS65001\",\"valid\":0,\"invalid_asn\":2,\"invalid_length\":0,\
+                 \"not_found\":1,\"invalids\":[{\"announcement\":\
+                 {\"asn\":\"AS65001\",\"prefix\":\"10.0.2.0/24\"},\"state\":\"InvalidAsn\",\
+                 \"expected_asns\":[\"AS65000\"],\
+                 \"covering_vrp\":{\"asn\":\"AS65000\",\"prefix\":\"10.0.0.0/16\",\
+                 \"max_length\":16,\"ta\":\"ripe\"}},\
+                 {\"announcement\":{\"asn\":\"AS65001\",\"prefix\":\"2001:db8:1::/48\"},\
+                 \"state\":\"InvalidAsn\",\"expected_asns\":[\"AS65000\"],\
+                 \"covering_vrp\":{\"asn\":\"AS65000\",\"prefix\":\"2001:db8::/32\",\
+                 \"max_length\":32,\"ta\":\"ripe\"}}],\
+                 \"not_found_prefixes\":[\"192.168.0.0/16\"]}"
+                    .to_string()
+            ),
+            get("65001")
+        );
+
+        assert_eq!(StatusCode::BAD_REQUEST, get("AS-foo").0);
+    }
+
+    #[test]
+    fn should_report_prefix_lengths() {
+        let get = |uri| {
+            let req = TestRequest::with_state(test_server()).uri(uri).finish();
+            let res = StatsApp::prefix_lengths(&req);
+            assert_eq!(StatusCode::OK, res.status());
+            body_string(&res)
+        };
+
+        assert_eq!(
+            "{\"announcements\":{\"v4\":{\"16\":2,\"24\":3},\"v6\":{\"32\":1,\"48\":1}},\
+             \"vrps\":{\"v4\":{\"16\":1,\"24\":1},\"v6\":{\"32\":1}}}",
+            get("/rpki-stats-api/prefix-lengths")
+        );
+
+        assert_eq!(
+            "{\"announcements\":{\"v4\":{\"16\":1,\"24\":2},\"v6\":{}},\
+             \"vrps\":{\"v4\":{\"16\":1},\"v6\":{}}}",
+            get("/rpki-stats-api/prefix-lengths?scope=10.0.0.0/8")
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_prefix_lengths_scope() {
+        let req = TestRequest::with_state(test_server())
+            .uri("/rpki-stats-api/prefix-lengths?scope=10.0.0.0/99")
+            .finish();
+        let res = StatsApp::prefix_lengths(&req);
+
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+    }
+
+    #[test]
+    fn should_report_reject_impact() {
+        let server = server_with(&["test/reject/riswhoisdump.IPv4"], "test/reject/vrps.csv");
+        let get = |uri| {
+            let req = TestRequest::with_state(server.clone()).uri(uri).finish();
+            let res = StatsApp::reject_impact(&req);
+            (res.status(), body_string(&res))
+        };
+
+        assert_eq!(
+            (
+                StatusCode::OK,
+                "{\"invalids\":3,\"unreachable\":[\"10.0.1.0/24\"]}".to_string()
+            ),
+            get("/rpki-stats-api/reject-impact")
+        );
+        assert_eq!(
+            (
+                StatusCode::OK,
+                "{\"invalids\":2,\"unreachable\":[]}".to_string()
+            ),
+            get("/rpki-stats-api/reject-impact?scope=172.16.0.0/16")
+        );
+        assert_eq!(
+            (
+                StatusCode::OK,
+                "{\"invalids\":3,\"unreachable\":[\"10.0.1.0/24\"],\"withdrawn\":[\
+                 {\"prefix\":\"10.0.0.0/23\",\"origin\":\"AS65001\",\"state\":\"InvalidAsn\"}]}"
+                    .to_string()
+            ),
+            get("/rpki-stats-api/reject-impact?withdrawn=true")
+        );
+        assert_eq!(
+            StatusCode::BAD_REQUEST,
+            get("/rpki-stats-api/reject-impact?withdrawn=yes").0
+        );
+        assert_eq!(
+            StatusCode::BAD_REQUEST,
+            get("/rpki-stats-api/reject-impact?scope=10.0.0.0/99").0
+        );
+    }
+
+    #[test]
+    fn should_list_unseen_vrps() {
+        let get = |uri| {
+            let req = TestRequest::with_state(test_server()).uri(uri).finish();
+            let res = StatsApp::unseen(&req);
+            (res.status(), body_string(&res))
+        };
+
+        let unseen = "{\"asn\":\"AS65003\",\"prefix\":\"192.168.1.0/24\",\"max_length\":24,\
+                      \"ta\":\"arin\"}";
+
+        assert_eq!(
+            (
+                StatusCode::OK,
+                format!("{{\"total\":1,\"offset\":0,\"unseen\":[{}]}}", unseen)
+            ),
+            get("/rpki-stats-api/unseen?scope=192.168.0.0/16")
+        );
+        assert_eq!(
+            (
+                StatusCode::OK,
+                "{\"total\":0,\"offset\":0,\"unseen\":[]}".to_string()
+            ),
+            get("/rpki-stats-api/unseen?scope=10.0.0.0/8")
+        );
+        assert_eq!(
+            (
+                StatusCode::OK,
+                "{\"total\":1,\"offset\":1,\"unseen\":[]}".to_string()
+            ),
+            get("/rpki-stats-api/unseen?offset=1&limit=10")
+        );
+        assert_eq!(
+            StatusCode::BAD_REQUEST,
+            get("/rpki-stats-api/unseen?limit=ten").0
+        );
+    }
+
+    #[test]
+    fn should_apply_page() {
+        let items = [1, 2, 3, 4, 5];
+        let page = |offset, limit| Page { offset, limit }.apply(&items).to_vec();
+
+        assert_eq!(vec![1, 2, 3, 4, 5], page(0, None));
+        assert_eq!(vec![2, 3], page(1, Some(2)));
+        assert_eq!(vec![4, 5], page(3, Some(10)));
+        assert_eq!(Vec::<i32>::new(), page(7, Some(2)));
+        assert_eq!(Vec::<i32>::new(), page(0, Some(0)));
+    }
+}
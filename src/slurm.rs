@@ -0,0 +1,233 @@
+//! Parse SLURM (RFC 8416) files with local exceptions to the validated
+//! ROA payloads. Only the prefix filters and assertions are used, BGPsec
+//! filters and assertions are ignored.
+use crate::ip::Asn;
+use crate::ip::IpPrefix;
+use crate::ip::IpPrefixError;
+use crate::vrps::ValidatedRoaPayload;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+//------------ PrefixFilter --------------------------------------------------
+
+/// Removes all VRPs with a prefix matching or more specific than the given
+/// prefix, and/or with the given ASN.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PrefixFilter {
+    prefix: Option<IpPrefix>,
+    asn: Option<Asn>,
+}
+
+impl PrefixFilter {
+    pub fn matches(&self, vrp: &ValidatedRoaPayload) -> bool {
+        let prefix_matches = match &self.prefix {
+            Some(prefix) => prefix.as_ref().contains(&vrp.as_ref().to_range()),
+            None => true,
+        };
+        let asn_matches = match self.asn {
+            Some(asn) => asn == vrp.asn(),
+            None => true,
+        };
+        prefix_matches && asn_matches
+    }
+}
+
+//------------ Slurm ---------------------------------------------------------
+
+#[derive(Clone, Debug, Default)]
+pub struct Slurm {
+    filters: Vec<PrefixFilter>,
+    assertions: Vec<ValidatedRoaPayload>,
+}
+
+impl Slurm {
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let content = fs::read_to_string(path)
+            .map_err(|_| Error::CannotRead(path.to_string_lossy().to_string()))?;
+        Self::from_str(&content)
+    }
+
+    pub fn filters(&self) -> &Vec<PrefixFilter> {
+        &self.filters
+    }
+
+    pub fn assertions(&self) -> &Vec<ValidatedRoaPayload> {
+        &self.assertions
+    }
+
+    /// Returns whether the VRP should be dropped because of a filter.
+    pub fn is_filtered(&self, vrp: &ValidatedRoaPayload) -> bool {
+        self.filters.iter().any(|filter| filter.matches(vrp))
+    }
+}
+
+impl FromStr for Slurm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let file: SlurmFile =
+            serde_json::from_str(s).map_err(|e| Error::ParseError(e.to_string()))?;
+
+        if file.slurm_version != 1 {
+            return Err(Error::UnsupportedVersion(file.slurm_version));
+        }
+
+        let mut filters = vec![];
+        for filter in file.validation_output_filters.prefix_filters {
+            if filter.prefix.is_none() && filter.asn.is_none() {
+                return Err(Error::EmptyFilter);
+            }
+            let prefix = match filter.prefix {
+                Some(prefix) => Some(IpPrefix::from_str(&prefix)?),
+                None => None,
+            };
+            let asn = filter.asn.map(Asn::from);
+            filters.push(PrefixFilter { prefix, asn });
+        }
+
+        let mut assertions = vec![];
+        for assertion in file.locally_added_assertions.prefix_assertions {
+            let prefix = IpPrefix::from_str(&assertion.prefix)?;
+            let max_length = assertion
+                .max_prefix_length
+                .unwrap_or_else(|| prefix.length());
+            let asn = Asn::from(assertion.asn);
+            assertions.push(ValidatedRoaPayload::new(asn, prefix, max_length));
+        }
+
+        Ok(Slurm {
+            filters,
+            assertions,
+        })
+    }
+}
+
+//------------ SlurmFile -----------------------------------------------------
+
+/// The JSON structure of a SLURM file, before prefixes are parsed.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SlurmFile {
+    slurm_version: u32,
+    validation_output_filters: OutputFilters,
+    locally_added_assertions: LocalAssertions,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OutputFilters {
+    prefix_filters: Vec<PrefixFilterEntry>,
+}
+
+#[derive(Deserialize)]
+struct PrefixFilterEntry {
+    prefix: Option<String>,
+    asn: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LocalAssertions {
+    prefix_assertions: Vec<PrefixAssertionEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PrefixAssertionEntry {
+    asn: u32,
+    prefix: String,
+    max_prefix_length: Option<u8>,
+}
+
+//------------ Error --------------------------------------------------------
+
+#[derive(Debug, Display)]
+pub enum Error {
+    #[display(fmt = "Cannot read SLURM file: {}", _0)]
+    CannotRead(String),
+
+    #[display(fmt = "Error parsing SLURM file: {}", _0)]
+    ParseError(String),
+
+    #[display(fmt = "Unsupported SLURM version: {}", _0)]
+    UnsupportedVersion(u32),
+
+    #[display(fmt = "SLURM prefix filter must have a prefix and/or an asn")]
+    EmptyFilter,
+}
+
+impl From<IpPrefixError> for Error {
+    fn from(e: IpPrefixError) -> Self {
+        Error::ParseError(e.to_string())
+    }
+}
+
+//------------ Tests --------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::announcements::Announcement;
+    use crate::validation::ValidatedAnnouncement;
+    use crate::validation::ValidationState;
+    use crate::vrps::Vrps;
+    use std::path::PathBuf;
+
+    fn state(vrps: &Vrps, prefix: &str, asn: &str) -> ValidationState {
+        let ann = Announcement::new(
+            IpPrefix::from_str(prefix).unwrap(),
+            Asn::from_str(asn).unwrap(),
+        );
+        let matching = vrps.containing(ann.as_ref());
+        ValidatedAnnouncement::create(&ann, &matching)
+            .state()
+            .clone()
+    }
+
+    #[test]
+    fn should_apply_filters_and_assertions() {
+        let slurm = Slurm::from_file(&PathBuf::from("test/small/slurm.json")).unwrap();
+        let vrps = Vrps::from_file(&PathBuf::from("test/small/vrps.csv")).unwrap();
+
+        assert_eq!(
+            ValidationState::Valid,
+            state(&vrps, "10.0.0.0/16", "AS65000")
+        );
+        assert_eq!(
+            ValidationState::NotFound,
+            state(&vrps, "172.16.0.0/24", "AS65002")
+        );
+
+        let vrps = vrps.apply_slurm(&slurm);
+
+        assert_eq!(
+            ValidationState::NotFound,
+            state(&vrps, "10.0.0.0/16", "AS65000")
+        );
+        assert_eq!(
+            ValidationState::Valid,
+            state(&vrps, "172.16.0.0/24", "AS65002")
+        );
+        assert_eq!(
+            ValidationState::Valid,
+            state(&vrps, "2001:db8::/32", "AS65000")
+        );
+        assert_eq!(3, vrps.all().len());
+    }
+
+    #[test]
+    fn should_reject_invalid_files() {
+        assert!(Slurm::from_str("{}").is_err());
+
+        let empty_filter = r#"{
+            "slurmVersion": 1,
+            "validationOutputFilters": { "prefixFilters": [ { "comment": "x" } ], "bgpsecFilters": [] },
+            "locallyAddedAssertions": { "prefixAssertions": [], "bgpsecAssertions": [] }
+        }"#;
+        match Slurm::from_str(empty_filter) {
+            Err(Error::EmptyFilter) => {}
+            _ => panic!("Expected EmptyFilter error"),
+        }
+    }
+}
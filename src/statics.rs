@@ -3,37 +3,54 @@
 use actix_web::{Error, HttpRequest, HttpResponse, Responder};
 use actix_web::http::StatusCode;
 
-/// Register ui resources at compile time. Specify the app first, and
-/// then for each resource:
-/// * the path
+/// Include ui resources at compile time, as a slice of StaticContent.
+/// For each resource specify:
+/// * the path, relative to the ui directory
 /// * the mime type
-/// * an etag value
 ///
-/// Note the etag cannot be dynamically derived at compile time until
-/// 'const fn' are stable. So, be sure to use a value here that is unique
-/// to this resource, and this version of this resource. E.g. calculate its
-/// hash on the command line and use that in a &str.
-#[allow(unused_macros)]
+/// The resources are served under /ui/ by the daemon, and written to disk
+/// with the world report, so they only need to be listed once, in UI.
 macro_rules! statics {
-    ( $app:expr, $( $path:expr => $mime:expr, )* ) => {{
-        $app
-        $(
-            .resource(concat!("/ui/", $path), |r| {
-                static CONTENT: ::statics::StaticContent
-                    = ::statics::StaticContent {
-                        content: include_bytes!(concat!("../ui/",$path)),
-                        ctype:   $mime
-                };
-                r.get().f(|_| &CONTENT)
-            })
-        )*
-    }}
+    ( $( $path:expr => $mime:expr, )* ) => {
+        &[
+            $(
+                ::statics::StaticContent {
+                    path:    $path,
+                    content: include_bytes!(concat!("../ui/",$path)),
+                    ctype:   $mime
+                },
+            )*
+        ]
+    }
 }
 
 
+//------------ UI ------------------------------------------------------------
+
+static HTML: &[u8] = b"text/html";
+static CSS: &[u8] = b"text/css";
+static JS: &[u8] = b"application/javascript";
+static JSON: &[u8] = b"application/json";
+
+/// All ui resources, used by the world map.
+pub static UI: &[StaticContent] = statics!(
+    "world.html" => HTML,
+    "css/bootstrap.min.css" => CSS,
+    "css/d3.geomap.css" => CSS,
+    "js/axios.min.js" => JS,
+    "js/bootstrap.min.js" => JS,
+    "js/d3.geomap.dependencies.min.js" => JS,
+    "js/d3.geomap.min.js" => JS,
+    "js/jquery-3.3.1.min.js" => JS,
+    "js/vue.min.js" => JS,
+    "json/countries_with_iso2.json" => JSON,
+);
+
+
 //------------ StaticContent -------------------------------------------------
 
 pub struct StaticContent {
+    pub path: &'static str,
     pub content: &'static [u8],
     pub ctype: &'static [u8],
 }
@@ -1,9 +1,30 @@
 use crate::announcements::Announcement;
+use crate::ip::Asn;
 use crate::vrps::ValidatedRoaPayload;
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::fmt;
 
 
+//------------ ValidationPolicy ---------------------------------------------
+
+/// How strictly announcements are validated. Some ROV implementations treat
+/// the max length as advisory, in which case only the ASN has to match.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ValidationPolicy {
+    #[default]
+    Strict,
+    IgnoreMaxLength
+}
+
+impl ValidationPolicy {
+    fn allows_length(self, vrp: &ValidatedRoaPayload, ann: &Announcement) -> bool {
+        self == ValidationPolicy::IgnoreMaxLength
+            || ann.prefix().length() <= vrp.max_length()
+    }
+}
+
+
 //------------ ValidationState ----------------------------------------------
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize)]
@@ -33,50 +54,114 @@ impl Display for ValidationState {
 #[derive(Clone, Debug, Serialize)]
 pub struct ValidatedAnnouncement {
     announcement: Announcement,
-    state: ValidationState
+    state: ValidationState,
+
+    /// The origins of the covering VRPs, if the state is InvalidAsn. This
+    /// tells operators which origin RPKI expects for the announcement.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    expected_asns: Vec<Asn>,
+
+    /// The covering VRP that decided the state, if the announcement is
+    /// invalid: the first VRP for the same origin with a max length that is
+    /// too short, or else the first VRP for another origin.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    covering_vrp: Option<ValidatedRoaPayload>
 }
 
 impl ValidatedAnnouncement {
+    pub fn announcement(&self) -> &Announcement {
+        &self.announcement
+    }
+
     pub fn state(&self) -> &ValidationState {
         &self.state
     }
 
-    fn derive_state(
+    /// Returns the origins of the covering VRPs, sorted and without
+    /// duplicates, if the state is InvalidAsn. Empty for other states.
+    pub fn expected_asns(&self) -> &[Asn] {
+        &self.expected_asns
+    }
+
+    /// Returns the VRP that made the announcement invalid, e.g. to show
+    /// the origin or the max length that it has. None for valid and not
+    /// found announcements.
+    pub fn covering_vrp(&self) -> Option<&ValidatedRoaPayload> {
+        self.covering_vrp.as_ref()
+    }
+
+    /// Returns the origins of the VRPs covering the announcement.
+    fn derive_expected_asns(
         ann: &Announcement,
         vrps: &[&ValidatedRoaPayload]
-    ) -> ValidationState {
+    ) -> Vec<Asn> {
+        let mut asns: Vec<Asn> = vrps.iter()
+            .filter(|vrp| vrp.contains(ann.as_ref()))
+            .map(|vrp| vrp.asn())
+            .collect();
+        asns.sort();
+        asns.dedup();
+        asns
+    }
+
+    /// Returns the state, and for invalids the VRP that decided it.
+    fn derive_state<'a>(
+        ann: &Announcement,
+        vrps: &[&'a ValidatedRoaPayload],
+        policy: ValidationPolicy
+    ) -> (ValidationState, Option<&'a ValidatedRoaPayload>) {
         let mut state = ValidationState::NotFound;
+        let mut deciding = None;
 
         for vrp in vrps {
             if vrp.contains(ann.as_ref()) {
                 if vrp.asn() != ann.asn() {
-                    if state != ValidationState::InvalidLength {
+                    if state == ValidationState::NotFound {
                         state = ValidationState::InvalidAsn;
+                        deciding = Some(*vrp);
                     }
                     continue
                 }
 
-                if ann.prefix().length() > vrp.max_length() {
-                    state = ValidationState::InvalidLength;
+                if !policy.allows_length(vrp, ann) {
+                    if state != ValidationState::InvalidLength {
+                        state = ValidationState::InvalidLength;
+                        deciding = Some(*vrp);
+                    }
                     continue
                 }
 
-                return ValidationState::Valid
+                return (ValidationState::Valid, None)
             }
         }
 
-        state
+        (state, deciding)
     }
 
     /// Creates a validated announcement for the referenced announcement, and
     /// validated roa prefixes. Takes references because this stuff is kept
     /// in immutable IntervalTree structures.
     pub fn create(ann: &Announcement, vrps: &[&ValidatedRoaPayload]) -> Self {
-        let state = Self::derive_state(ann, vrps);
+        Self::create_with_policy(ann, vrps, ValidationPolicy::Strict)
+    }
+
+    /// Creates a validated announcement, using the given policy.
+    pub fn create_with_policy(
+        ann: &Announcement,
+        vrps: &[&ValidatedRoaPayload],
+        policy: ValidationPolicy
+    ) -> Self {
+        let (state, deciding) = Self::derive_state(ann, vrps, policy);
+        let expected_asns = match state {
+            ValidationState::InvalidAsn => Self::derive_expected_asns(ann, vrps),
+            _ => vec![]
+        };
 
         ValidatedAnnouncement {
             announcement: ann.clone(),
-            state
+            state,
+            expected_asns,
+            covering_vrp: deciding.cloned()
         }
     }
 }
@@ -89,31 +174,127 @@ impl Display for ValidatedAnnouncement {
             self.announcement.asn(),
             self.announcement.prefix(),
             self.state
-        )
+        )?;
+        if !self.expected_asns.is_empty() {
+            let asns: Vec<String> = self.expected_asns.iter()
+                .map(Asn::to_string)
+                .collect();
+            write!(f, " (ROAs for: {})", asns.join(", "))?;
+        } else if let Some(vrp) = &self.covering_vrp {
+            write!(
+                f,
+                " (covered by {} {}, maxlen {})",
+                vrp.asn(),
+                vrp.prefix(),
+                vrp.max_length()
+            )?;
+        }
+        Ok(())
     }
 }
 
 
-//------------ RoaImpact -----------------------------------------------------
+//------------ ValidationCache -----------------------------------------------
 
-pub struct VrpImpact {
-    unseen: bool
+/// Remembers the validated announcements, including the expected origins
+/// and the covering VRP for invalids, so that duplicate (prefix, origin)
+/// pairs are only validated once.
+#[derive(Debug, Default)]
+pub struct ValidationCache {
+    states: HashMap<Announcement, ValidatedAnnouncement>,
+    policy: ValidationPolicy,
+    hits: usize,
+    misses: usize
 }
 
-impl VrpImpact {
-    pub fn evaluate(vrp: &ValidatedRoaPayload, anns: &[&Announcement]) -> Self {
-        for ann in anns {
-            if vrp.asn() == ann.asn()
-               && vrp.contains(ann.prefix().as_ref())
-               && vrp.max_length() >= ann.prefix().length() {
-                return VrpImpact { unseen: false }
-            }
+impl ValidationCache {
+    pub fn with_policy(policy: ValidationPolicy) -> Self {
+        ValidationCache { policy, ..Default::default() }
+    }
+
+    /// Returns the validated announcement, using the cached state if this
+    /// announcement was seen before. Otherwise the matching vrps are
+    /// looked up using the given function, and the result is cached.
+    pub fn validate<'a, F>(
+        &mut self,
+        ann: &Announcement,
+        matching_vrps: F
+    ) -> ValidatedAnnouncement
+        where F: FnOnce() -> Vec<&'a ValidatedRoaPayload>
+    {
+        if let Some(validated) = self.states.get(ann) {
+            self.hits += 1;
+            return validated.clone()
         }
-        VrpImpact { unseen: true }
+
+        self.misses += 1;
+        let validated = ValidatedAnnouncement::create_with_policy(
+            ann,
+            &matching_vrps(),
+            self.policy
+        );
+        self.states.insert(ann.clone(), validated.clone());
+        validated
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+}
+
+
+//------------ VrpImpact -----------------------------------------------------
+
+/// The announcements that a VRP makes valid. A VRP that makes no
+/// announcement valid is 'unseen' in BGP, which may mean that it is stale.
+/// Keeps references to the announcements, which are kept in immutable
+/// IntervalTree structures.
+#[derive(Clone, Debug)]
+pub struct VrpImpact<'a> {
+    matching: Vec<&'a Announcement>
+}
+
+impl<'a> VrpImpact<'a> {
+    /// Evaluates which of the announcements the VRP makes valid. The
+    /// announcements are typically those contained by the VRP prefix.
+    pub fn evaluate(
+        vrp: &ValidatedRoaPayload,
+        anns: &[&'a Announcement]
+    ) -> Self {
+        Self::evaluate_with_policy(vrp, anns, ValidationPolicy::Strict)
+    }
+
+    /// Evaluates which of the announcements the VRP makes valid under the
+    /// given policy.
+    pub fn evaluate_with_policy(
+        vrp: &ValidatedRoaPayload,
+        anns: &[&'a Announcement],
+        policy: ValidationPolicy
+    ) -> Self {
+        let matching = anns.iter().cloned().filter(|ann| {
+            vrp.asn() == ann.asn()
+               && vrp.contains(ann.prefix().as_ref())
+               && policy.allows_length(vrp, ann)
+        }).collect();
+        VrpImpact { matching }
+    }
+
+    /// Returns the announcements that this VRP makes valid.
+    pub fn matching_announcements(&self) -> &[&'a Announcement] {
+        &self.matching
+    }
+
+    /// Returns the number of announcements that this VRP makes valid.
+    pub fn seen_count(&self) -> usize {
+        self.matching.len()
     }
 
     pub fn is_unseen(&self) -> bool {
-        self.unseen
+        self.matching.is_empty()
     }
 }
 
@@ -181,6 +362,148 @@ mod tests {
         }
     }
 
+    #[test]
+    fn should_list_expected_origins_for_invalid_asn() {
+        let ann = ann("65000, 192.168.0.0/20");
+
+        let vrp_inv_asn = vrp("AS65001, 192.168.0.0/16, 20");
+        let vrp_other = vrp("AS65002, 192.168.0.0/20, 20");
+        let vrp_not_fnd = vrp("AS65003, 192.168.0.0/24, 24");
+
+        let validated = ValidatedAnnouncement::create(
+            &ann,
+            &[&vrp_other, &vrp_inv_asn, &vrp_not_fnd, &vrp_inv_asn]
+        );
+        assert_eq!(&ValidationState::InvalidAsn, validated.state());
+        assert_eq!(
+            &[Asn::from(65001), Asn::from(65002)],
+            validated.expected_asns()
+        );
+        assert_eq!(
+            "AS65000 192.168.0.0/20 invalid asn (ROAs for: AS65001, AS65002)",
+            validated.to_string()
+        );
+
+        let mut cache = ValidationCache::default();
+        cache.validate(&ann, || vec![&vrp_inv_asn]);
+        let cached = cache.validate(&ann, Vec::new);
+        assert_eq!(1, cache.hits());
+        assert_eq!(&[Asn::from(65001)], cached.expected_asns());
+
+        // Only for invalid ASNs
+        let vrp_inv_len = vrp("AS65000, 192.168.0.0/16, 16");
+        let validated = ValidatedAnnouncement::create(
+            &ann,
+            &[&vrp_inv_len, &vrp_inv_asn]
+        );
+        assert_eq!(&ValidationState::InvalidLength, validated.state());
+        assert!(validated.expected_asns().is_empty());
+    }
+
+    #[test]
+    fn should_keep_covering_vrp_for_invalids() {
+        let ann = ann("65000, 192.168.0.0/20");
+
+        let vrp_valid   = vrp("AS65000, 192.168.0.0/20, 20");
+        let vrp_inv_len = vrp("AS65000, 192.168.0.0/16, 16");
+        let vrp_inv_asn = vrp("AS65001, 192.168.0.0/16, 20");
+        let vrp_other   = vrp("AS65002, 192.168.0.0/18, 20");
+
+        let covering_vrp = |vrps: &[&ValidatedRoaPayload]| {
+            ValidatedAnnouncement::create(&ann, vrps)
+                .covering_vrp()
+                .map(ValidatedRoaPayload::to_string)
+        };
+
+        // The VRP for the same origin decides, even if it comes last.
+        assert_eq!(
+            Some(vrp_inv_len.to_string()),
+            covering_vrp(&[&vrp_inv_asn, &vrp_inv_len])
+        );
+        assert_eq!(
+            Some(vrp_inv_asn.to_string()),
+            covering_vrp(&[&vrp_inv_asn, &vrp_other])
+        );
+        assert_eq!(None, covering_vrp(&[&vrp_inv_len, &vrp_valid]));
+        assert_eq!(None, covering_vrp(&[]));
+
+        let validated = ValidatedAnnouncement::create(&ann, &[&vrp_inv_len]);
+        assert_eq!(
+            "AS65000 192.168.0.0/20 invalid length \
+             (covered by AS65000 192.168.0.0/16, maxlen 16)",
+            validated.to_string()
+        );
+
+        let mut cache = ValidationCache::default();
+        cache.validate(&ann, || vec![&vrp_inv_len]);
+        let cached = cache.validate(&ann, Vec::new);
+        assert_eq!(
+            Some(vrp_inv_len.to_string()),
+            cached.covering_vrp().map(ValidatedRoaPayload::to_string)
+        );
+    }
+
+    #[test]
+    fn should_ignore_max_length_if_advisory() {
+        let ann = ann("65000, 192.168.0.0/20");
+
+        let vrp_inv_len = vrp("AS65000, 192.168.0.0/16, 16");
+        let vrp_inv_asn = vrp("AS65001, 192.168.0.0/16, 20");
+
+        let strict = ValidatedAnnouncement::create(&ann, &[&vrp_inv_len]);
+        assert_eq!(&ValidationState::InvalidLength, strict.state());
+
+        let advisory = ValidatedAnnouncement::create_with_policy(
+            &ann,
+            &[&vrp_inv_len],
+            ValidationPolicy::IgnoreMaxLength
+        );
+        assert_eq!(&ValidationState::Valid, advisory.state());
+
+        // The ASN must still match
+        let advisory = ValidatedAnnouncement::create_with_policy(
+            &ann,
+            &[&vrp_inv_asn],
+            ValidationPolicy::IgnoreMaxLength
+        );
+        assert_eq!(&ValidationState::InvalidAsn, advisory.state());
+
+        let mut cache = ValidationCache::with_policy(
+            ValidationPolicy::IgnoreMaxLength
+        );
+        let cached = cache.validate(&ann, || vec![&vrp_inv_len]);
+        assert_eq!(&ValidationState::Valid, cached.state());
+
+        assert!(VrpImpact::evaluate(&vrp_inv_len, &[&ann]).is_unseen());
+        assert!(!VrpImpact::evaluate_with_policy(
+            &vrp_inv_len,
+            &[&ann],
+            ValidationPolicy::IgnoreMaxLength
+        ).is_unseen());
+    }
+
+    #[test]
+    fn should_reuse_cached_validation() {
+        let vrp_inv_asn = vrp("AS65001, 192.168.0.0/16, 20");
+
+        let mut cache = ValidationCache::default();
+
+        let first = cache.validate(
+            &ann("65000, 192.168.0.0/20"),
+            || vec![&vrp_inv_asn]
+        );
+        let second = cache.validate(
+            &ann("65000, 192.168.0.0/20"),
+            || panic!("should use the cached state")
+        );
+        cache.validate(&ann("65001, 192.168.0.0/20"), || vec![&vrp_inv_asn]);
+
+        assert_eq!(1, cache.hits());
+        assert_eq!(2, cache.misses());
+        assert_eq!(&ValidationState::InvalidAsn, first.state());
+        assert_eq!(first.state(), second.state());
+    }
+
     #[test]
     fn should_detect_staleness() {
         let vrp_current = vrp("AS65000, 192.168.0.0/20, 20");
@@ -192,4 +515,25 @@ mod tests {
         assert!(!VrpImpact::evaluate(&vrp_current, &[&ann1, &ann2]).is_unseen());
         assert!(VrpImpact::evaluate(&vrp_stale, &[&ann1, &ann2]).is_unseen());
     }
+
+    #[test]
+    fn should_list_matching_announcements() {
+        let vrp = vrp("AS65000, 192.168.0.0/16, 24");
+
+        let ann_valid   = ann("65000, 192.168.0.0/20");
+        let ann_more    = ann("65000, 192.168.1.0/24");
+        let ann_inv_len = ann("65000, 192.168.2.0/25");
+        let ann_inv_asn = ann("65001, 192.168.3.0/24");
+        let anns = [&ann_valid, &ann_more, &ann_inv_len, &ann_inv_asn];
+
+        let seen = VrpImpact::evaluate(&vrp, &anns);
+        assert!(!seen.is_unseen());
+        assert_eq!(2, seen.seen_count());
+        assert_eq!(&[&ann_valid, &ann_more], seen.matching_announcements());
+
+        let unseen = VrpImpact::evaluate(&vrp, &[&ann_inv_len, &ann_inv_asn]);
+        assert!(unseen.is_unseen());
+        assert_eq!(0, unseen.seen_count());
+        assert!(unseen.matching_announcements().is_empty());
+    }
 }
\ No newline at end of file
@@ -1,4 +1,7 @@
 //! Parse ROAs.csv
+use crate::announcements::Announcement;
+use crate::input;
+use crate::input::Progress;
 use crate::ip::Asn;
 use crate::ip::AsnError;
 use crate::ip::IpPrefix;
@@ -7,11 +10,22 @@ use crate::ip::IpRange;
 use crate::ip::IpRangeTree;
 use crate::ip::IpRangeTreeBuilder;
 use crate::report::ScopeLimits;
+use crate::slurm;
+use crate::slurm::Slurm;
+use crate::validation::ValidatedAnnouncement;
+use crate::validation::ValidationPolicy;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use csv::ReaderBuilder;
+use csv::StringRecord;
+use csv::Trim;
+use serde::Serializer;
 use std::fmt;
 use std::fmt::Display;
-use std::fs::File;
+use std::fmt::Write;
 use std::io::BufRead;
-use std::io::BufReader;
+use std::io::Read;
 use std::num::ParseIntError;
 use std::path::Path;
 use std::str::FromStr;
@@ -23,9 +37,57 @@ pub struct ValidatedRoaPayload {
     asn: Asn,
     prefix: IpPrefix,
     max_length: u8,
+
+    /// The trust anchor, if known from the input.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ta: Option<String>,
+
+    /// The validity window of the certificates that the VRP was derived
+    /// from, if known from the input.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_timestamp"
+    )]
+    not_before: Option<NaiveDateTime>,
+
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_timestamp"
+    )]
+    not_after: Option<NaiveDateTime>,
 }
 
 impl ValidatedRoaPayload {
+    /// Creates a VRP. The max length is used as given, like it is when
+    /// reading a CSV file.
+    pub fn new(asn: Asn, prefix: IpPrefix, max_length: u8) -> Self {
+        ValidatedRoaPayload {
+            asn,
+            prefix,
+            max_length,
+            ta: None,
+            not_before: None,
+            not_after: None,
+        }
+    }
+
+    /// Sets the validity window of the VRP. Either end may be unknown.
+    pub fn with_validity(
+        mut self,
+        not_before: Option<NaiveDateTime>,
+        not_after: Option<NaiveDateTime>,
+    ) -> Self {
+        self.not_before = not_before;
+        self.not_after = not_after;
+        self
+    }
+
+    /// Sets the trust anchor that this VRP was derived under.
+    pub fn with_ta(mut self, ta: &str) -> Self {
+        self.ta = Some(ta.to_string());
+        self
+    }
+
     pub fn asn(&self) -> Asn {
         self.asn
     }
@@ -35,9 +97,100 @@ impl ValidatedRoaPayload {
     pub fn max_length(&self) -> u8 {
         self.max_length
     }
+    pub fn ta(&self) -> Option<&str> {
+        self.ta.as_deref()
+    }
+
+    pub fn not_before(&self) -> Option<NaiveDateTime> {
+        self.not_before
+    }
+    pub fn not_after(&self) -> Option<NaiveDateTime> {
+        self.not_after
+    }
+
+    /// Returns true if the VRP is known to have expired at the given time.
+    pub fn is_expired(&self, now: NaiveDateTime) -> bool {
+        self.not_after.is_some_and(|not_after| not_after < now)
+    }
+
+    /// Returns true if the VRP is not expired at the given time, but will
+    /// expire within the given window.
+    pub fn expires_within(&self, now: NaiveDateTime, window: Duration) -> bool {
+        self.not_after
+            .is_some_and(|not_after| now <= not_after && not_after <= now + window)
+    }
+
+    /// Returns true if the VRP is known to be not valid yet at the given
+    /// time.
+    pub fn is_not_yet_valid(&self, now: NaiveDateTime) -> bool {
+        self.not_before.is_some_and(|not_before| now < not_before)
+    }
+
+    /// Returns false if the max length is shorter than the prefix length.
+    /// Such a VRP can never make an announcement valid.
+    pub fn is_well_formed(&self) -> bool {
+        self.max_length >= self.prefix.length()
+    }
+
+    /// Returns an error if the VRP is not well formed.
+    fn check_well_formed(self) -> Result<Self, Error> {
+        if self.is_well_formed() {
+            Ok(self)
+        } else {
+            Err(Error::MalformedVrp(self.to_string()))
+        }
+    }
+
+    /// Returns true if this VRP was derived under the given trust anchor.
+    /// Names are compared case insensitively, because validators differ in
+    /// how they write them, e.g. 'arin' vs 'ARIN'.
+    pub fn is_from_ta(&self, ta: &str) -> bool {
+        match &self.ta {
+            Some(own) => own.eq_ignore_ascii_case(ta),
+            None => false,
+        }
+    }
 }
 
 impl ValidatedRoaPayload {
+    /// Parses the ASN, prefix and max length columns of a CSV record, and
+    /// the trust anchor and validity columns if present. Any further columns
+    /// are ignored.
+    fn from_record(record: &StringRecord, columns: &Columns) -> Result<Self, Error> {
+        let optional = |column: Option<usize>| {
+            column
+                .and_then(|column| record.get(column))
+                .filter(|value| !value.is_empty())
+        };
+
+        let asn_str = record.get(columns.asn).ok_or(Error::MissingColumn)?;
+        let asn = Asn::from_str(asn_str)?;
+
+        let prefix_str = record.get(columns.prefix).ok_or(Error::MissingColumn)?;
+        let prefix = IpPrefix::from_str(prefix_str)?;
+
+        let length_str = record.get(columns.max_length).ok_or(Error::MissingColumn)?;
+        let max_length = u8::from_str(length_str)?;
+        prefix.ip_address_family().check_prefix_length(max_length)?;
+
+        let not_before = match optional(columns.not_before) {
+            Some(s) => Some(parse_timestamp(s)?),
+            None => None,
+        };
+        let not_after = match optional(columns.not_after) {
+            Some(s) => Some(parse_timestamp(s)?),
+            None => None,
+        };
+
+        let vrp = ValidatedRoaPayload::new(asn, prefix, max_length)
+            .with_validity(not_before, not_after)
+            .check_well_formed()?;
+        match optional(columns.ta) {
+            Some(ta) => Ok(vrp.with_ta(ta)),
+            None => Ok(vrp),
+        }
+    }
+
     pub fn contains(&self, range: &IpRange) -> bool {
         self.prefix.as_ref().contains(&range.to_range())
     }
@@ -53,24 +206,9 @@ impl FromStr for ValidatedRoaPayload {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let line = s.replace("\"", "");
-        let line = line.replace(" ", "");
-        let mut values = line.split(',');
-
-        let asn_str = values.next().ok_or(Error::MissingColumn)?;
-        let asn = Asn::from_str(asn_str)?;
-
-        let prefix_str = values.next().ok_or(Error::MissingColumn)?;
-        let prefix = IpPrefix::from_str(prefix_str)?;
-
-        let length_str = values.next().ok_or(Error::MissingColumn)?;
-        let max_length = u8::from_str(length_str)?;
-
-        Ok(ValidatedRoaPayload {
-            asn,
-            prefix,
-            max_length,
-        })
+        let mut records = csv_reader(s.as_bytes()).into_records();
+        let record = records.next().ok_or(Error::MissingColumn)??;
+        Self::from_record(&record, &Columns::default())
     }
 }
 
@@ -92,20 +230,63 @@ pub struct Vrps {
 }
 
 impl Vrps {
+    /// Reads the VRPs from a CSV file, from a JSON Lines file if the
+    /// extension is 'jsonl' or 'ndjson', or from a JSON file as produced by
+    /// Routinator or rpki-client if the extension is 'json' or if the file
+    /// starts with a '{'. A path of '-' reads from stdin, see 'from_reader'.
     pub fn from_file(path: &Path) -> Result<Self, Error> {
-        let file = File::open(path).map_err(|_| Error::read_error(path))?;
-        let reader = BufReader::new(file);
+        Self::from_file_with_progress(path, Progress::Silent)
+    }
+
+    /// Like 'from_file', but logs the lines read if progress is enabled.
+    pub fn from_file_with_progress(path: &Path, progress: Progress) -> Result<Self, Error> {
+        let reader = input::open(path, progress).map_err(|_| Error::read_error(path))?;
 
+        let vrps = match path.extension().and_then(|e| e.to_str()) {
+            Some("jsonl") | Some("ndjson") => Self::from_ndjson(reader)?,
+            Some("json") => Self::from_json(reader)?,
+            _ => Self::from_reader(reader)?,
+        };
+
+        if vrps.tree.iter_values().next().is_none() {
+            return Err(Error::no_records(path));
+        }
+        Ok(vrps)
+    }
+
+    /// Reads the VRPs as JSON if the reader starts with a '{', and as CSV
+    /// otherwise.
+    pub fn from_reader<R: BufRead>(mut reader: R) -> Result<Self, Error> {
+        if Self::starts_with_object(&mut reader)? {
+            Self::from_json(reader)
+        } else {
+            Self::from_csv(reader)
+        }
+    }
+
+    /// Returns whether the first non-whitespace byte in the reader is a '{',
+    /// without consuming anything.
+    fn starts_with_object<R: BufRead>(reader: &mut R) -> Result<bool, Error> {
+        let buf = reader.fill_buf().map_err(Error::parse_error)?;
+        Ok(buf.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{'))
+    }
+
+    /// Reads VRPs from CSV, with or without a header.
+    fn from_csv<R: Read>(reader: R) -> Result<Self, Error> {
         let mut builder = IpRangeTreeBuilder::empty();
+        let mut columns = Columns::default();
 
-        for lres in reader.lines() {
-            let line = lres.map_err(Error::parse_error)?;
-            let line = line.replace("\"", "");
-            let line = line.replace(" ", "");
-            if line.starts_with("ASN") {
+        for record in csv_reader(reader).into_records() {
+            let record = record?;
+            if let Some(header) = Columns::from_header(&record)? {
+                columns = header;
                 continue;
             }
-            let vrp = ValidatedRoaPayload::from_str(&line)?;
+            let first = record.get(0).unwrap_or("");
+            if first.is_empty() {
+                continue;
+            }
+            let vrp = ValidatedRoaPayload::from_record(&record, &columns)?;
             builder.add(vrp);
         }
 
@@ -114,29 +295,116 @@ impl Vrps {
         })
     }
 
+    /// Reads VRPs from the JSON output of Routinator or rpki-client, i.e.
+    /// an object with a 'roas' list of VRP objects like in 'from_ndjson':
+    ///
+    ///   {"roas":[{"asn":"AS13335","prefix":"1.0.0.0/24","maxLength":24,"ta":"apnic"}]}
+    ///
+    /// Other keys, like the metadata from rpki-client, are ignored.
+    pub fn from_json<R: Read>(reader: R) -> Result<Self, Error> {
+        let json: JsonVrps = serde_json::from_reader(reader).map_err(Error::parse_error)?;
+
+        let mut builder = IpRangeTreeBuilder::empty();
+        for vrp in json.roas {
+            builder.add(vrp.into_vrp()?);
+        }
+
+        Ok(Vrps {
+            tree: builder.build(),
+        })
+    }
+
+    /// Reads VRPs from JSON Lines, i.e. one JSON object per line with the
+    /// 'asn', 'prefix' and 'maxLength', and optionally the trust anchor in
+    /// 'ta'. The ASN may be a number or a string like "AS65000". Empty lines
+    /// are skipped.
+    pub fn from_ndjson<R: BufRead>(reader: R) -> Result<Self, Error> {
+        let mut builder = IpRangeTreeBuilder::empty();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line.map_err(Error::parse_error)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let vrp: JsonVrp = serde_json::from_str(&line)
+                .map_err(|e| Error::parse_error(format!("line {}: {}", index + 1, e)))?;
+            builder.add(vrp.into_vrp()?);
+        }
+
+        Ok(Vrps {
+            tree: builder.build(),
+        })
+    }
+
+    /// Reads the VRPs from file, and applies the local exceptions from the
+    /// SLURM file, if given. The lines read from the VRPs are logged if
+    /// progress is enabled.
+    pub fn load(path: &Path, slurm: Option<&Path>, progress: Progress) -> Result<Self, Error> {
+        let vrps = Self::from_file_with_progress(path, progress)?;
+        match slurm {
+            Some(slurm) => Ok(vrps.apply_slurm(&Slurm::from_file(slurm)?)),
+            None => Ok(vrps),
+        }
+    }
+
+    /// Returns the VRPs without the ones matching the SLURM prefix filters,
+    /// and with the SLURM prefix assertions added.
+    pub fn apply_slurm(&self, slurm: &Slurm) -> Self {
+        let mut builder = IpRangeTreeBuilder::empty();
+
+        for vrp in self.iter().filter(|vrp| !slurm.is_filtered(vrp)) {
+            builder.add(vrp.clone());
+        }
+        for vrp in slurm.assertions() {
+            builder.add(vrp.clone());
+        }
+
+        Vrps {
+            tree: builder.build(),
+        }
+    }
+
     pub fn in_scope(&self, scope: &ScopeLimits) -> Vec<&ValidatedRoaPayload> {
-        let mut vrps = if scope.limits_ips() {
-            let set = scope.ips();
-            set.ranges()
-                .iter()
-                .flat_map(|range| self.contained_by(range))
-                .collect()
-        } else {
-            self.all()
+        self.iter_in_scope(scope).collect()
+    }
+
+    /// Iterates over the VRPs in scope. Without IP limits in the scope, this
+    /// does not collect the VRPs first.
+    pub fn iter_in_scope<'a, 's>(
+        &'a self,
+        scope: &'s ScopeLimits,
+    ) -> impl Iterator<Item = &'a ValidatedRoaPayload> + 's
+    where
+        'a: 's,
+    {
+        let asn_set = scope.asns();
+        let in_limits = move |vrp: &&ValidatedRoaPayload| {
+            (!scope.limits_asns() || asn_set.contains(vrp.asn()))
+                && scope.includes_family(vrp.prefix().ip_address_family())
         };
 
-        if scope.limits_asns() {
-            let set = scope.asns();
-            vrps.retain(|vrp| set.contains(vrp.asn()))
-        }
+        let (ranges, all) = if scope.limits_ips() {
+            (scope.ips().ranges().as_slice(), None)
+        } else {
+            (&[][..], Some(self.iter()))
+        };
 
-        vrps
+        ranges
+            .iter()
+            .flat_map(move |range| self.contained_by(range))
+            .chain(all.into_iter().flatten())
+            .filter(in_limits)
     }
 
     pub fn all(&self) -> Vec<&ValidatedRoaPayload> {
         self.tree.all()
     }
 
+    /// Iterates over all VRPs, without collecting them first.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = &'a ValidatedRoaPayload> + 'a {
+        self.tree.iter_values()
+    }
+
     pub fn containing(&self, range: &IpRange) -> Vec<&ValidatedRoaPayload> {
         self.tree.matching_or_less_specific(range)
     }
@@ -144,6 +412,194 @@ impl Vrps {
     pub fn contained_by(&self, range: &IpRange) -> Vec<&ValidatedRoaPayload> {
         self.tree.matching_or_more_specific(range)
     }
+
+    /// Returns all VRPs that overlap the given range.
+    pub fn intersecting(&self, range: &IpRange) -> Vec<&ValidatedRoaPayload> {
+        self.tree.intersecting(range)
+    }
+
+    /// Validates a single announcement against these VRPs.
+    ///
+    /// ```
+    /// use secure_routing_stats::announcements::Announcement;
+    /// use secure_routing_stats::ip::{Asn, IpPrefix};
+    /// use secure_routing_stats::validation::ValidationState;
+    /// use secure_routing_stats::vrps::Vrps;
+    /// use std::path::Path;
+    /// use std::str::FromStr;
+    ///
+    /// let vrps = Vrps::from_file(Path::new("test/small/vrps.csv")).unwrap();
+    /// let ann = Announcement::new(
+    ///     IpPrefix::from_str("10.0.0.0/16").unwrap(),
+    ///     Asn::from_str("AS65000").unwrap(),
+    /// );
+    /// assert_eq!(&ValidationState::Valid, vrps.validate(&ann).state());
+    /// ```
+    pub fn validate(&self, ann: &Announcement) -> ValidatedAnnouncement {
+        self.validate_with_policy(ann, ValidationPolicy::default())
+    }
+
+    /// Like 'validate', but with the given validation policy.
+    pub fn validate_with_policy(
+        &self,
+        ann: &Announcement,
+        policy: ValidationPolicy,
+    ) -> ValidatedAnnouncement {
+        let vrps = self.containing(ann.as_ref());
+        ValidatedAnnouncement::create_with_policy(ann, &vrps, policy)
+    }
+
+    /// Returns all VRPs in the CSV format understood by 'from_file', i.e.
+    /// with the header: ASN,IP Prefix,Max Length
+    pub fn to_csv(&self) -> String {
+        let mut s = String::new();
+        writeln!(s, "ASN,IP Prefix,Max Length").unwrap();
+        for vrp in self.all() {
+            writeln!(s, "{},{},{}", vrp.asn(), vrp.prefix(), vrp.max_length()).unwrap();
+        }
+        s
+    }
+}
+
+//------------ JsonVrps ------------------------------------------------------
+
+/// The VRPs as found in the JSON output of Routinator and rpki-client.
+#[derive(Deserialize)]
+struct JsonVrps {
+    roas: Vec<JsonVrp>,
+}
+
+//------------ JsonVrp -------------------------------------------------------
+
+/// A VRP as a JSON object, as found in the JSON output of validators.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonVrp {
+    asn: JsonAsn,
+    prefix: String,
+    max_length: u8,
+    ta: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonAsn {
+    Number(u32),
+    Text(String),
+}
+
+impl JsonVrp {
+    fn into_vrp(self) -> Result<ValidatedRoaPayload, Error> {
+        let asn = match self.asn {
+            JsonAsn::Number(number) => Asn::from(number),
+            JsonAsn::Text(text) => Asn::from_str(&text)?,
+        };
+        let prefix = IpPrefix::from_str(&self.prefix)?;
+        prefix
+            .ip_address_family()
+            .check_prefix_length(self.max_length)?;
+
+        let vrp = ValidatedRoaPayload::new(asn, prefix, self.max_length).check_well_formed()?;
+        match self.ta {
+            Some(ta) => Ok(vrp.with_ta(&ta)),
+            None => Ok(vrp),
+        }
+    }
+}
+
+//------------ Columns -------------------------------------------------------
+
+/// The positions of the columns in a VRP CSV file. Files without a header
+/// have the columns: ASN, IP Prefix, Max Length, Trust Anchor. Any further
+/// columns are ignored, the validity is only read from the columns named
+/// in a header. A header can give a different order, e.g. the 'csvext'
+/// format of Routinator starts with the URI of the ROA.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Columns {
+    asn: usize,
+    prefix: usize,
+    max_length: usize,
+    ta: Option<usize>,
+    not_before: Option<usize>,
+    not_after: Option<usize>,
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Columns {
+            asn: 0,
+            prefix: 1,
+            max_length: 2,
+            ta: Some(3),
+            not_before: None,
+            not_after: None,
+        }
+    }
+}
+
+impl Columns {
+    /// Returns the columns if the record is a header, i.e. it has a column
+    /// named 'ASN', or None if it is not. Column names are matched case
+    /// insensitively, and the names used by different validators are
+    /// accepted. A header without a prefix or max length column is an error.
+    fn from_header(record: &StringRecord) -> Result<Option<Self>, Error> {
+        let find = |names: &[&str]| {
+            record
+                .iter()
+                .position(|field| names.iter().any(|name| field.eq_ignore_ascii_case(name)))
+        };
+        let required = |names: &[&str]| {
+            find(names).ok_or_else(|| Error::MissingHeaderColumn(names[0].to_string()))
+        };
+
+        let asn = match find(&["ASN", "AS Number", "Origin AS"]) {
+            Some(asn) => asn,
+            None => return Ok(None),
+        };
+
+        Ok(Some(Columns {
+            asn,
+            prefix: required(&["IP Prefix", "Prefix", "IPPrefix"])?,
+            max_length: required(&["Max Length", "maxLength", "MaxLen", "Max-Length"])?,
+            ta: find(&["Trust Anchor", "TA"]),
+            not_before: find(&["Not Before"]),
+            not_after: find(&["Not After", "Expires"]),
+        }))
+    }
+}
+
+/// Parses a timestamp as found in VRP exports, in UTC. Accepts RFC 3339
+/// style "2019-03-04T10:00:00Z", "2019-03-04 10:00:00", or only a date,
+/// which is taken as the start of that day.
+pub fn parse_timestamp(s: &str) -> Result<NaiveDateTime, Error> {
+    let s = s.trim_end_matches('Z');
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|d| d.and_hms_opt(0, 0, 0).unwrap())
+        })
+        .map_err(|_| Error::parse_error(format!("invalid timestamp: {}", s)))
+}
+
+fn serialize_timestamp<S>(t: &Option<NaiveDateTime>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match t {
+        Some(t) => serializer.serialize_str(&t.format("%Y-%m-%dT%H:%M:%SZ").to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Returns a CSV reader that handles quoted fields, and trims whitespace
+/// around fields. The header is skipped by 'from_file' rather than by the
+/// reader, so that files without a header can be read as well.
+fn csv_reader<R: Read>(reader: R) -> csv::Reader<R> {
+    ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(Trim::All)
+        .from_reader(reader)
 }
 
 //------------ Error --------------------------------------------------------
@@ -156,14 +612,29 @@ pub enum Error {
     #[display(fmt = "Missing column in roas.csv")]
     MissingColumn,
 
+    #[display(fmt = "Missing column in the header of roas.csv: {}", _0)]
+    MissingHeaderColumn(String),
+
     #[display(fmt = "Error parsing ROAs.csv: {}", _0)]
     ParseError(String),
+
+    #[display(fmt = "Max length is shorter than the prefix length for: {}", _0)]
+    MalformedVrp(String),
+
+    #[display(fmt = "No usable records in: {}", _0)]
+    NoRecords(String),
+
+    #[display(fmt = "{}", _0)]
+    SlurmError(slurm::Error),
 }
 
 impl Error {
     fn read_error(path: &Path) -> Self {
         Error::CannotRead(path.to_string_lossy().to_string())
     }
+    fn no_records(path: &Path) -> Self {
+        Error::NoRecords(path.to_string_lossy().to_string())
+    }
     fn parse_error(e: impl Display) -> Self {
         Error::ParseError(format!("{}", e))
     }
@@ -181,6 +652,18 @@ impl From<ParseIntError> for Error {
     }
 }
 
+impl From<csv::Error> for Error {
+    fn from(e: csv::Error) -> Self {
+        Error::parse_error(e)
+    }
+}
+
+impl From<slurm::Error> for Error {
+    fn from(e: slurm::Error) -> Self {
+        Error::SlurmError(e)
+    }
+}
+
 impl From<AsnError> for Error {
     fn from(e: AsnError) -> Self {
         Error::parse_error(e)
@@ -192,6 +675,10 @@ impl From<AsnError> for Error {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::validation::ValidationState;
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::io::Cursor;
     use std::path::PathBuf;
 
     #[test]
@@ -199,4 +686,293 @@ mod tests {
         let path = PathBuf::from("test/20190304/vrps.csv");
         Vrps::from_file(&path).unwrap();
     }
+
+    fn sorted_strings(vrps: &Vrps) -> Vec<String> {
+        let mut res: Vec<String> = vrps.all().iter().map(|v| v.to_string()).collect();
+        res.sort();
+        res
+    }
+
+    #[test]
+    fn should_validate_against_constructed_vrp() {
+        use crate::announcements::Announcement;
+        use crate::validation::ValidatedAnnouncement;
+        use crate::validation::ValidationState;
+
+        let prefix = IpPrefix::from_str("10.0.0.0/16").unwrap();
+        let vrp = ValidatedRoaPayload::new(Asn::from(65000), prefix.clone(), 24);
+        assert_eq!(Asn::from(65000), vrp.asn());
+        assert_eq!(&prefix, vrp.prefix());
+        assert_eq!(24, vrp.max_length());
+
+        let state = |asn, prefix| {
+            let ann = Announcement::new(IpPrefix::from_str(prefix).unwrap(), Asn::from(asn));
+            ValidatedAnnouncement::create(&ann, &[&vrp]).state().clone()
+        };
+        assert_eq!(ValidationState::Valid, state(65000, "10.0.1.0/24"));
+        assert_eq!(ValidationState::InvalidLength, state(65000, "10.0.1.0/25"));
+        assert_eq!(ValidationState::InvalidAsn, state(65001, "10.0.1.0/24"));
+    }
+
+    #[test]
+    fn should_reject_max_length_shorter_than_prefix() {
+        let prefix = IpPrefix::from_str("10.0.0.0/24").unwrap();
+        assert!(!ValidatedRoaPayload::new(Asn::from(1), prefix.clone(), 16).is_well_formed());
+        assert!(ValidatedRoaPayload::new(Asn::from(1), prefix, 24).is_well_formed());
+
+        match ValidatedRoaPayload::from_str("AS1, 10.0.0.0/24, 16") {
+            Err(Error::MalformedVrp(vrp)) => {
+                assert_eq!("AS: AS1, Prefix: 10.0.0.0/24, Max Length: 16", vrp)
+            }
+            _ => panic!("Expected malformed VRP error"),
+        }
+
+        let json = r#"{"roas":[{"asn":"AS1","prefix":"10.0.0.0/24","maxLength":16}]}"#;
+        match Vrps::from_json(json.as_bytes()) {
+            Err(Error::MalformedVrp(_)) => {}
+            _ => panic!("Expected malformed VRP error"),
+        }
+    }
+
+    #[test]
+    fn should_read_quoted_fields() {
+        let vrps = Vrps::from_file(&PathBuf::from("test/quoted/vrps.csv")).unwrap();
+        assert_eq!(
+            vec![
+                "AS: AS65000, Prefix: 10.0.0.0/16, Max Length: 16",
+                "AS: AS65001, Prefix: 10.1.0.0/16, Max Length: 24",
+                "AS: AS65002, Prefix: 2001:db8::/32, Max Length: 48",
+            ],
+            sorted_strings(&vrps)
+        );
+
+        let vrp = ValidatedRoaPayload::from_str("\"AS65000\",\"10.0.0.0/16\",\"16\"").unwrap();
+        assert_eq!(16, vrp.max_length());
+        assert_eq!(None, vrp.ta());
+
+        let vrp = ValidatedRoaPayload::from_str("AS65000,10.0.0.0/16,16,\"ripe ncc\"").unwrap();
+        assert_eq!(Some("ripe ncc"), vrp.ta());
+        assert!(vrp.is_from_ta("RIPE NCC"));
+        assert!(!vrp.is_from_ta("arin"));
+
+        assert!(ValidatedRoaPayload::from_str("AS65000,10.0.0.0/16,40").is_err());
+        assert!(ValidatedRoaPayload::from_str("AS65000,2001:db8::/32,129").is_err());
+    }
+
+    #[test]
+    fn should_find_columns_in_header() {
+        let header = |fields: &[&str]| Columns::from_header(&StringRecord::from(fields.to_vec()));
+
+        let ripe = header(&["ASN", "IP Prefix", "Max Length", "Trust Anchor"]).unwrap();
+        let expected = Columns {
+            not_before: None,
+            not_after: None,
+            ..Columns::default()
+        };
+        assert_eq!(Some(expected), ripe);
+
+        let csvext = header(&[
+            "URI",
+            "ASN",
+            "IP Prefix",
+            "Max Length",
+            "Not Before",
+            "Not After",
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!((1, 2, 3), (csvext.asn, csvext.prefix, csvext.max_length));
+        assert_eq!(
+            (None, Some(4), Some(5)),
+            (csvext.ta, csvext.not_before, csvext.not_after)
+        );
+
+        let variant = header(&["asn", "prefix", "maxLength", "ta"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (0, 1, 2, Some(3)),
+            (variant.asn, variant.prefix, variant.max_length, variant.ta)
+        );
+
+        assert_eq!(None, header(&["AS65000", "10.0.0.0/16", "16"]).unwrap());
+        match header(&["ASN", "IP Prefix", "Trust Anchor"]) {
+            Err(Error::MissingHeaderColumn(column)) => assert_eq!("Max Length", column),
+            _ => panic!("Expected MissingHeaderColumn error"),
+        }
+        match header(&["ASN", "Max Length"]) {
+            Err(Error::MissingHeaderColumn(column)) => assert_eq!("IP Prefix", column),
+            _ => panic!("Expected MissingHeaderColumn error"),
+        }
+    }
+
+    #[test]
+    fn should_read_file_without_header() {
+        let vrps = Vrps::from_file(&PathBuf::from("test/noheader/vrps.csv")).unwrap();
+        assert_eq!(
+            vec![
+                "AS: AS65000, Prefix: 10.0.0.0/16, Max Length: 16",
+                "AS: AS65001, Prefix: 2001:db8::/32, Max Length: 48",
+            ],
+            sorted_strings(&vrps)
+        );
+        assert!(vrps.iter().all(|vrp| vrp.ta().is_some()));
+    }
+
+    #[test]
+    fn should_read_ndjson() {
+        let expected = vec![
+            "AS: AS65000, Prefix: 10.0.0.0/16, Max Length: 16",
+            "AS: AS65001, Prefix: 10.1.0.0/16, Max Length: 24",
+            "AS: AS65002, Prefix: 2001:db8::/32, Max Length: 48",
+        ];
+
+        let path = PathBuf::from("test/ndjson/vrps.jsonl");
+        let file = File::open(&path).unwrap();
+        let vrps = Vrps::from_ndjson(BufReader::new(file)).unwrap();
+        assert_eq!(expected, sorted_strings(&vrps));
+
+        let tas: Vec<Option<&str>> = {
+            let mut vrps: Vec<&ValidatedRoaPayload> = vrps.iter().collect();
+            vrps.sort_by_key(|vrp| vrp.asn());
+            vrps.iter().map(|vrp| vrp.ta()).collect()
+        };
+        assert_eq!(vec![Some("ripe"), None, Some("arin")], tas);
+
+        assert_eq!(expected, sorted_strings(&Vrps::from_file(&path).unwrap()));
+
+        let invalid = "{\"asn\": 65000, \"prefix\": \"10.0.0.0/16\", \"maxLength\": 33}";
+        assert!(Vrps::from_ndjson(invalid.as_bytes()).is_err());
+        match Vrps::from_ndjson("\n{\"asn\": 65000}".as_bytes()) {
+            Err(Error::ParseError(msg)) => assert!(msg.starts_with("line 2:")),
+            _ => panic!("Expected ParseError"),
+        }
+    }
+
+    #[test]
+    fn should_read_json() {
+        let csv = Vrps::from_file(&PathBuf::from("test/small/vrps.csv")).unwrap();
+        let json = Vrps::from_file(&PathBuf::from("test/json/vrps.json")).unwrap();
+        assert_eq!(sorted_strings(&csv), sorted_strings(&json));
+
+        // Files without the 'json' extension are recognised by their content.
+        let path = ::std::env::temp_dir().join("secure_routing_stats_vrps_export.txt");
+        ::std::fs::copy("test/json/vrps.json", &path).unwrap();
+        let sniffed = Vrps::from_file(&path);
+        ::std::fs::remove_file(&path).unwrap();
+        assert_eq!(sorted_strings(&csv), sorted_strings(&sniffed.unwrap()));
+
+        // The full set, as if it were exported by a validator as JSON.
+        let csv = Vrps::from_file(&PathBuf::from("test/20190304/vrps.csv")).unwrap();
+        let roas: Vec<String> = csv
+            .iter()
+            .map(|vrp| {
+                format!(
+                    "{{\"asn\":\"{}\",\"prefix\":\"{}\",\"maxLength\":{},\"ta\":\"{}\"}}",
+                    vrp.asn(),
+                    vrp.prefix(),
+                    vrp.max_length(),
+                    vrp.ta().unwrap()
+                )
+            })
+            .collect();
+        let json = format!("{{\"roas\":[{}]}}", roas.join(","));
+        let json = Vrps::from_json(json.as_bytes()).unwrap();
+        assert_eq!(sorted_strings(&csv), sorted_strings(&json));
+
+        assert!(Vrps::from_json("{\"vrps\":[]}".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn should_reject_files_without_vrps() {
+        for path in &["test/empty/empty", "test/empty/vrps.csv"] {
+            match Vrps::from_file(&PathBuf::from(path)) {
+                Err(Error::NoRecords(p)) => assert_eq!(*path, p),
+                _ => panic!("Expected NoRecords error for {}", path),
+            }
+        }
+    }
+
+    #[test]
+    fn should_reload_exported_csv() {
+        let vrps = Vrps::from_file(&PathBuf::from("test/small/vrps.csv")).unwrap();
+
+        let path = ::std::env::temp_dir().join("secure_routing_stats_vrps_export.csv");
+        ::std::fs::write(&path, vrps.to_csv()).unwrap();
+        let reloaded = Vrps::from_file(&path).unwrap();
+        ::std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(3, reloaded.all().len());
+        assert_eq!(sorted_strings(&vrps), sorted_strings(&reloaded));
+        assert_eq!(vrps.to_csv(), reloaded.to_csv());
+    }
+
+    #[test]
+    fn should_ignore_extra_columns_without_header() {
+        let csv = "AS65000,10.0.0.0/16,24,ripe,some,extra,columns\n\
+                   AS65001,10.1.0.0/16,16,arin,,not a date\n";
+        let vrps = Vrps::from_reader(Cursor::new(csv)).unwrap();
+        assert_eq!(
+            vec![
+                "AS: AS65000, Prefix: 10.0.0.0/16, Max Length: 24",
+                "AS: AS65001, Prefix: 10.1.0.0/16, Max Length: 16",
+            ],
+            sorted_strings(&vrps)
+        );
+        assert!(vrps.iter().all(|vrp| vrp.not_before().is_none()));
+        assert!(vrps.iter().all(|vrp| vrp.not_after().is_none()));
+    }
+
+    #[test]
+    fn should_read_from_reader() {
+        let csv = "ASN,IP Prefix,Max Length\nAS65000,10.0.0.0/16,24\n";
+        let vrps = Vrps::from_reader(Cursor::new(csv)).unwrap();
+        assert_eq!(
+            vec!["AS: AS65000, Prefix: 10.0.0.0/16, Max Length: 24"],
+            sorted_strings(&vrps)
+        );
+
+        let json = r#"  {"roas":[{"asn":"AS65000","prefix":"10.0.0.0/16","maxLength":24}]}"#;
+        let vrps = Vrps::from_reader(Cursor::new(json)).unwrap();
+        assert_eq!(
+            vec!["AS: AS65000, Prefix: 10.0.0.0/16, Max Length: 24"],
+            sorted_strings(&vrps)
+        );
+    }
+
+    #[test]
+    fn should_validate_announcement() {
+        let vrps = Vrps::from_file(&PathBuf::from("test/small/vrps.csv")).unwrap();
+        let ann = |prefix, asn| {
+            Announcement::new(
+                IpPrefix::from_str(prefix).unwrap(),
+                Asn::from_str(asn).unwrap(),
+            )
+        };
+        let state = |ann, policy| vrps.validate_with_policy(&ann, policy).state().clone();
+
+        assert_eq!(
+            &ValidationState::Valid,
+            vrps.validate(&ann("10.0.0.0/16", "AS65000")).state()
+        );
+        assert_eq!(
+            &ValidationState::InvalidAsn,
+            vrps.validate(&ann("10.0.0.0/16", "AS65001")).state()
+        );
+        assert_eq!(
+            &ValidationState::NotFound,
+            vrps.validate(&ann("172.16.0.0/24", "AS65000")).state()
+        );
+        assert_eq!(
+            ValidationState::InvalidLength,
+            state(ann("10.0.1.0/24", "AS65000"), ValidationPolicy::Strict)
+        );
+        assert_eq!(
+            ValidationState::Valid,
+            state(
+                ann("10.0.1.0/24", "AS65000"),
+                ValidationPolicy::IgnoreMaxLength
+            )
+        );
+    }
 }